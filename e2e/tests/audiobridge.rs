@@ -21,7 +21,7 @@ use jarust::plugins::audio_bridge::params::AudioBridgeMuteParams;
 use jarust::plugins::audio_bridge::params::AudioBridgeMuteRoomParams;
 use jarust::plugins::JanusId;
 use std::time::Duration;
-use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::Receiver;
 
 // Allowing unused labels so we can use labels to have named blocks for test cases
 // I feel it's better than comments
@@ -299,12 +299,8 @@ async fn participants_e2e() {
 
         assert_eq!(room, room_id, "Eve should join correct room");
         assert_eq!(participants.len(), 2, "Alice and Bob should be in room");
-        assert_eq!(
-            participants.contains(&alice),
-            true,
-            "Alice should be in room"
-        );
-        assert_eq!(participants.contains(&bob), true, "Bob should be in room");
+        assert!(participants.contains(&alice), "Alice should be in room");
+        assert!(participants.contains(&bob), "Bob should be in room");
 
         AudioBridgeParticipant {
             id,
@@ -376,13 +372,12 @@ async fn participants_e2e() {
             panic!("Alice received unexpected event")
         };
 
-        assert_eq!(
+        assert!(
             participants
                 .iter()
                 .find(|p| p.id == alice.id)
                 .expect("Alice not found")
-                .muted,
-            true
+                .muted
         );
 
         // Bob should receive the mute event of Alice
@@ -397,13 +392,12 @@ async fn participants_e2e() {
             panic!("Bob received unexpected event")
         };
 
-        assert_eq!(
+        assert!(
             participants
                 .iter()
                 .find(|p| p.id == alice.id)
                 .expect("Alice not found")
-                .muted,
-            true
+                .muted
         );
 
         // Eve should receive the mute event of Alice
@@ -418,13 +412,12 @@ async fn participants_e2e() {
             panic!("Eve received unexpected event")
         };
 
-        assert_eq!(
+        assert!(
             participants
                 .iter()
                 .find(|p| p.id == alice.id)
                 .expect("Alice not found")
-                .muted,
-            true
+                .muted
         );
     }
 
@@ -450,13 +443,12 @@ async fn participants_e2e() {
             panic!("Alice received unexpected event")
         };
 
-        assert_eq!(
-            participants
+        assert!(
+            !participants
                 .iter()
                 .find(|p| p.id == alice.id)
                 .expect("Alice not found")
-                .muted,
-            false
+                .muted
         );
 
         // Bob should receive the unmute event of Alice
@@ -471,13 +463,12 @@ async fn participants_e2e() {
             panic!("Bob received unexpected event")
         };
 
-        assert_eq!(
-            participants
+        assert!(
+            !participants
                 .iter()
                 .find(|p| p.id == alice.id)
                 .expect("Alice not found")
-                .muted,
-            false
+                .muted
         );
 
         // Eve should receive the unmute event of Alice
@@ -492,13 +483,12 @@ async fn participants_e2e() {
             panic!("Eve received unexpected event")
         };
 
-        assert_eq!(
-            participants
+        assert!(
+            !participants
                 .iter()
                 .find(|p| p.id == alice.id)
                 .expect("Alice not found")
-                .muted,
-            false
+                .muted
         );
     }
 
@@ -534,7 +524,7 @@ async fn participants_e2e() {
             .find(|p| p.id == eve.id)
             .expect("Eve not found");
 
-        assert_eq!(eve.muted, true);
+        assert!(eve.muted);
         assert_eq!(eve.display, Some(new_display.clone()));
 
         // Bob should receive the mute event of Eve
@@ -554,7 +544,7 @@ async fn participants_e2e() {
             .find(|p| p.id == eve.id)
             .expect("Eve not found");
 
-        assert_eq!(eve.muted, true);
+        assert!(eve.muted);
         assert_eq!(eve.display, Some(new_display.clone()));
 
         // Eve should not receive muted event, instead it receives `"result": "ok"`
@@ -587,7 +577,7 @@ async fn participants_e2e() {
         else {
             panic!("Alice received unexpected event")
         };
-        assert_eq!(muted, true);
+        assert!(muted);
 
         // Bob should receive the mute event of all participants
         let PluginEvent::AudioBridgeEvent(AudioBridgeEvent::RoomMuteUpdated { muted, .. }) =
@@ -598,7 +588,7 @@ async fn participants_e2e() {
         else {
             panic!("Bob received unexpected event")
         };
-        assert_eq!(muted, true);
+        assert!(muted);
 
         // Eve should receive the mute event of all participants
         let PluginEvent::AudioBridgeEvent(AudioBridgeEvent::RoomMuteUpdated { muted, .. }) =
@@ -609,7 +599,7 @@ async fn participants_e2e() {
         else {
             panic!("Eve received unexpected event")
         };
-        assert_eq!(muted, true);
+        assert!(muted);
     }
 
     'unmute_room: {
@@ -630,7 +620,7 @@ async fn participants_e2e() {
         else {
             panic!("Alice received unexpected event")
         };
-        assert_eq!(muted, false);
+        assert!(!muted);
 
         // Bob should receive the unmute event of all participants
         let PluginEvent::AudioBridgeEvent(AudioBridgeEvent::RoomMuteUpdated { muted, .. }) =
@@ -641,7 +631,7 @@ async fn participants_e2e() {
         else {
             panic!("Bob received unexpected event")
         };
-        assert_eq!(muted, false);
+        assert!(!muted);
 
         // Eve should receive the unmute event of all participants
         let PluginEvent::AudioBridgeEvent(AudioBridgeEvent::RoomMuteUpdated { muted, .. }) =
@@ -652,7 +642,7 @@ async fn participants_e2e() {
         else {
             panic!("Eve received unexpected event")
         };
-        assert_eq!(muted, false);
+        assert!(!muted);
     }
 
     'list_participants: {
@@ -668,15 +658,10 @@ async fn participants_e2e() {
             .participants;
 
         assert_eq!(participants.len(), 3);
-        assert_eq!(
-            participants.contains(&alice),
-            true,
-            "Alice should be in room"
-        );
-        assert_eq!(participants.contains(&bob), true, "Bob should be in room");
-        assert_eq!(
+        assert!(participants.contains(&alice), "Alice should be in room");
+        assert!(participants.contains(&bob), "Bob should be in room");
+        assert!(
             participants.iter().any(|p| p.id == eve.id),
-            true,
             "Eve should be in room"
         );
     }
@@ -784,7 +769,7 @@ async fn participants_e2e() {
             .await
             .expect("Failed to list participants");
         assert_eq!(participants.participants.len(), 2);
-        assert_eq!(participants.participants.contains(&bob), false);
+        assert!(!participants.participants.contains(&bob));
     }
 
     // Bob rejoins
@@ -884,7 +869,7 @@ async fn participants_e2e() {
             .await
             .expect("Failed to list participants");
         assert_eq!(participants.participants.len(), 2);
-        assert_eq!(participants.participants.contains(&bob), false);
+        assert!(!participants.participants.contains(&bob));
     }
 
     'kick_all: {
@@ -924,12 +909,13 @@ async fn participants_e2e() {
     }
 }
 
-async fn make_audiobridge_attachment() -> (AudioBridgeHandle, UnboundedReceiver<PluginEvent>) {
+async fn make_audiobridge_attachment() -> (AudioBridgeHandle, Receiver<PluginEvent>) {
     let config = JaConfig {
         url: "ws://localhost:8188/ws".to_string(),
         apisecret: None,
         server_root: "janus".to_string(),
         capacity: 32,
+        ..Default::default()
     };
     let mut connection =
         jarust::core::connect(config, JanusAPI::WebSocket, RandomTransactionGenerator)
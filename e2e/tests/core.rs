@@ -16,6 +16,7 @@ async fn it_websocket_core_tests() {
         apisecret: None,
         server_root: "janus".to_string(),
         capacity: 32,
+        ..Default::default()
     };
     let mut connection = connect(config, JanusAPI::WebSocket, RandomTransactionGenerator)
         .await
@@ -14,6 +14,7 @@ async fn echotest_ws_e2e() {
         apisecret: None,
         server_root: "janus".to_string(),
         capacity: 32,
+        ..Default::default()
     };
     let mut connection =
         jarust::core::connect(config, JanusAPI::WebSocket, RandomTransactionGenerator)
@@ -65,6 +66,7 @@ async fn echotest_rest_e2e() {
         apisecret: None,
         server_root: "janus".to_string(),
         capacity: 32,
+        ..Default::default()
     };
     let mut connection =
         jarust::core::connect(config, JanusAPI::Restful, RandomTransactionGenerator)
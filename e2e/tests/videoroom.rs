@@ -9,7 +9,7 @@ use jarust::plugins::video_room::params::VideoRoomEditParamsOptional;
 use jarust::plugins::video_room::params::VideoRoomExistsParams;
 use jarust::plugins::JanusId;
 use std::time::Duration;
-use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::Receiver;
 
 #[allow(unused_labels)]
 #[tokio::test]
@@ -146,12 +146,13 @@ async fn room_crud_e2e() {
     }
 }
 
-async fn make_videoroom_attachment() -> (VideoRoomHandle, UnboundedReceiver<PluginEvent>) {
+async fn make_videoroom_attachment() -> (VideoRoomHandle, Receiver<PluginEvent>) {
     let config = JaConfig {
         url: "ws://localhost:8188/ws".to_string(),
         apisecret: None,
         server_root: "janus".to_string(),
         capacity: 32,
+        ..Default::default()
     };
     let mut connection =
         jarust::core::connect(config, JanusAPI::WebSocket, RandomTransactionGenerator)
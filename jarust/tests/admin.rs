@@ -0,0 +1,91 @@
+mod fixtures;
+mod mocks;
+
+#[cfg(test)]
+mod tests {
+    use crate::mocks::mock_admin_interface::MockAdminInterface;
+    use crate::mocks::mock_generate_transaction::MockGenerateTransaction;
+    use jarust::core::custom_connect_admin;
+    use jarust::interface::admin::admin_interface::JanusAdminInterface;
+    use jarust::interface::janus_interface::ConnectionParams;
+    use jarust::interface::Error;
+    use serde_json::json;
+    use std::time::Duration;
+
+    async fn make_interface() -> MockAdminInterface {
+        let conn_params = ConnectionParams {
+            url: "mock://some.janus.com".to_string(),
+            server_root: "admin".to_string(),
+            admin_secret: Some("overlord".to_string()),
+            ..Default::default()
+        };
+        MockAdminInterface::make_admin_interface(conn_params, MockGenerateTransaction::new())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_successfully_lists_handles() {
+        let interface = make_interface().await;
+        let admin = custom_connect_admin(interface.clone()).await.unwrap();
+        interface
+            .mock_rsp(Ok(json!({
+                "janus": "success",
+                "transaction": "abc123",
+                "session_id": 73,
+                "handles": [1, 2]
+            })))
+            .await;
+
+        let handles = admin
+            .list_handles(73, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert_eq!(handles, vec![1, 2]);
+        let requests = interface.requests().await;
+        assert_eq!(requests[0].session_id, Some(73));
+        assert_eq!(requests[0].handle_id, None);
+        assert_eq!(requests[0].body, json!({"janus": "list_handles"}));
+    }
+
+    #[tokio::test]
+    async fn it_successfully_messages_a_plugin() {
+        let interface = make_interface().await;
+        let admin = custom_connect_admin(interface.clone()).await.unwrap();
+        interface
+            .mock_rsp(Ok(json!({
+                "janus": "success",
+                "transaction": "abc123",
+                "response": {"videoroom": "success", "list": []}
+            })))
+            .await;
+
+        let rsp = admin
+            .message_plugin::<serde_json::Value>(
+                "janus.plugin.videoroom".to_string(),
+                json!({"request": "list"}),
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(rsp, json!({"videoroom": "success", "list": []}));
+        let requests = interface.requests().await;
+        assert_eq!(requests[0].body["plugin"], "janus.plugin.videoroom");
+        assert_eq!(requests[0].body["request"], json!({"request": "list"}));
+    }
+
+    #[tokio::test]
+    async fn it_fails_to_parse_an_unexpected_response() {
+        let interface = make_interface().await;
+        let admin = custom_connect_admin(interface.clone()).await.unwrap();
+        interface
+            .mock_rsp(Ok(json!({"janus": "success", "transaction": "abc123"})))
+            .await;
+
+        let result = admin.list_sessions(Duration::from_secs(5)).await;
+
+        assert!(matches!(result, Err(Error::UnexpectedResponse)));
+    }
+}
@@ -0,0 +1,67 @@
+use async_trait::async_trait;
+use jarust::core::GenerateTransaction;
+use jarust::interface::admin::admin_interface::AdminRequest;
+use jarust::interface::admin::admin_interface::JanusAdminInterface;
+use jarust::interface::error::Error;
+use jarust::interface::janus_interface::ConnectionParams;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Default)]
+pub struct Exclusive {
+    response: Option<Result<Value, Error>>,
+    requests: Vec<AdminRequest>,
+}
+
+#[derive(Debug, Default)]
+pub struct InnerMockAdminInterface {
+    exclusive: Mutex<Exclusive>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct MockAdminInterface {
+    inner: Arc<InnerMockAdminInterface>,
+}
+
+#[allow(dead_code)]
+impl MockAdminInterface {
+    pub async fn mock_rsp(&self, rsp: Result<Value, Error>) {
+        self.inner.exclusive.lock().await.response = Some(rsp);
+    }
+
+    pub async fn requests(&self) -> Vec<AdminRequest> {
+        self.inner.exclusive.lock().await.requests.clone()
+    }
+}
+
+#[async_trait]
+impl JanusAdminInterface for MockAdminInterface {
+    async fn make_admin_interface(
+        _: ConnectionParams,
+        _: impl GenerateTransaction,
+    ) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        Ok(Self::default())
+    }
+
+    async fn internal_send_admin_request(
+        &self,
+        request: AdminRequest,
+        _timeout: Duration,
+    ) -> Result<Value, Error> {
+        let mut exclusive = self.inner.exclusive.lock().await;
+        exclusive.requests.push(request);
+        let Some(rsp) = exclusive.response.take() else {
+            panic!("Admin response is not set");
+        };
+        rsp
+    }
+
+    fn name(&self) -> Box<str> {
+        "Mock Admin Interface".to_string().into_boxed_str()
+    }
+}
@@ -2,6 +2,10 @@ use async_trait::async_trait;
 use jarust::core::prelude::JaResponse;
 use jarust::core::GenerateTransaction;
 use jarust::interface::error::Error;
+use jarust::interface::event_channel::event_channel;
+use jarust::interface::event_channel::EventChannelConfig;
+use jarust::interface::event_channel::EventReceiver;
+use jarust::interface::event_channel::EventSender;
 use jarust::interface::handle_msg::HandleMessage;
 use jarust::interface::handle_msg::HandleMessageWithJsep;
 use jarust::interface::janus_interface::ConnectionParams;
@@ -13,8 +17,7 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc;
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::broadcast;
 use tokio::sync::Mutex;
 
 #[derive(Debug, Default)]
@@ -22,15 +25,16 @@ pub struct Exclusive {
     create_rsp: Option<JaResponse>,
     attach_rsp: Option<JaResponse>,
     server_info_rsp: Option<ServerInfoRsp>,
-    handles_rx: HashMap<u64, UnboundedSender<JaResponse>>,
+    handles_rx: HashMap<u64, EventSender<JaResponse>>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct InnerMockInterface {
     exclusive: Mutex<Exclusive>,
+    lost_sessions: broadcast::Sender<Vec<u64>>,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone)]
 pub struct MockInterface {
     inner: Arc<InnerMockInterface>,
 }
@@ -49,9 +53,13 @@ impl MockInterface {
         self.inner.exclusive.lock().await.server_info_rsp = Some(rsp);
     }
 
+    pub fn mock_lost_sessions(&self, sessions: Vec<u64>) {
+        self.inner.lost_sessions.send(sessions).unwrap();
+    }
+
     pub async fn mock_event(&self, handle_id: u64, rsp: JaResponse) {
         if let Some(tx) = self.inner.exclusive.lock().await.handles_rx.get(&handle_id) {
-            tx.send(rsp).unwrap();
+            tx.send(rsp).await.unwrap();
         }
     }
 }
@@ -66,7 +74,10 @@ impl JanusInterface for MockInterface {
         Self: Sized,
     {
         let exclusive = Mutex::new(Exclusive::default());
-        let inner = InnerMockInterface { exclusive };
+        let inner = InnerMockInterface {
+            exclusive,
+            lost_sessions: broadcast::channel(1).0,
+        };
         Ok(Self {
             inner: Arc::new(inner),
         })
@@ -108,8 +119,9 @@ impl JanusInterface for MockInterface {
         &self,
         _session_id: u64,
         _plugin_id: String,
+        channel: Option<EventChannelConfig>,
         _timeout: Duration,
-    ) -> Result<(u64, mpsc::UnboundedReceiver<JaResponse>), jarust::interface::Error> {
+    ) -> Result<(u64, EventReceiver<JaResponse>), jarust::interface::Error> {
         let Some(rsp) = self.inner.exclusive.lock().await.attach_rsp.clone() else {
             panic!("Attach response is not set");
         };
@@ -128,7 +140,7 @@ impl JanusInterface for MockInterface {
                 return Err(Error::UnexpectedResponse);
             }
         };
-        let (tx, rx) = mpsc::unbounded_channel();
+        let (tx, rx) = event_channel(channel.unwrap_or_default());
         self.inner
             .exclusive
             .lock()
@@ -155,7 +167,7 @@ impl JanusInterface for MockInterface {
         _session_id: u64,
         _timeout: Duration,
     ) -> Result<(), jarust::interface::Error> {
-        todo!("Destroy is not implemented");
+        Ok(())
     }
 
     async fn fire_and_forget_msg(
@@ -207,4 +219,8 @@ impl JanusInterface for MockInterface {
     ) -> Result<String, Error> {
         todo!("Send handle request and waiting on ack is not implemented");
     }
+
+    fn lost_sessions(&self) -> Option<broadcast::Receiver<Vec<u64>>> {
+        Some(self.inner.lost_sessions.subscribe())
+    }
 }
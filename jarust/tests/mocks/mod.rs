@@ -1,2 +1,3 @@
+pub mod mock_admin_interface;
 pub mod mock_generate_transaction;
 pub mod mock_interface;
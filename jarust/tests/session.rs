@@ -8,6 +8,7 @@ mod tests {
     use jarust::core::custom_connect;
     use jarust::core::prelude::Attach;
     use jarust::core::prelude::JaResponse;
+    use jarust::interface::connection_state::ConnectionState;
     use jarust::interface::janus_interface::ConnectionParams;
     use jarust::interface::janus_interface::JanusInterface;
     use jarust::interface::japrotocol::ErrorResponse;
@@ -16,6 +17,88 @@ mod tests {
     use jarust::interface::japrotocol::ResponseType;
     use std::time::Duration;
 
+    #[tokio::test]
+    async fn it_closes_the_session_state_on_destroy() {
+        let conn_params = ConnectionParams {
+            url: "mock://some.janus.com".to_string(),
+            capacity: 10,
+            apisecret: None,
+            server_root: "mock".to_string(),
+            ..Default::default()
+        };
+        let transaction_generator = MockGenerateTransaction::new();
+        let interface = MockInterface::make_interface(conn_params, transaction_generator)
+            .await
+            .unwrap();
+        let mut connection = custom_connect(interface.clone()).await.unwrap();
+
+        let response = JaResponse {
+            janus: ResponseType::Success(JaSuccessProtocol::Data {
+                data: JaData { id: 73 },
+            }),
+            transaction: Some("abc123".to_string()),
+            session_id: None,
+            sender: None,
+            jsep: None,
+        };
+        interface.mock_create_rsp(response).await;
+
+        let session = connection
+            .create_session(10, Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert_eq!(connection.state(), ConnectionState::Connected);
+        assert_eq!(session.state(), ConnectionState::Connected);
+
+        let mut transitions = session.state_transitions();
+        session.destroy(Duration::from_secs(5)).await.unwrap();
+
+        let transition = transitions.recv().await.unwrap();
+        assert_eq!(transition.from, ConnectionState::Connected);
+        assert_eq!(transition.to, ConnectionState::Closed);
+        assert_eq!(session.state(), ConnectionState::Closed);
+        assert_eq!(connection.state(), ConnectionState::Connected);
+    }
+
+    #[tokio::test]
+    async fn it_closes_the_session_state_once_lost() {
+        let conn_params = ConnectionParams {
+            url: "mock://some.janus.com".to_string(),
+            capacity: 10,
+            apisecret: None,
+            server_root: "mock".to_string(),
+            ..Default::default()
+        };
+        let transaction_generator = MockGenerateTransaction::new();
+        let interface = MockInterface::make_interface(conn_params, transaction_generator)
+            .await
+            .unwrap();
+        let mut connection = custom_connect(interface.clone()).await.unwrap();
+
+        let response = JaResponse {
+            janus: ResponseType::Success(JaSuccessProtocol::Data {
+                data: JaData { id: 73 },
+            }),
+            transaction: Some("abc123".to_string()),
+            session_id: None,
+            sender: None,
+            jsep: None,
+        };
+        interface.mock_create_rsp(response).await;
+
+        let session = connection
+            .create_session(10, Duration::from_secs(10))
+            .await
+            .unwrap();
+        let mut transitions = session.state_transitions();
+        interface.mock_lost_sessions(vec![42, 73]);
+
+        let transition = transitions.recv().await.unwrap();
+        assert_eq!(transition.to, ConnectionState::Closed);
+        assert_eq!(transition.reason, "Session lost");
+        assert_eq!(connection.state(), ConnectionState::Connected);
+    }
+
     #[tokio::test]
     async fn it_successfully_attach_to_handle() {
         let conn_params = ConnectionParams {
@@ -23,6 +106,7 @@ mod tests {
             capacity: 10,
             apisecret: None,
             server_root: "mock".to_string(),
+            ..Default::default()
         };
         let transaction_generator = MockGenerateTransaction::new();
         let interface = MockInterface::make_interface(conn_params, transaction_generator)
@@ -72,6 +156,7 @@ mod tests {
             capacity: 10,
             apisecret: None,
             server_root: "mock".to_string(),
+            ..Default::default()
         };
         let transaction_generator = MockGenerateTransaction::new();
         let interface = MockInterface::make_interface(conn_params, transaction_generator)
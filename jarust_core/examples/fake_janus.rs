@@ -0,0 +1,134 @@
+//! A fake janus server speaking the websocket transport, for the tests running in the browser (`tests/wasm.rs`).
+//!
+//! Sessions and handles are made up, the messages sent to a handle are acknowledged then answered with an event,
+//! unless their body is `{"request": "ignore"}`.
+//!
+//! ```sh
+//! cargo run -p jarust_core --example fake_janus -- 127.0.0.1:8189
+//! ```
+
+#[cfg(target_family = "wasm")]
+fn main() {}
+
+#[cfg(not(target_family = "wasm"))]
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    use tokio::net::TcpListener;
+
+    let address = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:8189".to_string());
+    let listener = TcpListener::bind(&address).await?;
+    println!("Fake janus listening on ws://{address}");
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            if let Err(e) = fake::serve(stream).await {
+                eprintln!("Connection failed: {e}");
+            }
+        });
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+mod fake {
+    use futures_util::SinkExt;
+    use futures_util::StreamExt;
+    use serde_json::json;
+    use serde_json::Value;
+    use std::sync::atomic::AtomicU64;
+    use std::sync::atomic::Ordering;
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::tungstenite::handshake::server::Callback;
+    use tokio_tungstenite::tungstenite::handshake::server::ErrorResponse;
+    use tokio_tungstenite::tungstenite::handshake::server::Request;
+    use tokio_tungstenite::tungstenite::handshake::server::Response;
+    use tokio_tungstenite::tungstenite::Message;
+
+    static IDS: AtomicU64 = AtomicU64::new(1);
+
+    /// The browser fails the handshake unless the subprotocol it asked for is picked
+    struct EchoProtocol;
+
+    impl Callback for EchoProtocol {
+        fn on_request(
+            self,
+            request: &Request,
+            mut response: Response,
+        ) -> Result<Response, ErrorResponse> {
+            if let Some(protocol) = request.headers().get("Sec-WebSocket-Protocol") {
+                response
+                    .headers_mut()
+                    .insert("Sec-WebSocket-Protocol", protocol.clone());
+            }
+            Ok(response)
+        }
+    }
+
+    pub async fn serve(stream: TcpStream) -> anyhow::Result<()> {
+        let mut ws = tokio_tungstenite::accept_hdr_async(stream, EchoProtocol).await?;
+        while let Some(message) = ws.next().await {
+            let request = match message? {
+                Message::Text(text) => serde_json::from_str::<Value>(&text)?,
+                Message::Binary(data) => serde_json::from_slice::<Value>(&data)?,
+                Message::Close(_) => break,
+                _ => continue,
+            };
+            for response in respond(&request) {
+                ws.send(Message::Text(response.to_string().into())).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn respond(request: &Value) -> Vec<Value> {
+        let transaction = &request["transaction"];
+        let session_id = &request["session_id"];
+        match request["janus"].as_str().unwrap_or_default() {
+            "create" => vec![json!({
+                "janus": "success",
+                "transaction": transaction,
+                "data": {"id": IDS.fetch_add(1, Ordering::Relaxed)}
+            })],
+            "attach" => vec![json!({
+                "janus": "success",
+                "transaction": transaction,
+                "session_id": session_id,
+                "data": {"id": IDS.fetch_add(1, Ordering::Relaxed)}
+            })],
+            "keepalive" => vec![json!({
+                "janus": "ack",
+                "transaction": transaction,
+                "session_id": session_id
+            })],
+            "detach" | "destroy" => vec![json!({
+                "janus": "success",
+                "transaction": transaction,
+                "session_id": session_id
+            })],
+            "message" if request["body"]["request"] == "ignore" => vec![],
+            "message" => vec![
+                json!({
+                    "janus": "ack",
+                    "transaction": transaction,
+                    "session_id": session_id
+                }),
+                json!({
+                    "janus": "event",
+                    "transaction": transaction,
+                    "session_id": session_id,
+                    "sender": request["handle_id"],
+                    "plugindata": {
+                        "plugin": "janus.plugin.echotest",
+                        "data": {"echotest": "event", "result": "ok"}
+                    }
+                }),
+            ],
+            _ => vec![json!({
+                "janus": "error",
+                "transaction": transaction,
+                "error": {"code": 453, "reason": "Unknown request"}
+            })],
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use jarust_interface::admin::admin_interface::AdminRequest;
+use jarust_interface::admin::admin_interface::JanusAdminInterface;
+use jarust_interface::admin::admin_interface::JanusAdminInterfaceImpl;
+use jarust_interface::admin::admin_protocol::AddTokenRsp;
+use jarust_interface::admin::admin_protocol::AdminPluginRsp;
+use jarust_interface::admin::admin_protocol::HandleInfo;
+use jarust_interface::admin::admin_protocol::HandleInfoRsp;
+use jarust_interface::admin::admin_protocol::ListHandlesRsp;
+use jarust_interface::admin::admin_protocol::ListSessionsRsp;
+use jarust_interface::admin::admin_protocol::ListTokensRsp;
+use jarust_interface::admin::admin_protocol::LogLevelRsp;
+use jarust_interface::admin::admin_protocol::StoredToken;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use serde_json::Value;
+use std::time::Duration;
+
+/// Client of the janus admin/monitor api, full docs: <https://janus.conf.meetecho.com/docs/admin.html>
+#[derive(Clone, Debug)]
+pub struct JaAdmin {
+    interface: JanusAdminInterfaceImpl,
+}
+
+impl JaAdmin {
+    pub(crate) fn open(interface: impl JanusAdminInterface) -> Self {
+        tracing::info!("Creating new admin connection");
+        Self {
+            interface: JanusAdminInterfaceImpl::new(interface),
+        }
+    }
+
+    async fn request<R>(
+        &self,
+        session_id: Option<u64>,
+        handle_id: Option<u64>,
+        body: Value,
+        timeout: Duration,
+    ) -> Result<R, jarust_interface::Error>
+    where
+        R: DeserializeOwned,
+    {
+        let request = AdminRequest {
+            session_id,
+            handle_id,
+            body,
+        };
+        self.interface.send_admin_request(request, timeout).await
+    }
+
+    /// Lists the ids of the sessions janus is handling
+    #[tracing::instrument(level = tracing::Level::DEBUG, skip_all)]
+    pub async fn list_sessions(
+        &self,
+        timeout: Duration,
+    ) -> Result<Vec<u64>, jarust_interface::Error> {
+        let rsp = self
+            .request::<ListSessionsRsp>(None, None, json!({"janus": "list_sessions"}), timeout)
+            .await?;
+        Ok(rsp.sessions)
+    }
+
+    /// Lists the ids of the handles of the given session
+    #[tracing::instrument(level = tracing::Level::DEBUG, skip_all, fields(session_id = session_id))]
+    pub async fn list_handles(
+        &self,
+        session_id: u64,
+        timeout: Duration,
+    ) -> Result<Vec<u64>, jarust_interface::Error> {
+        let rsp = self
+            .request::<ListHandlesRsp>(
+                Some(session_id),
+                None,
+                json!({"janus": "list_handles"}),
+                timeout,
+            )
+            .await?;
+        Ok(rsp.handles)
+    }
+
+    /// Retrieves the details of a handle (state, plugin, ICE/DTLS, media stats, etc.)
+    #[tracing::instrument(level = tracing::Level::DEBUG, skip_all, fields(session_id = session_id, handle_id = handle_id))]
+    pub async fn handle_info(
+        &self,
+        session_id: u64,
+        handle_id: u64,
+        timeout: Duration,
+    ) -> Result<HandleInfo, jarust_interface::Error> {
+        let rsp = self
+            .request::<HandleInfoRsp>(
+                Some(session_id),
+                Some(handle_id),
+                json!({"janus": "handle_info"}),
+                timeout,
+            )
+            .await?;
+        Ok(rsp.info)
+    }
+
+    /// Changes the log level of janus (0-7), returns the new level
+    #[tracing::instrument(level = tracing::Level::DEBUG, skip_all)]
+    pub async fn set_log_level(
+        &self,
+        level: u8,
+        timeout: Duration,
+    ) -> Result<u8, jarust_interface::Error> {
+        let rsp = self
+            .request::<LogLevelRsp>(
+                None,
+                None,
+                json!({"janus": "set_log_level", "level": level}),
+                timeout,
+            )
+            .await?;
+        Ok(rsp.level)
+    }
+
+    /// Adds a token to the stored tokens, allowed to access the given plugins (all of them if empty).
+    ///
+    /// Returns the plugins the token is allowed to access.
+    #[tracing::instrument(level = tracing::Level::DEBUG, skip_all)]
+    pub async fn add_token(
+        &self,
+        token: String,
+        plugins: Vec<String>,
+        timeout: Duration,
+    ) -> Result<Vec<String>, jarust_interface::Error> {
+        let mut body = json!({"janus": "add_token", "token": token});
+        if !plugins.is_empty() {
+            body["plugins"] = plugins.into();
+        }
+        let rsp = self
+            .request::<AddTokenRsp>(None, None, body, timeout)
+            .await?;
+        Ok(rsp.data.plugins)
+    }
+
+    /// Lists the stored tokens along with the plugins they can access
+    #[tracing::instrument(level = tracing::Level::DEBUG, skip_all)]
+    pub async fn list_tokens(
+        &self,
+        timeout: Duration,
+    ) -> Result<Vec<StoredToken>, jarust_interface::Error> {
+        let rsp = self
+            .request::<ListTokensRsp>(None, None, json!({"janus": "list_tokens"}), timeout)
+            .await?;
+        Ok(rsp.data.tokens)
+    }
+
+    /// Removes a token from the stored tokens
+    #[tracing::instrument(level = tracing::Level::DEBUG, skip_all)]
+    pub async fn remove_token(
+        &self,
+        token: String,
+        timeout: Duration,
+    ) -> Result<(), jarust_interface::Error> {
+        self.request::<Value>(
+            None,
+            None,
+            json!({"janus": "remove_token", "token": token}),
+            timeout,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Destroys a session, along with all of its handles
+    #[tracing::instrument(level = tracing::Level::DEBUG, skip_all, fields(session_id = session_id))]
+    pub async fn destroy_session(
+        &self,
+        session_id: u64,
+        timeout: Duration,
+    ) -> Result<(), jarust_interface::Error> {
+        self.request::<Value>(
+            Some(session_id),
+            None,
+            json!({"janus": "destroy_session"}),
+            timeout,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Detaches a handle from its plugin
+    #[tracing::instrument(level = tracing::Level::DEBUG, skip_all, fields(session_id = session_id, handle_id = handle_id))]
+    pub async fn detach_handle(
+        &self,
+        session_id: u64,
+        handle_id: u64,
+        timeout: Duration,
+    ) -> Result<(), jarust_interface::Error> {
+        self.request::<Value>(
+            Some(session_id),
+            Some(handle_id),
+            json!({"janus": "detach_handle"}),
+            timeout,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Sends a synchronous request to a plugin (e.g. `janus.plugin.videoroom`) and parses its response
+    #[tracing::instrument(level = tracing::Level::DEBUG, skip_all)]
+    pub async fn message_plugin<R>(
+        &self,
+        plugin: String,
+        request: Value,
+        timeout: Duration,
+    ) -> Result<R, jarust_interface::Error>
+    where
+        R: DeserializeOwned,
+    {
+        let rsp = self
+            .request::<AdminPluginRsp<R>>(
+                None,
+                None,
+                json!({"janus": "message_plugin", "plugin": plugin, "request": request}),
+                timeout,
+            )
+            .await?;
+        Ok(rsp.response)
+    }
+
+    /// Sends a synchronous request to an event handler (e.g. `janus.eventhandler.sampleevh`) and parses its response
+    #[tracing::instrument(level = tracing::Level::DEBUG, skip_all)]
+    pub async fn query_eventhandler<R>(
+        &self,
+        handler: String,
+        request: Value,
+        timeout: Duration,
+    ) -> Result<R, jarust_interface::Error>
+    where
+        R: DeserializeOwned,
+    {
+        let rsp = self
+            .request::<AdminPluginRsp<R>>(
+                None,
+                None,
+                json!({"janus": "query_eventhandler", "handler": handler, "request": request}),
+                timeout,
+            )
+            .await?;
+        Ok(rsp.response)
+    }
+}
@@ -1,13 +1,25 @@
+use crate::jaload::ConnectionLoad;
+use crate::jaload::LoadTracker;
 use crate::jasession::JaSession;
 use crate::jasession::NewSessionParams;
+use jarust_interface::compression_metrics::CompressionMetrics;
+use jarust_interface::connection_state::ConnectionState;
+use jarust_interface::connection_state::ConnectionStateTracker;
+use jarust_interface::connection_state::StateTransition;
+use jarust_interface::fallback::Transport;
 use jarust_interface::janus_interface::JanusInterface;
 use jarust_interface::janus_interface::JanusInterfaceImpl;
 use jarust_interface::japrotocol::ServerInfoRsp;
+use jarust_interface::transaction_metrics::TransactionMetrics;
 use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::watch;
 
 #[derive(Clone, Debug)]
 pub struct JaConnection {
     interface: JanusInterfaceImpl,
+    state: ConnectionStateTracker,
+    load: LoadTracker,
 }
 
 impl JaConnection {
@@ -15,8 +27,13 @@ impl JaConnection {
         interface: impl JanusInterface,
     ) -> Result<Self, jarust_interface::Error> {
         tracing::info!("Creating new connection");
+        let state = interface
+            .connection_state()
+            .unwrap_or_else(|| ConnectionStateTracker::new(ConnectionState::Connected));
         Ok(Self {
             interface: JanusInterfaceImpl::new(interface),
+            state,
+            load: LoadTracker::default(),
         })
     }
 
@@ -33,6 +50,8 @@ impl JaConnection {
             session_id,
             ka_interval,
             interface: self.interface.clone(),
+            connection_state: self.state.clone(),
+            load: self.load.clone(),
         })
         .await;
         tracing::info!(id = session_id, "Session created");
@@ -48,4 +67,39 @@ impl JaConnection {
         let res = self.interface.server_info(timeout).await?;
         Ok(res)
     }
+
+    /// Returns the current state of the connection
+    pub fn state(&self) -> ConnectionState {
+        self.state.state()
+    }
+
+    /// Returns a receiver that gets notified whenever the connection state changes
+    pub fn watch_state(&self) -> watch::Receiver<ConnectionState> {
+        self.state.watch()
+    }
+
+    /// Returns a receiver of the connection state transitions along with their reasons
+    pub fn state_transitions(&self) -> broadcast::Receiver<StateTransition> {
+        self.state.transitions()
+    }
+
+    /// Returns the metrics of the transactions in flight, if the underlying interface tracks them
+    pub fn transaction_metrics(&self) -> Option<TransactionMetrics> {
+        self.interface.transaction_metrics()
+    }
+
+    /// Returns the metrics of the `permessage-deflate` compression, if the underlying interface compresses
+    pub fn compression_metrics(&self) -> Option<CompressionMetrics> {
+        self.interface.compression_metrics()
+    }
+
+    /// Returns the transport the requests are going through, if the underlying interface switches between transports
+    pub fn active_transport(&self) -> Option<Transport> {
+        self.interface.active_transport()
+    }
+
+    /// Returns the sessions and handles created through this connection that are still alive
+    pub fn load(&self) -> ConnectionLoad {
+        self.load.load()
+    }
 }
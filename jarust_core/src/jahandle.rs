@@ -1,3 +1,4 @@
+use crate::jaload::LoadGuard;
 use jarust_interface::handle_msg::HandleMessage;
 use jarust_interface::handle_msg::HandleMessageWithJsep;
 use jarust_interface::janus_interface::JanusInterfaceImpl;
@@ -12,6 +13,7 @@ struct InnerHandle {
     id: u64,
     session_id: u64,
     interface: JanusInterfaceImpl,
+    load: LoadGuard,
 }
 
 pub struct JaHandle {
@@ -22,6 +24,7 @@ pub struct NewHandleParams {
     pub handle_id: u64,
     pub session_id: u64,
     pub interface: JanusInterfaceImpl,
+    pub(crate) load: LoadGuard,
 }
 
 impl JaHandle {
@@ -31,6 +34,7 @@ impl JaHandle {
                 id: params.handle_id,
                 session_id: params.session_id,
                 interface: params.interface,
+                load: params.load,
             },
         }
     }
@@ -197,6 +201,7 @@ impl JaHandle {
             "janus": "detach"
         });
         self.send_handle_request(request).await?;
+        self.inner.load.release();
         Ok(())
     }
 
@@ -210,6 +215,7 @@ impl JaHandle {
             "janus": "detach"
         });
         self.send_handle_request(request).await?;
+        self.inner.load.release();
         Ok(())
     }
 
@@ -1,19 +1,28 @@
+use jarust_interface::connection_state::ConnectionStateTracker;
 use jarust_interface::janus_interface::JanusInterfaceImpl;
 use std::time::Duration;
-use tokio::time;
 
 pub struct JaKeepAlive {
     interface: JanusInterfaceImpl,
     session_id: u64,
     ka_interval: u32,
+    state: ConnectionStateTracker,
 }
 
 impl JaKeepAlive {
-    pub fn new(interface: JanusInterfaceImpl, session_id: u64, ka_interval: u32) -> Self {
+    /// The session state is degraded when a keep-alive fails and recovered once it succeeds again.
+    /// The connection state is left alone, a single session failing says little about the connection.
+    pub fn new(
+        interface: JanusInterfaceImpl,
+        session_id: u64,
+        ka_interval: u32,
+        state: ConnectionStateTracker,
+    ) -> Self {
         Self {
             interface,
             session_id,
             ka_interval,
+            state,
         }
     }
 
@@ -24,14 +33,19 @@ impl JaKeepAlive {
             return Ok(());
         }
         let duration = Duration::from_secs(self.ka_interval.into());
-        let mut interval = time::interval(duration);
         loop {
-            interval.tick().await;
             tracing::debug!("Sending keep-alive");
             match self.interface.keep_alive(self.session_id, duration).await {
-                Ok(_) => tracing::debug!("Keep-alive success"),
-                Err(e) => tracing::error!("Keep-alive failed: {:?}", e),
+                Ok(_) => {
+                    tracing::debug!("Keep-alive success");
+                    self.state.recover("Keep-alive succeeded");
+                }
+                Err(e) => {
+                    tracing::error!("Keep-alive failed: {:?}", e);
+                    self.state.degrade(format!("Keep-alive failed: {e}"));
+                }
             };
+            jarust_rt::sleep(duration).await;
         }
     }
 }
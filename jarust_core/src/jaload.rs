@@ -0,0 +1,67 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// Snapshot of the sessions and handles living on a connection.
+///
+/// Janus doesn't report its load in the server info, so only the sessions created and the handles attached
+/// through the connection are counted, until they're destroyed, detached or dropped.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct ConnectionLoad {
+    pub sessions: usize,
+    pub handles: usize,
+}
+
+/// Counts the sessions and handles of a connection, shared with the sessions and handles themselves
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LoadTracker {
+    sessions: Arc<AtomicUsize>,
+    handles: Arc<AtomicUsize>,
+}
+
+impl LoadTracker {
+    pub(crate) fn load(&self) -> ConnectionLoad {
+        ConnectionLoad {
+            sessions: self.sessions.load(Ordering::Relaxed),
+            handles: self.handles.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn session(&self) -> LoadGuard {
+        LoadGuard::new(self.sessions.clone())
+    }
+
+    pub(crate) fn handle(&self) -> LoadGuard {
+        LoadGuard::new(self.handles.clone())
+    }
+}
+
+/// Keeps a session or a handle counted until it's released or dropped
+#[derive(Debug)]
+pub(crate) struct LoadGuard {
+    counter: Arc<AtomicUsize>,
+    released: AtomicBool,
+}
+
+impl LoadGuard {
+    fn new(counter: Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self {
+            counter,
+            released: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn release(&self) {
+        if !self.released.swap(true, Ordering::Relaxed) {
+            self.counter.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for LoadGuard {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
@@ -1,8 +1,9 @@
 use crate::prelude::*;
 use async_trait::async_trait;
+use jarust_interface::event_channel::EventChannelConfig;
+use jarust_interface::event_channel::EventReceiver;
 use jarust_rt::JaTask;
 use std::time::Duration;
-use tokio::sync::mpsc;
 
 pub trait PluginTask {
     fn assign_task(&mut self, task: JaTask);
@@ -15,5 +16,14 @@ pub trait Attach {
         &self,
         plugin_id: String,
         timeout: Duration,
-    ) -> Result<(JaHandle, mpsc::UnboundedReceiver<JaResponse>), jarust_interface::Error>;
+    ) -> Result<(JaHandle, EventReceiver<JaResponse>), jarust_interface::Error>;
+
+    /// Same as [`attach`](Self::attach), but the events of the handle are queued on a channel of its own
+    /// instead of one configured by the connection
+    async fn attach_with_channel(
+        &self,
+        plugin_id: String,
+        channel: EventChannelConfig,
+        timeout: Duration,
+    ) -> Result<(JaHandle, EventReceiver<JaResponse>), jarust_interface::Error>;
 }
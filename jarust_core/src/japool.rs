@@ -0,0 +1,437 @@
+use crate::jaconnection::JaConnection;
+use crate::jaload::ConnectionLoad;
+use crate::jasession::JaSession;
+use futures_util::future::join_all;
+use jarust_interface::connection_state::ConnectionState;
+use jarust_interface::japrotocol::ServerInfoRsp;
+use jarust_rt::JaTask;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How the pool picks the server of a new session, among the healthy servers having the plugin
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum PlacementStrategy {
+    /// Takes turns between the servers
+    #[default]
+    RoundRobin,
+    /// Picks the server with the fewest handles, then the fewest sessions
+    LeastLoaded,
+    /// Places the sessions of the same room on the same server, a room only moves when its server
+    /// can't take it anymore. Sessions created without a room are placed on the least loaded server.
+    ///
+    /// The rooms are hashed with FNV-1a, integers written in little endian, so a room keeps its server
+    /// across restarts and platforms as long as the servers are pooled in the same order
+    Sticky,
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct JaPoolConfig {
+    pub strategy: PlacementStrategy,
+    /// How often the servers are asked for their info, to follow their health and plugins
+    pub health_check_interval: Duration,
+    /// How long a server has to answer a health check before it's considered unhealthy
+    pub health_check_timeout: Duration,
+}
+
+impl Default for JaPoolConfig {
+    fn default() -> Self {
+        Self {
+            strategy: PlacementStrategy::default(),
+            health_check_interval: Duration::from_secs(10),
+            health_check_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Snapshot of a pooled server
+#[derive(Clone, Debug)]
+pub struct ServerStatus {
+    pub state: ConnectionState,
+    /// The server info of the last health check, `None` if the server didn't answer it
+    pub info: Option<ServerInfoRsp>,
+    pub load: ConnectionLoad,
+}
+
+impl ServerStatus {
+    /// Whether the server is connected, answered the last health check and accepts new sessions
+    pub fn is_healthy(&self) -> bool {
+        self.state == ConnectionState::Connected
+            && self
+                .info
+                .as_ref()
+                .is_some_and(|info| info.accepting_new_sessions)
+    }
+
+    /// Whether the server has the plugin, as of the last health check
+    pub fn has_plugin(&self, plugin_id: &str) -> bool {
+        self.info
+            .as_ref()
+            .is_some_and(|info| info.plugins.contains_key(plugin_id))
+    }
+}
+
+#[derive(Debug)]
+struct Server {
+    connection: JaConnection,
+    info: Arc<Mutex<Option<ServerInfoRsp>>>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    servers: Vec<Server>,
+    config: JaPoolConfig,
+}
+
+#[derive(Debug)]
+struct InnerPool {
+    shared: Shared,
+    turn: AtomicUsize,
+    tasks: Vec<JaTask>,
+}
+
+/// Connections to a fleet of janus servers, placing each new session on one of them.
+///
+/// The servers are asked for their info periodically, only the healthy ones having the plugin the
+/// session is created for are candidates, then the [`PlacementStrategy`] picks one of them.
+#[derive(Clone, Debug)]
+pub struct JaConnectionPool {
+    inner: Arc<InnerPool>,
+}
+
+impl JaConnectionPool {
+    /// Pools the connections, their order identifies the servers so it should be kept across restarts
+    /// for the rooms to stick to the same servers
+    pub async fn open(connections: Vec<JaConnection>, config: JaPoolConfig) -> Self {
+        tracing::info!(servers = connections.len(), "Creating new connection pool");
+        let servers = connections
+            .into_iter()
+            .map(|connection| Server {
+                connection,
+                info: Arc::new(Mutex::new(None)),
+            })
+            .collect::<Vec<_>>();
+        join_all(servers.iter().enumerate().map(|(index, server)| {
+            check_health(
+                index,
+                &server.connection,
+                &server.info,
+                config.health_check_timeout,
+            )
+        }))
+        .await;
+
+        let tasks = servers
+            .iter()
+            .enumerate()
+            .map(|(index, server)| {
+                let connection = server.connection.clone();
+                let info = server.info.clone();
+                let interval = config.health_check_interval;
+                let timeout = config.health_check_timeout;
+                jarust_rt::spawn("Health check task", async move {
+                    // The servers were just checked
+                    loop {
+                        jarust_rt::sleep(interval).await;
+                        check_health(index, &connection, &info, timeout).await;
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            inner: Arc::new(InnerPool {
+                shared: Shared { servers, config },
+                turn: AtomicUsize::new(0),
+                tasks,
+            }),
+        }
+    }
+
+    /// Creates a new session on one of the servers having the plugin
+    #[tracing::instrument(level = tracing::Level::DEBUG, skip_all, fields(plugin_id = plugin_id))]
+    pub async fn create_session(
+        &self,
+        plugin_id: &str,
+        ka_interval: u32,
+        timeout: Duration,
+    ) -> Result<JaSession, jarust_interface::Error> {
+        self.create_placed_session(plugin_id, None, ka_interval, timeout)
+            .await
+    }
+
+    /// Creates a new session on one of the servers having the plugin, the sessions of the same room are
+    /// placed on the same server when using [`PlacementStrategy::Sticky`]
+    #[tracing::instrument(level = tracing::Level::DEBUG, skip_all, fields(plugin_id = plugin_id))]
+    pub async fn create_room_session(
+        &self,
+        plugin_id: &str,
+        room: impl Hash,
+        ka_interval: u32,
+        timeout: Duration,
+    ) -> Result<JaSession, jarust_interface::Error> {
+        self.create_placed_session(plugin_id, Some(stable_hash(room)), ka_interval, timeout)
+            .await
+    }
+
+    /// Asks all the servers for their info right away, instead of waiting on the next periodic check
+    pub async fn check_health(&self) {
+        let timeout = self.inner.shared.config.health_check_timeout;
+        let servers = self.inner.shared.servers.iter().enumerate();
+        join_all(
+            servers.map(|(index, server)| {
+                check_health(index, &server.connection, &server.info, timeout)
+            }),
+        )
+        .await;
+    }
+
+    /// Returns the status of each server, in the order the connections were pooled
+    pub fn status(&self) -> Vec<ServerStatus> {
+        self.inner
+            .shared
+            .servers
+            .iter()
+            .map(|server| ServerStatus {
+                state: server.connection.state(),
+                info: lock(&server.info).clone(),
+                load: server.connection.load(),
+            })
+            .collect()
+    }
+
+    async fn create_placed_session(
+        &self,
+        plugin_id: &str,
+        room: Option<u64>,
+        ka_interval: u32,
+        timeout: Duration,
+    ) -> Result<JaSession, jarust_interface::Error> {
+        let candidates = self
+            .status()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, status)| status.is_healthy() && status.has_plugin(plugin_id))
+            .map(|(index, status)| Candidate {
+                index,
+                load: status.load,
+            })
+            .collect::<Vec<_>>();
+        let turn = self.inner.turn.fetch_add(1, Ordering::Relaxed);
+        let Some(index) = place(self.inner.shared.config.strategy, &candidates, turn, room) else {
+            tracing::error!("No healthy server has the plugin");
+            return Err(jarust_interface::Error::NoServerAvailable {
+                plugin_id: plugin_id.to_string(),
+            });
+        };
+
+        tracing::debug!(server = index, "Placing session");
+        let mut connection = self.inner.shared.servers[index].connection.clone();
+        connection.create_session(ka_interval, timeout).await
+    }
+}
+
+impl Drop for InnerPool {
+    fn drop(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.cancel()
+        }
+    }
+}
+
+#[tracing::instrument(level = tracing::Level::TRACE, skip_all, fields(server = index))]
+async fn check_health(
+    index: usize,
+    connection: &JaConnection,
+    info: &Mutex<Option<ServerInfoRsp>>,
+    timeout: Duration,
+) {
+    let checked = match connection.server_info(timeout).await {
+        Ok(server_info) => Some(server_info),
+        Err(e) => {
+            tracing::warn!("Health check failed: {e}");
+            None
+        }
+    };
+    *lock(info) = checked;
+}
+
+fn lock(info: &Mutex<Option<ServerInfoRsp>>) -> std::sync::MutexGuard<'_, Option<ServerInfoRsp>> {
+    info.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// A server able to take the session
+#[derive(Clone, Copy, Debug)]
+struct Candidate {
+    index: usize,
+    load: ConnectionLoad,
+}
+
+/// Picks the server of a new session among the candidates, returning its index
+fn place(
+    strategy: PlacementStrategy,
+    candidates: &[Candidate],
+    turn: usize,
+    room: Option<u64>,
+) -> Option<usize> {
+    let least_loaded = || {
+        candidates
+            .iter()
+            .min_by_key(|candidate| (candidate.load.handles, candidate.load.sessions))
+            .map(|candidate| candidate.index)
+    };
+    match (strategy, room) {
+        (PlacementStrategy::RoundRobin, _) => {
+            (!candidates.is_empty()).then(|| candidates[turn % candidates.len()].index)
+        }
+        (PlacementStrategy::LeastLoaded, _) | (PlacementStrategy::Sticky, None) => least_loaded(),
+        // Rendezvous hashing, a room only moves when its server stops being a candidate
+        (PlacementStrategy::Sticky, Some(room)) => candidates
+            .iter()
+            .max_by_key(|candidate| stable_hash((room, candidate.index)))
+            .map(|candidate| candidate.index),
+    }
+}
+
+/// Hashes with [`Fnv1a`], unlike the std hashers its output is specified, so it can be relied on across
+/// releases and restarts
+fn stable_hash(value: impl Hash) -> u64 {
+    let mut hasher = Fnv1a::default();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 64-bit FNV-1a, the integers are written in little endian and `usize` as a `u64` so the hashes don't depend
+/// on the platform either
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::place;
+    use super::stable_hash;
+    use super::Candidate;
+    use super::Fnv1a;
+    use super::PlacementStrategy;
+    use crate::jaload::ConnectionLoad;
+    use std::hash::Hasher;
+
+    fn candidate(index: usize, sessions: usize, handles: usize) -> Candidate {
+        Candidate {
+            index,
+            load: ConnectionLoad { sessions, handles },
+        }
+    }
+
+    #[test]
+    fn it_should_place_sessions_by_strategy() {
+        let candidates = [candidate(0, 2, 4), candidate(2, 3, 1), candidate(5, 1, 1)];
+
+        let turns = (0..4)
+            .map(|turn| place(PlacementStrategy::RoundRobin, &candidates, turn, None))
+            .collect::<Vec<_>>();
+        assert_eq!(turns, [Some(0), Some(2), Some(5), Some(0)]);
+
+        assert_eq!(
+            place(PlacementStrategy::LeastLoaded, &candidates, 0, None),
+            Some(5)
+        );
+        assert_eq!(
+            place(PlacementStrategy::Sticky, &candidates, 0, None),
+            Some(5)
+        );
+        assert_eq!(place(PlacementStrategy::RoundRobin, &[], 0, None), None);
+    }
+
+    #[test]
+    fn it_should_keep_rooms_on_their_server() {
+        let candidates = (0..5)
+            .map(|index| candidate(index, 0, 0))
+            .collect::<Vec<_>>();
+        for room in 0..100 {
+            let server = place(PlacementStrategy::Sticky, &candidates, 0, Some(room)).unwrap();
+            assert_eq!(
+                place(PlacementStrategy::Sticky, &candidates, 7, Some(room)),
+                Some(server)
+            );
+
+            // Losing another server doesn't move the room
+            let other = (server + 1) % candidates.len();
+            let remaining = candidates
+                .iter()
+                .copied()
+                .filter(|candidate| candidate.index != other)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                place(PlacementStrategy::Sticky, &remaining, 0, Some(room)),
+                Some(server)
+            );
+        }
+    }
+
+    #[test]
+    fn it_should_hash_the_rooms_the_same_everywhere() {
+        let fnv1a = |bytes: &[u8]| {
+            let mut hasher = Fnv1a::default();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x85944171f73967e8);
+        assert_eq!(stable_hash(0u64), fnv1a(&[0; 8]));
+        assert_eq!(stable_hash(1usize), fnv1a(&1u64.to_le_bytes()));
+
+        // The rooms are spread over the servers
+        let candidates = (0..5)
+            .map(|index| candidate(index, 0, 0))
+            .collect::<Vec<_>>();
+        let mut placed = [0; 5];
+        for room in 0..1000 {
+            let server = place(PlacementStrategy::Sticky, &candidates, 0, Some(room)).unwrap();
+            placed[server] += 1;
+        }
+        assert!(placed.iter().all(|&count| count > 120), "{placed:?}");
+    }
+}
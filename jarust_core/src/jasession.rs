@@ -1,24 +1,36 @@
 use crate::jahandle::JaHandle;
 use crate::jahandle::NewHandleParams;
 use crate::jakeepalive::JaKeepAlive;
+use crate::jaload::LoadGuard;
+use crate::jaload::LoadTracker;
 use crate::prelude::*;
 use async_trait::async_trait;
+use jarust_interface::connection_state::ConnectionState;
+use jarust_interface::connection_state::ConnectionStateTracker;
+use jarust_interface::connection_state::StateTransition;
+use jarust_interface::event_channel::EventChannelConfig;
+use jarust_interface::event_channel::EventReceiver;
 use jarust_interface::janus_interface::JanusInterfaceImpl;
 use jarust_rt::JaTask;
 use std::sync::Arc;
+use std::sync::Weak;
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::broadcast;
+use tokio::sync::watch;
 use tokio::sync::Mutex;
 
 #[derive(Debug)]
 pub struct Shared {
     id: u64,
     interface: JanusInterfaceImpl,
+    state: ConnectionStateTracker,
+    load: LoadTracker,
+    load_guard: LoadGuard,
 }
 
 #[derive(Debug, Default)]
 pub struct Exclusive {
-    task: Option<JaTask>,
+    tasks: Vec<JaTask>,
 }
 
 #[derive(Debug)]
@@ -36,28 +48,75 @@ pub struct NewSessionParams {
     pub session_id: u64,
     pub ka_interval: u32,
     pub interface: JanusInterfaceImpl,
+    pub connection_state: ConnectionStateTracker,
+    pub(crate) load: LoadTracker,
 }
 
 impl JaSession {
     pub(crate) async fn new(params: NewSessionParams) -> Self {
+        let connection_transitions = params.connection_state.transitions();
+        let state = ConnectionStateTracker::new(params.connection_state.state());
         let shared = Shared {
             id: params.session_id,
             interface: params.interface.clone(),
+            state: state.clone(),
+            load_guard: params.load.session(),
+            load: params.load,
         };
         let exclusive = Mutex::new(Exclusive::default());
         let session = Self {
             inner: Arc::new(InnerSession { shared, exclusive }),
         };
 
-        let jakeepalive = JaKeepAlive::new(params.interface, params.session_id, params.ka_interval);
+        let lost_sessions = params.interface.lost_sessions();
+        let jakeepalive = JaKeepAlive::new(
+            params.interface,
+            params.session_id,
+            params.ka_interval,
+            state.clone(),
+        );
 
         let keepalive_task =
             jarust_rt::spawn("KeepAlive task", async move { jakeepalive.start().await });
 
-        session.inner.exclusive.lock().await.task = Some(keepalive_task);
+        let state_task = jarust_rt::spawn(
+            "Session state task",
+            follow_connection_state(params.connection_state, connection_transitions, state),
+        );
+
+        let mut tasks = vec![keepalive_task, state_task];
+        if let Some(lost_sessions) = lost_sessions {
+            tasks.push(jarust_rt::spawn(
+                "Lost session task",
+                close_once_lost(Arc::downgrade(&session.inner), lost_sessions),
+            ));
+        }
+        session.inner.exclusive.lock().await.tasks = tasks;
 
         session
     }
+
+    /// Returns the current state of the session
+    pub fn state(&self) -> ConnectionState {
+        self.inner.shared.state.state()
+    }
+
+    /// Returns a receiver that gets notified whenever the session state changes
+    pub fn watch_state(&self) -> watch::Receiver<ConnectionState> {
+        self.inner.shared.state.watch()
+    }
+
+    /// Returns a receiver of the session state transitions along with their reasons
+    pub fn state_transitions(&self) -> broadcast::Receiver<StateTransition> {
+        self.inner.shared.state.transitions()
+    }
+
+    async fn close(&self, reason: &str) {
+        self.inner.shared.state.set(ConnectionState::Closed, reason);
+        self.inner.shared.load_guard.release();
+        let mut exclusive = self.inner.exclusive.lock().await;
+        exclusive.tasks.drain(..).for_each(|task| task.cancel());
+    }
 }
 
 impl JaSession {
@@ -73,6 +132,7 @@ impl JaSession {
             .interface
             .destroy(session_id, timeout)
             .await?;
+        self.close("Session destroyed").await;
         Ok(())
     }
 
@@ -88,32 +148,33 @@ impl JaSession {
             .interface
             .destroy(session_id, timeout)
             .await?;
+        self.close("Session destroyed").await;
         Ok(())
     }
 }
 
-#[async_trait]
-impl Attach for JaSession {
-    /// Attach a plugin to the current session
+impl JaSession {
     #[tracing::instrument(level = tracing::Level::DEBUG, skip_all, fields(session_id = self.inner.shared.id))]
-    async fn attach(
+    async fn attach_handle(
         &self,
         plugin_id: String,
+        channel: Option<EventChannelConfig>,
         timeout: Duration,
-    ) -> Result<(JaHandle, mpsc::UnboundedReceiver<JaResponse>), jarust_interface::Error> {
+    ) -> Result<(JaHandle, EventReceiver<JaResponse>), jarust_interface::Error> {
         tracing::info!(plugin = &plugin_id, "Attaching new handle");
         let session_id = self.inner.shared.id;
         let (handle_id, event_receiver) = self
             .inner
             .shared
             .interface
-            .attach(session_id, plugin_id, timeout)
+            .attach(session_id, plugin_id, channel, timeout)
             .await?;
 
         let handle = JaHandle::new(NewHandleParams {
             handle_id,
             session_id,
             interface: self.inner.shared.interface.clone(),
+            load: self.inner.shared.load.handle(),
         })
         .await;
         tracing::info!(id = handle_id, "Handle created");
@@ -121,10 +182,80 @@ impl Attach for JaSession {
     }
 }
 
+#[async_trait]
+impl Attach for JaSession {
+    /// Attach a plugin to the current session
+    async fn attach(
+        &self,
+        plugin_id: String,
+        timeout: Duration,
+    ) -> Result<(JaHandle, EventReceiver<JaResponse>), jarust_interface::Error> {
+        self.attach_handle(plugin_id, None, timeout).await
+    }
+
+    /// Attach a plugin to the current session, with its own event channel
+    async fn attach_with_channel(
+        &self,
+        plugin_id: String,
+        channel: EventChannelConfig,
+        timeout: Duration,
+    ) -> Result<(JaHandle, EventReceiver<JaResponse>), jarust_interface::Error> {
+        self.attach_handle(plugin_id, Some(channel), timeout).await
+    }
+}
+
 impl Drop for Exclusive {
     fn drop(&mut self) {
-        if let Some(task) = self.task.take() {
+        for task in self.tasks.drain(..) {
             task.cancel()
         }
     }
 }
+
+/// Closes the session once the interface reports it lost, e.g. when falling back from the transport it was
+/// created with
+async fn close_once_lost(
+    session: Weak<InnerSession>,
+    mut lost_sessions: broadcast::Receiver<Vec<u64>>,
+) {
+    loop {
+        let lost = match lost_sessions.recv().await {
+            Ok(lost) => lost,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let Some(inner) = session.upgrade() else {
+            return;
+        };
+        if lost.contains(&inner.shared.id) {
+            tracing::warn!(session_id = inner.shared.id, "Session lost");
+            JaSession { inner }.close("Session lost").await;
+            return;
+        }
+    }
+}
+
+/// Mirrors the transport level transitions of the connection into the session state.
+///
+/// Degrading and recovering are left out, as they're driven by each session's own keep-alives.
+async fn follow_connection_state(
+    connection: ConnectionStateTracker,
+    mut transitions: broadcast::Receiver<StateTransition>,
+    session: ConnectionStateTracker,
+) {
+    loop {
+        match transitions.recv().await {
+            Ok(StateTransition { from, to, reason }) => {
+                let recovered =
+                    from == ConnectionState::Degraded && to == ConnectionState::Connected;
+                if to != ConnectionState::Degraded && !recovered {
+                    session.set(to, reason);
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => {
+                session.set(connection.state(), "Caught up with the connection state");
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
@@ -15,29 +15,51 @@
 //!
 //! ## Runtime
 //!
-//! We currently only support the Tokio runtime and are planning to support more runtimes in the future. For that, we've abstracted the runtime-specific code in the [`jarust_rt`] crate.
+//! We currently support the Tokio runtime, and the browser's event loop when targeting wasm (`wasm-rt` feature). For that, we've abstracted the runtime-specific code in the [`jarust_rt`] crate.
+//!
+//! In the browser, only the WebSocket interfaces are available.
 //!
 //! ## Plugins
 //!
 //! We have a separate crate for Janus plugins, [`jarust_plugins`](https://crates.io/crates/jarust_plugins).
 //!
 
+pub mod jaadmin;
 pub mod jaconfig;
 pub mod jaconnection;
 pub mod jahandle;
 mod jakeepalive;
+pub mod jaload;
 pub mod japlugin;
+pub mod japool;
 pub mod jasession;
 pub mod prelude;
 
 pub use jarust_interface::tgenerator::GenerateTransaction;
 
+use jaadmin::JaAdmin;
 use jaconfig::JaConfig;
 use jaconfig::JanusAPI;
+use jaconfig::JanusAdminAPI;
 use jaconnection::JaConnection;
+use japool::JaConnectionPool;
+use japool::JaPoolConfig;
+use jarust_interface::admin::admin_interface::JanusAdminInterface;
+#[cfg(not(target_family = "wasm"))]
+use jarust_interface::admin::RestfulAdminInterface;
+use jarust_interface::admin::WebSocketAdminInterface;
+#[cfg(feature = "amqp")]
+use jarust_interface::amqp::AmqpInterface;
+#[cfg(not(target_family = "wasm"))]
+use jarust_interface::fallback::FallbackInterface;
 use jarust_interface::janus_interface::ConnectionParams;
 use jarust_interface::janus_interface::JanusInterface;
+#[cfg(feature = "mqtt")]
+use jarust_interface::mqtt::MqttInterface;
+#[cfg(not(target_family = "wasm"))]
 use jarust_interface::restful::RestfulInterface;
+#[cfg(unix)]
+use jarust_interface::unix_socket::UnixSocketInterface;
 use jarust_interface::websocket::WebSocketInterface;
 use tracing::Level;
 
@@ -52,18 +74,14 @@ use tracing::Level;
 ///     .build();
 /// let mut connection = jarust_core::connect(config, ApiInterface::WebSocket, RandomTransactionGenerator).await.unwrap();
 /// ```
-#[cfg(not(target_family = "wasm"))]
+///
+/// In the browser, only [`JanusAPI::WebSocket`] is available.
 pub async fn connect(
     jaconfig: JaConfig,
     api_interface: JanusAPI,
     transaction_generator: impl GenerateTransaction,
 ) -> Result<JaConnection, jarust_interface::Error> {
-    let conn_params = ConnectionParams {
-        url: jaconfig.url,
-        capacity: jaconfig.capacity,
-        apisecret: jaconfig.apisecret,
-        server_root: jaconfig.server_root,
-    };
+    let conn_params = connection_params(jaconfig);
     match api_interface {
         JanusAPI::WebSocket => {
             custom_connect(
@@ -71,23 +89,82 @@ pub async fn connect(
             )
             .await
         }
+        #[cfg(not(target_family = "wasm"))]
         JanusAPI::Restful => {
             custom_connect(
                 RestfulInterface::make_interface(conn_params, transaction_generator).await?,
             )
             .await
         }
+        #[cfg(not(target_family = "wasm"))]
+        JanusAPI::WebSocketWithFallback => {
+            custom_connect(
+                FallbackInterface::make_interface(conn_params, transaction_generator).await?,
+            )
+            .await
+        }
+        #[cfg(target_family = "wasm")]
+        JanusAPI::Restful | JanusAPI::WebSocketWithFallback => {
+            Err(jarust_interface::Error::Unsupported {
+                reason: format!("{api_interface:?} is not available in the browser"),
+            })
+        }
+        #[cfg(unix)]
+        JanusAPI::UnixSocket => {
+            custom_connect(
+                UnixSocketInterface::make_interface(conn_params, transaction_generator).await?,
+            )
+            .await
+        }
+        #[cfg(feature = "mqtt")]
+        JanusAPI::Mqtt => {
+            custom_connect(MqttInterface::make_interface(conn_params, transaction_generator).await?)
+                .await
+        }
+        #[cfg(feature = "amqp")]
+        JanusAPI::Amqp => {
+            custom_connect(AmqpInterface::make_interface(conn_params, transaction_generator).await?)
+                .await
+        }
     }
 }
 
-/// Creates a new connection with janus server from the provided configs
-#[cfg(target_family = "wasm")]
-pub async fn connect(
-    jaconfig: JaConfig,
+/// Connects to each of the janus servers and pools the connections, failing if any of them can't be reached.
+///
+/// ## Example:
+///
+/// ```rust
+/// let configs = ["ws://janus-1:8188/ws", "ws://janus-2:8188/ws"]
+///     .into_iter()
+///     .map(|url| JaConfig {
+///         url: url.to_string(),
+///         ..Default::default()
+///     })
+///     .collect();
+/// let pool_config = JaPoolConfig {
+///     strategy: PlacementStrategy::Sticky,
+///     ..Default::default()
+/// };
+/// let pool = jarust_core::connect_pool(configs, JanusAPI::WebSocket, pool_config, RandomTransactionGenerator).await.unwrap();
+/// let session = pool.create_room_session("janus.plugin.videoroom", 1234, 10, Duration::from_secs(10)).await.unwrap();
+/// ```
+pub async fn connect_pool(
+    jaconfigs: Vec<JaConfig>,
     api_interface: JanusAPI,
-    transaction_generator: impl GenerateTransaction,
-) -> Result<JaConnection, jarust_interface::Error> {
-    todo!("WASM is not supported yet")
+    pool_config: JaPoolConfig,
+    transaction_generator: impl GenerateTransaction + Clone,
+) -> Result<JaConnectionPool, jarust_interface::Error> {
+    let mut connections = Vec::with_capacity(jaconfigs.len());
+    for jaconfig in jaconfigs {
+        let connection = connect(
+            jaconfig,
+            api_interface.clone(),
+            transaction_generator.clone(),
+        )
+        .await?;
+        connections.push(connection);
+    }
+    Ok(JaConnectionPool::open(connections, pool_config).await)
 }
 
 /// Creates a new customized connection with janus servers.
@@ -97,3 +174,80 @@ pub async fn custom_connect(
 ) -> Result<JaConnection, jarust_interface::Error> {
     JaConnection::open(interface).await
 }
+
+/// Creates a new connection with the janus admin/monitor api from the provided configs.
+///
+/// ## Example:
+///
+/// ```rust
+/// let config = JaConfig {
+///     url: "http://localhost:7088".to_string(),
+///     server_root: "admin".to_string(),
+///     admin_secret: Some("janusoverlord".to_string()),
+///     ..Default::default()
+/// };
+/// let admin = jarust_core::connect_admin(config, JanusAdminAPI::Restful, RandomTransactionGenerator).await.unwrap();
+/// ```
+///
+/// In the browser, only [`JanusAdminAPI::WebSocket`] is available.
+pub async fn connect_admin(
+    jaconfig: JaConfig,
+    api_interface: JanusAdminAPI,
+    transaction_generator: impl GenerateTransaction,
+) -> Result<JaAdmin, jarust_interface::Error> {
+    let conn_params = ConnectionParams {
+        token_provider: None,
+        ..connection_params(jaconfig)
+    };
+    match api_interface {
+        JanusAdminAPI::WebSocket => {
+            custom_connect_admin(
+                WebSocketAdminInterface::make_admin_interface(conn_params, transaction_generator)
+                    .await?,
+            )
+            .await
+        }
+        #[cfg(not(target_family = "wasm"))]
+        JanusAdminAPI::Restful => {
+            custom_connect_admin(
+                RestfulAdminInterface::make_admin_interface(conn_params, transaction_generator)
+                    .await?,
+            )
+            .await
+        }
+        #[cfg(target_family = "wasm")]
+        JanusAdminAPI::Restful => Err(jarust_interface::Error::Unsupported {
+            reason: format!("{api_interface:?} is not available in the browser"),
+        }),
+    }
+}
+
+/// Creates a new customized connection with the janus admin/monitor api.
+#[tracing::instrument(level = Level::TRACE, skip_all)]
+pub async fn custom_connect_admin(
+    interface: impl JanusAdminInterface,
+) -> Result<JaAdmin, jarust_interface::Error> {
+    Ok(JaAdmin::open(interface))
+}
+
+fn connection_params(jaconfig: JaConfig) -> ConnectionParams {
+    ConnectionParams {
+        url: jaconfig.url,
+        capacity: jaconfig.capacity,
+        transaction_ttl: jaconfig.transaction_ttl,
+        apisecret: jaconfig.apisecret,
+        token_provider: jaconfig.token_provider,
+        admin_secret: jaconfig.admin_secret,
+        server_root: jaconfig.server_root,
+        headers: jaconfig.headers,
+        tls: jaconfig.tls,
+        proxy: jaconfig.proxy,
+        writer: jaconfig.writer,
+        deflate: jaconfig.deflate,
+        heartbeat: jaconfig.heartbeat,
+        reconnect: jaconfig.reconnect,
+        long_poll: jaconfig.long_poll,
+        fallback: jaconfig.fallback,
+        event_channel: jaconfig.event_channel,
+    }
+}
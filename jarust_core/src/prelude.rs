@@ -1,3 +1,4 @@
+pub use crate::jaadmin::JaAdmin;
 pub use crate::jahandle::JaHandle;
 pub use crate::japlugin::Attach;
 pub use crate::japlugin::PluginTask;
@@ -0,0 +1,90 @@
+//! Runs in a headless browser against the fake janus server of `examples/fake_janus.rs`:
+//!
+//! ```sh
+//! cargo run -p jarust_core --example fake_janus &
+//! wasm-pack test --headless --chrome jarust_core --no-default-features --features wasm-rt
+//! ```
+//!
+//! The server's url can be overridden at build time with `JARUST_FAKE_JANUS_URL`.
+#![cfg(target_family = "wasm")]
+
+use jarust_core::connect;
+use jarust_core::jaconfig::JaConfig;
+use jarust_core::jaconfig::JanusAPI;
+use jarust_core::prelude::Attach;
+use jarust_interface::japrotocol::JaHandleEvent;
+use jarust_interface::japrotocol::ResponseType;
+use jarust_interface::tgenerator::RandomTransactionGenerator;
+use serde_json::json;
+use std::time::Duration;
+use wasm_bindgen_test::wasm_bindgen_test;
+use wasm_bindgen_test::wasm_bindgen_test_configure;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn config() -> JaConfig {
+    JaConfig {
+        url: option_env!("JARUST_FAKE_JANUS_URL")
+            .unwrap_or("ws://127.0.0.1:8189")
+            .to_string(),
+        ..Default::default()
+    }
+}
+
+#[wasm_bindgen_test]
+async fn it_should_talk_to_a_plugin_from_the_browser() {
+    let timeout = Duration::from_secs(5);
+    let mut connection = connect(config(), JanusAPI::WebSocket, RandomTransactionGenerator)
+        .await
+        .unwrap();
+    let session = connection.create_session(1, timeout).await.unwrap();
+    let (handle, mut events) = session
+        .attach("janus.plugin.echotest".to_string(), timeout)
+        .await
+        .unwrap();
+
+    handle
+        .send_waiton_ack(json!({"audio": true}), timeout)
+        .await
+        .unwrap();
+    let event = events.recv().await.unwrap();
+    assert!(matches!(
+        event.janus,
+        ResponseType::Event(JaHandleEvent::PluginEvent { .. })
+    ));
+
+    // Long enough for a couple of keep-alives to go through the browser's timers
+    jarust_rt::sleep(Duration::from_millis(2500)).await;
+    handle.detach().await.unwrap();
+    session.destroy(timeout).await.unwrap();
+}
+
+#[wasm_bindgen_test]
+async fn it_should_time_out_unanswered_requests() {
+    let timeout = Duration::from_millis(200);
+    let mut connection = connect(config(), JanusAPI::WebSocket, RandomTransactionGenerator)
+        .await
+        .unwrap();
+    let session = connection.create_session(10, timeout).await.unwrap();
+    let (handle, _) = session
+        .attach("janus.plugin.echotest".to_string(), timeout)
+        .await
+        .unwrap();
+
+    let result = handle
+        .send_waiton_ack(json!({"request": "ignore"}), timeout)
+        .await;
+    assert!(matches!(
+        result,
+        Err(jarust_interface::Error::RequestTimeout)
+    ));
+}
+
+#[wasm_bindgen_test]
+async fn it_should_only_connect_over_websocket() {
+    let result = connect(config(), JanusAPI::Restful, RandomTransactionGenerator).await;
+    assert!(matches!(
+        result,
+        Err(jarust_interface::Error::Unsupported { .. })
+    ));
+}
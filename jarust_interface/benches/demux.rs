@@ -0,0 +1,194 @@
+//! Throughput of the inbound path of the websocket interface, from the frames janus pushes
+//! to the event channels of the handles.
+
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+use futures_util::SinkExt;
+use futures_util::StreamExt;
+use jarust_interface::event_channel::EventReceiver;
+use jarust_interface::janus_interface::ConnectionParams;
+use jarust_interface::janus_interface::JanusInterface;
+use jarust_interface::japrotocol::JaResponse;
+use jarust_interface::tgenerator::RandomTransactionGenerator;
+use jarust_interface::websocket::WebSocketInterface;
+use serde_json::json;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::handshake::server::Callback;
+use tokio_tungstenite::tungstenite::handshake::server::ErrorResponse;
+use tokio_tungstenite::tungstenite::handshake::server::Request;
+use tokio_tungstenite::tungstenite::handshake::server::Response;
+use tokio_tungstenite::tungstenite::Message;
+
+const SESSION_ID: u64 = 1;
+const EVENTS_PER_HANDLE: usize = 100;
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What the fake server pushes on each iteration
+#[derive(Clone, Copy)]
+enum Burst {
+    /// Events for every attached handle
+    Routed,
+    /// Events for handles nobody attached, followed by a single routed event marking the end
+    Unrouted,
+}
+
+struct Fixture {
+    _interface: WebSocketInterface,
+    handles: Vec<EventReceiver<JaResponse>>,
+    bursts: mpsc::UnboundedSender<Burst>,
+}
+
+/// Picks the janus subprotocol during the handshake
+struct JanusProtocol;
+
+impl Callback for JanusProtocol {
+    fn on_request(self, _: &Request, mut response: Response) -> Result<Response, ErrorResponse> {
+        response
+            .headers_mut()
+            .insert("Sec-Websocket-Protocol", "janus-protocol".parse().unwrap());
+        Ok(response)
+    }
+}
+
+fn talking(sender: u64) -> String {
+    json!({
+        "janus": "event",
+        "session_id": SESSION_ID,
+        "sender": sender,
+        "plugindata": {
+            "plugin": "janus.plugin.videoroom",
+            "data": {
+                "videoroom": "talking",
+                "room": 1234,
+                "id": sender,
+                "audio-level-dBov-avg": -45.2
+            }
+        }
+    })
+    .to_string()
+}
+
+/// Serves a session with `handles` handles, then pushes a burst of events whenever asked to
+async fn serve(listener: TcpListener, handles: u64, mut bursts: mpsc::UnboundedReceiver<Burst>) {
+    let (stream, _) = listener.accept().await.unwrap();
+    let mut ws = tokio_tungstenite::accept_hdr_async(stream, JanusProtocol)
+        .await
+        .unwrap();
+
+    // The session, then its handles
+    for id in SESSION_ID..=SESSION_ID + handles {
+        let message = ws.next().await.unwrap().unwrap();
+        let request: Value = serde_json::from_slice(&message.into_data()).unwrap();
+        let response =
+            json!({"janus": "success", "transaction": request["transaction"], "data": {"id": id}});
+        ws.send(Message::Text(response.to_string().into()))
+            .await
+            .unwrap();
+    }
+
+    let routed = (1..=handles)
+        .map(|handle| talking(SESSION_ID + handle))
+        .collect::<Vec<_>>();
+    let unrouted = talking(u64::MAX);
+    while let Some(burst) = bursts.recv().await {
+        match burst {
+            Burst::Routed => {
+                for _ in 0..EVENTS_PER_HANDLE {
+                    for event in &routed {
+                        ws.feed(Message::Text(event.clone().into())).await.unwrap();
+                    }
+                }
+            }
+            Burst::Unrouted => {
+                for _ in 0..EVENTS_PER_HANDLE * routed.len() {
+                    ws.feed(Message::Text(unrouted.clone().into()))
+                        .await
+                        .unwrap();
+                }
+                ws.feed(Message::Text(routed[0].clone().into()))
+                    .await
+                    .unwrap();
+            }
+        }
+        ws.flush().await.unwrap();
+    }
+}
+
+async fn setup(handles: u64) -> Fixture {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let (bursts, bursts_rx) = mpsc::unbounded_channel();
+    tokio::spawn(serve(listener, handles, bursts_rx));
+
+    let conn_params = ConnectionParams {
+        url,
+        ..Default::default()
+    };
+    let interface = WebSocketInterface::make_interface(conn_params, RandomTransactionGenerator)
+        .await
+        .unwrap();
+    let session_id = interface.create(TIMEOUT).await.unwrap();
+    let mut receivers = Vec::new();
+    for _ in 0..handles {
+        let (_, receiver) = interface
+            .attach(
+                session_id,
+                "janus.plugin.videoroom".to_string(),
+                None,
+                TIMEOUT,
+            )
+            .await
+            .unwrap();
+        receivers.push(receiver);
+    }
+    Fixture {
+        _interface: interface,
+        handles: receivers,
+        bursts,
+    }
+}
+
+fn demux(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("demux");
+    for handles in [1, 10, 100] {
+        let mut fixture = runtime.block_on(setup(handles));
+        group.throughput(Throughput::Elements(handles * EVENTS_PER_HANDLE as u64));
+
+        group.bench_function(BenchmarkId::new("routed", handles), |b| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    fixture.bursts.send(Burst::Routed).unwrap();
+                    let mut events = Vec::with_capacity(EVENTS_PER_HANDLE);
+                    for handle in &mut fixture.handles {
+                        events.clear();
+                        while events.len() < EVENTS_PER_HANDLE {
+                            let missing = EVENTS_PER_HANDLE - events.len();
+                            handle.recv_many(&mut events, missing).await;
+                        }
+                    }
+                })
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("unrouted", handles), |b| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    fixture.bursts.send(Burst::Unrouted).unwrap();
+                    fixture.handles[0].recv().await.unwrap();
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, demux);
+criterion_main!(benches);
@@ -0,0 +1,110 @@
+use crate::janus_interface::ConnectionParams;
+use crate::japrotocol::ErrorResponse;
+use crate::tgenerator::GenerateTransaction;
+use crate::Error;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt::Debug;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// An admin api request, optionally scoped to a session or a handle
+#[derive(Clone, PartialEq, Debug)]
+pub struct AdminRequest {
+    pub session_id: Option<u64>,
+    pub handle_id: Option<u64>,
+    pub body: Value,
+}
+
+/// [`JanusAdminInterface`] is the contract of the transports of the janus admin/monitor api,
+/// full docs: <https://janus.conf.meetecho.com/docs/admin.html>
+///
+/// The `admin_secret` of the connection params is attached to every request.
+#[async_trait::async_trait]
+pub trait JanusAdminInterface: Debug + Send + Sync + 'static {
+    /// Constructs a new admin interface with the given connection parameters and transaction generator.
+    async fn make_admin_interface(
+        conn_params: ConnectionParams,
+        transaction_generator: impl GenerateTransaction,
+    ) -> Result<Self, Error>
+    where
+        Self: Sized;
+
+    /// Internal method to send an admin request and wait for its successful response,
+    /// see `send_admin_request` for the public method with a typed response.
+    async fn internal_send_admin_request(
+        &self,
+        request: AdminRequest,
+        timeout: Duration,
+    ) -> Result<Value, Error>;
+
+    /// Returns the name of the interface (for the debug trait)
+    fn name(&self) -> Box<str> {
+        "Janus Admin Interface".to_string().into_boxed_str()
+    }
+}
+
+impl dyn JanusAdminInterface {
+    /// Sends an admin request and parses its response.
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    pub async fn send_admin_request<R>(
+        &self,
+        request: AdminRequest,
+        timeout: Duration,
+    ) -> Result<R, Error>
+    where
+        R: DeserializeOwned,
+    {
+        let response = self.internal_send_admin_request(request, timeout).await?;
+        serde_json::from_value::<R>(response).map_err(|error| {
+            tracing::error!("Failed to parse with error {error:#?}");
+            Error::UnexpectedResponse
+        })
+    }
+}
+
+/// Maps a janus error response (`{"janus": "error", ...}`) to [`Error::JanusError`]
+pub(crate) fn admin_error(response: &Value) -> Option<Error> {
+    if response["janus"] != "error" {
+        return None;
+    }
+    let what = match serde_json::from_value::<ErrorResponse>(response["error"].clone()) {
+        Ok(error) => Error::JanusError {
+            code: error.code,
+            reason: error.reason,
+        },
+        Err(_) => Error::UnexpectedResponse,
+    };
+    tracing::error!("{what}");
+    Some(what)
+}
+
+#[derive(Clone)]
+pub struct JanusAdminInterfaceImpl {
+    inner: Arc<dyn JanusAdminInterface>,
+}
+
+impl Deref for JanusAdminInterfaceImpl {
+    type Target = Arc<dyn JanusAdminInterface>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl JanusAdminInterfaceImpl {
+    pub fn new(interface: impl JanusAdminInterface) -> Self {
+        Self {
+            inner: Arc::new(interface),
+        }
+    }
+}
+
+impl Debug for JanusAdminInterfaceImpl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AdminInterface")
+            .field(&self.inner.name())
+            .finish()
+    }
+}
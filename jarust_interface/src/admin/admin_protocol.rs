@@ -0,0 +1,145 @@
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Map;
+use serde_json::Value;
+
+/// Response of `list_sessions`
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ListSessionsRsp {
+    pub sessions: Vec<u64>,
+}
+
+/// Response of `list_handles`
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ListHandlesRsp {
+    pub session_id: u64,
+    pub handles: Vec<u64>,
+}
+
+/// Response of `handle_info`
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct HandleInfoRsp {
+    pub session_id: u64,
+    pub handle_id: u64,
+    pub info: HandleInfo,
+}
+
+/// The details of a handle, only the common fields are typed as the rest varies across janus versions and plugins
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct HandleInfo {
+    pub session_id: u64,
+    pub handle_id: u64,
+    pub opaque_id: Option<String>,
+    pub created: u64,
+    pub current_time: u64,
+    pub plugin: Option<String>,
+    pub plugin_specific: Option<Value>,
+    pub flags: Option<Value>,
+    pub sdps: Option<Value>,
+    pub streams: Option<Vec<Value>>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+/// Response of `set_log_level`
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct LogLevelRsp {
+    pub level: u8,
+}
+
+/// Response of `add_token`
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct AddTokenRsp {
+    pub data: AddTokenData,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct AddTokenData {
+    /// The plugins the token is allowed to access
+    pub plugins: Vec<String>,
+}
+
+/// Response of `list_tokens`
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ListTokensRsp {
+    pub data: ListTokensData,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ListTokensData {
+    pub tokens: Vec<StoredToken>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct StoredToken {
+    pub token: String,
+    pub allowed_plugins: Vec<String>,
+}
+
+/// Response of `message_plugin` and `query_eventhandler`
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct AdminPluginRsp<R> {
+    pub response: R,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HandleInfoRsp;
+    use super::ListTokensRsp;
+    use super::StoredToken;
+    use serde_json::json;
+
+    #[test]
+    fn it_parse_handle_info_rsp() {
+        let rsp = json!({
+            "janus": "success",
+            "session_id": 1,
+            "transaction": "abc",
+            "handle_id": 2,
+            "info": {
+                "session_id": 1,
+                "session_last_activity": 1000,
+                "session_timeout": 60,
+                "session_transport": "janus.transport.websockets",
+                "handle_id": 2,
+                "opaque_id": "echotest-abc",
+                "loop-running": true,
+                "created": 900,
+                "current_time": 1100,
+                "plugin": "janus.plugin.echotest",
+                "plugin_specific": {"audio_active": true},
+                "flags": {"got-offer": false},
+                "sdps": {},
+                "queued-packets": 0,
+                "streams": []
+            }
+        });
+        let rsp = serde_json::from_value::<HandleInfoRsp>(rsp).unwrap();
+        assert_eq!(rsp.handle_id, 2);
+        assert_eq!(rsp.info.opaque_id, Some("echotest-abc".to_string()));
+        assert_eq!(rsp.info.plugin, Some("janus.plugin.echotest".to_string()));
+        assert_eq!(
+            rsp.info.other["session_transport"],
+            "janus.transport.websockets"
+        );
+    }
+
+    #[test]
+    fn it_parse_list_tokens_rsp() {
+        let rsp = json!({
+            "janus": "success",
+            "transaction": "abc",
+            "data": {
+                "tokens": [{"token": "secret", "allowed_plugins": ["janus.plugin.echotest"]}]
+            }
+        });
+        let rsp = serde_json::from_value::<ListTokensRsp>(rsp).unwrap();
+        assert_eq!(
+            rsp.data.tokens,
+            vec![StoredToken {
+                token: "secret".to_string(),
+                allowed_plugins: vec!["janus.plugin.echotest".to_string()]
+            }]
+        );
+    }
+}
@@ -0,0 +1,9 @@
+pub mod admin_interface;
+pub mod admin_protocol;
+#[cfg(not(target_family = "wasm"))]
+pub mod restful_admin_interface;
+pub mod websocket_admin_interface;
+
+#[cfg(not(target_family = "wasm"))]
+pub use restful_admin_interface::RestfulAdminInterface;
+pub use websocket_admin_interface::WebSocketAdminInterface;
@@ -0,0 +1,223 @@
+use super::admin_interface::admin_error;
+use super::admin_interface::AdminRequest;
+use super::admin_interface::JanusAdminInterface;
+use crate::janus_interface::ConnectionParams;
+use crate::restful::http_client::make_client;
+use crate::restful::http_client::HttpClient;
+use crate::tgenerator::GenerateTransaction;
+use crate::tgenerator::TransactionGenerator;
+use crate::Error;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug)]
+struct Shared {
+    admin_secret: Option<String>,
+    transaction_generator: TransactionGenerator,
+    client: HttpClient,
+    url: String,
+}
+
+#[derive(Debug)]
+struct InnerRestfulAdminInterface {
+    shared: Shared,
+}
+
+/// Admin api over HTTP, the requests are posted to `{url}/{server_root}`, where the server root
+/// should match the `admin_base_path` of the janus http transport (`admin` by default).
+#[derive(Debug, Clone)]
+pub struct RestfulAdminInterface {
+    inner: Arc<InnerRestfulAdminInterface>,
+}
+
+impl RestfulAdminInterface {
+    fn decorate_request(&self, mut request: Value) -> (Value, String) {
+        let transaction = self
+            .inner
+            .shared
+            .transaction_generator
+            .generate_transaction();
+        if let Some(admin_secret) = self.inner.shared.admin_secret.clone() {
+            request["admin_secret"] = admin_secret.into();
+        };
+        request["transaction"] = transaction.clone().into();
+        (request, transaction)
+    }
+}
+
+#[async_trait::async_trait]
+impl JanusAdminInterface for RestfulAdminInterface {
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn make_admin_interface(
+        conn_params: ConnectionParams,
+        transaction_generator: impl GenerateTransaction,
+    ) -> Result<Self, Error> {
+        tracing::debug!("Creating new Restful Admin Interface");
+        let client = make_client(&conn_params)?;
+        let shared = Shared {
+            admin_secret: conn_params.admin_secret,
+            transaction_generator: TransactionGenerator::new(transaction_generator),
+            client,
+            url: format!("{}/{}", conn_params.url, conn_params.server_root),
+        };
+        Ok(Self {
+            inner: Arc::new(InnerRestfulAdminInterface { shared }),
+        })
+    }
+
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn internal_send_admin_request(
+        &self,
+        request: AdminRequest,
+        timeout: Duration,
+    ) -> Result<Value, Error> {
+        let url = &self.inner.shared.url;
+        let url = match (request.session_id, request.handle_id) {
+            (Some(session_id), Some(handle_id)) => format!("{url}/{session_id}/{handle_id}"),
+            (Some(session_id), None) => format!("{url}/{session_id}"),
+            (None, None) => url.to_string(),
+            (None, Some(handle_id)) => {
+                return Err(Error::InvalidJanusRequest {
+                    reason: format!("Handle {handle_id} has no session"),
+                })
+            }
+        };
+        let (body, _) = self.decorate_request(request.body);
+
+        let response = self
+            .inner
+            .shared
+            .client
+            .post(url)
+            .json(&body)
+            .timeout(timeout)
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+        match admin_error(&response) {
+            Some(what) => Err(what),
+            None => Ok(response),
+        }
+    }
+
+    fn name(&self) -> Box<str> {
+        "Restful Admin Interface".to_string().into_boxed_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RestfulAdminInterface;
+    use crate::admin::admin_interface::AdminRequest;
+    use crate::admin::admin_interface::JanusAdminInterface;
+    use crate::janus_interface::ConnectionParams;
+    use crate::restful::restful_interface::tests::serve;
+    use crate::tgenerator::RandomTransactionGenerator;
+    use crate::Error;
+    use serde_json::json;
+    use serde_json::Value;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    /// Answers every request with the given json, and reports the request lines and bodies
+    fn serve_json(
+        listener: TcpListener,
+        response: Value,
+    ) -> mpsc::UnboundedReceiver<(String, Value)> {
+        let (requests, received) = mpsc::unbounded_channel();
+        tokio::spawn(serve(listener, move |request_line, body| {
+            let _ = requests.send((request_line, body));
+            let response = response.clone();
+            async move { response }
+        }));
+        received
+    }
+
+    async fn make_interface(listener: &TcpListener) -> RestfulAdminInterface {
+        let conn_params = ConnectionParams {
+            url: format!("http://{}", listener.local_addr().unwrap()),
+            server_root: "admin".to_string(),
+            admin_secret: Some("overlord".to_string()),
+            ..Default::default()
+        };
+        RestfulAdminInterface::make_admin_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_should_post_handle_requests_to_the_handle_path() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let interface = make_interface(&listener).await;
+        let mut requests = serve_json(
+            listener,
+            json!({"janus": "success", "session_id": 1, "handle_id": 2, "info": {}}),
+        );
+
+        let response = interface
+            .internal_send_admin_request(
+                AdminRequest {
+                    session_id: Some(1),
+                    handle_id: Some(2),
+                    body: json!({"janus": "handle_info"}),
+                },
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+        let (request_line, body) = requests.recv().await.unwrap();
+
+        assert_eq!(request_line, "POST /admin/1/2 HTTP/1.1");
+        assert_eq!(body["janus"], "handle_info");
+        assert_eq!(body["admin_secret"], "overlord");
+        assert!(body["transaction"].is_string());
+        assert_eq!(response["handle_id"], 2);
+    }
+
+    #[tokio::test]
+    async fn it_should_map_janus_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let interface = make_interface(&listener).await;
+        let _requests = serve_json(
+            listener,
+            json!({"janus": "error", "error": {"code": 403, "reason": "Unauthorized request (wrong or missing secret/token)"}}),
+        );
+
+        let result = interface
+            .internal_send_admin_request(
+                AdminRequest {
+                    session_id: None,
+                    handle_id: None,
+                    body: json!({"janus": "list_sessions"}),
+                },
+                Duration::from_secs(5),
+            )
+            .await;
+
+        assert!(matches!(result, Err(Error::JanusError { code: 403, .. })));
+    }
+
+    #[tokio::test]
+    async fn it_should_refuse_a_handle_without_a_session() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let interface = make_interface(&listener).await;
+        let mut requests = serve_json(listener, json!({"janus": "success"}));
+
+        let result = interface
+            .internal_send_admin_request(
+                AdminRequest {
+                    session_id: None,
+                    handle_id: Some(2),
+                    body: json!({"janus": "handle_info"}),
+                },
+                Duration::from_secs(5),
+            )
+            .await;
+
+        assert!(matches!(result, Err(Error::InvalidJanusRequest { .. })));
+        assert!(requests.try_recv().is_err());
+    }
+}
@@ -0,0 +1,246 @@
+use super::admin_interface::admin_error;
+use super::admin_interface::AdminRequest;
+use super::admin_interface::JanusAdminInterface;
+use crate::janus_interface::ConnectionParams;
+use crate::tgenerator::GenerateTransaction;
+use crate::tgenerator::TransactionGenerator;
+use crate::websocket::pending::PendingRequests;
+use crate::websocket::websocket_client::WebSocketClient;
+use crate::websocket::websocket_client::WebSocketReceivers;
+use crate::Error;
+use jarust_rt::JaTask;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+#[derive(Debug)]
+struct Shared {
+    tasks: Vec<JaTask>,
+    admin_secret: Option<String>,
+    transaction_generator: TransactionGenerator,
+    responses: PendingRequests<Value>,
+}
+
+#[derive(Debug)]
+struct Exclusive {
+    ws: WebSocketClient,
+}
+
+#[derive(Debug)]
+struct InnerWebSocketAdminInterface {
+    shared: Shared,
+    exclusive: Mutex<Exclusive>,
+}
+
+/// Admin api over WebSocket, using the `janus-admin-protocol` subprotocol.
+///
+/// The url should point to the admin websocket of the janus websockets transport (e.g. `ws://localhost:7188`).
+#[derive(Debug, Clone)]
+pub struct WebSocketAdminInterface {
+    inner: Arc<InnerWebSocketAdminInterface>,
+}
+
+impl WebSocketAdminInterface {
+    fn decorate_request(&self, mut request: Value) -> (Value, String) {
+        let transaction = self
+            .inner
+            .shared
+            .transaction_generator
+            .generate_transaction();
+        if let Some(admin_secret) = self.inner.shared.admin_secret.clone() {
+            request["admin_secret"] = admin_secret.into();
+        };
+        request["transaction"] = transaction.clone().into();
+        (request, transaction)
+    }
+}
+
+#[async_trait::async_trait]
+impl JanusAdminInterface for WebSocketAdminInterface {
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn make_admin_interface(
+        conn_params: ConnectionParams,
+        transaction_generator: impl GenerateTransaction,
+    ) -> Result<Self, Error> {
+        tracing::debug!("Creating WebSocket Admin Interface");
+        let mut websocket = WebSocketClient::new()
+            .with_headers(conn_params.headers.header_map()?)
+            .with_tls(conn_params.tls.clone())
+            .with_proxy(conn_params.proxy.clone())
+            .with_deflate(conn_params.deflate)
+            .with_writer_config(conn_params.writer)
+            .with_heartbeat(conn_params.heartbeat);
+        let WebSocketReceivers { mut inbound, .. } = websocket
+            .connect_with_protocol(
+                &conn_params.url,
+                conn_params
+                    .headers
+                    .subprotocol
+                    .as_deref()
+                    .unwrap_or("janus-admin-protocol"),
+            )
+            .await?;
+        let responses = PendingRequests::new();
+
+        let rsp_task = jarust_rt::spawn("Admin responses gathering task", {
+            let responses = responses.clone();
+            async move {
+                while let Some(message) = inbound.recv().await {
+                    let response = match serde_json::from_slice::<Value>(&message) {
+                        Ok(response) => response,
+                        Err(what) => {
+                            tracing::error!("Error parsing response: {what}");
+                            continue;
+                        }
+                    };
+                    if let Some(transaction) = response["transaction"].as_str().map(str::to_string)
+                    {
+                        responses.resolve(&transaction, response);
+                    }
+                }
+            }
+        });
+
+        let shared = Shared {
+            tasks: vec![rsp_task],
+            admin_secret: conn_params.admin_secret,
+            transaction_generator: TransactionGenerator::new(transaction_generator),
+            responses,
+        };
+        let exclusive = Exclusive { ws: websocket };
+        Ok(Self {
+            inner: Arc::new(InnerWebSocketAdminInterface {
+                shared,
+                exclusive: Mutex::new(exclusive),
+            }),
+        })
+    }
+
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn internal_send_admin_request(
+        &self,
+        request: AdminRequest,
+        timeout: Duration,
+    ) -> Result<Value, Error> {
+        let mut body = request.body;
+        if let Some(session_id) = request.session_id {
+            body["session_id"] = session_id.into();
+        }
+        if let Some(handle_id) = request.handle_id {
+            body["handle_id"] = handle_id.into();
+        }
+        let (body, transaction) = self.decorate_request(body);
+        let pending = self
+            .inner
+            .shared
+            .responses
+            .register(&transaction, request.session_id);
+        self.inner
+            .exclusive
+            .lock()
+            .await
+            .ws
+            .send(body.to_string().as_bytes())
+            .await?;
+
+        let response = pending.wait(timeout).await?;
+        match admin_error(&response) {
+            Some(what) => Err(what),
+            None => Ok(response),
+        }
+    }
+
+    fn name(&self) -> Box<str> {
+        "WebSocket Admin Interface".to_string().into_boxed_str()
+    }
+}
+
+impl Drop for InnerWebSocketAdminInterface {
+    fn drop(&mut self) {
+        self.shared.tasks.iter().for_each(|task| {
+            task.cancel();
+        });
+    }
+}
+
+#[cfg(all(test, not(target_family = "wasm")))]
+mod tests {
+    use super::WebSocketAdminInterface;
+    use crate::admin::admin_interface::AdminRequest;
+    use crate::admin::admin_interface::JanusAdminInterface;
+    use crate::janus_interface::ConnectionParams;
+    use crate::tgenerator::RandomTransactionGenerator;
+    use crate::websocket::handshake::Accept;
+    use futures_util::SinkExt;
+    use futures_util::StreamExt;
+    use serde_json::json;
+    use serde_json::Value;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::handshake::server::Request;
+    use tokio_tungstenite::tungstenite::handshake::server::Response;
+    use tokio_tungstenite::tungstenite::Message;
+
+    #[tokio::test]
+    async fn it_should_scope_requests_in_the_body() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let callback = Accept(|request: &Request, response: &mut Response| {
+                assert_eq!(
+                    request.headers()["Sec-Websocket-Protocol"],
+                    "janus-admin-protocol"
+                );
+                response.headers_mut().insert(
+                    "Sec-Websocket-Protocol",
+                    "janus-admin-protocol".parse().unwrap(),
+                );
+            });
+            let mut ws = tokio_tungstenite::accept_hdr_async(stream, callback)
+                .await
+                .unwrap();
+            let message = ws.next().await.unwrap().unwrap();
+            let request: Value = serde_json::from_slice(&message.into_data()).unwrap();
+            let response = json!({
+                "janus": "success",
+                "transaction": request["transaction"],
+                "session_id": request["session_id"],
+                "handles": [2, 3]
+            });
+            ws.send(Message::Text(response.to_string().into()))
+                .await
+                .unwrap();
+            request
+        });
+
+        let conn_params = ConnectionParams {
+            url,
+            admin_secret: Some("overlord".to_string()),
+            ..Default::default()
+        };
+        let interface =
+            WebSocketAdminInterface::make_admin_interface(conn_params, RandomTransactionGenerator)
+                .await
+                .unwrap();
+        let response = interface
+            .internal_send_admin_request(
+                AdminRequest {
+                    session_id: Some(1),
+                    handle_id: None,
+                    body: json!({"janus": "list_handles"}),
+                },
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+        let request = server.await.unwrap();
+
+        assert_eq!(request["janus"], "list_handles");
+        assert_eq!(request["session_id"], 1);
+        assert_eq!(request["admin_secret"], "overlord");
+        assert_eq!(response["handles"], json!([2, 3]));
+    }
+}
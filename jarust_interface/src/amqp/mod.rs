@@ -0,0 +1,6 @@
+mod amqp_client;
+
+pub mod amqp_interface;
+
+pub use amqp_client::AmqpClient;
+pub use amqp_interface::AmqpInterface;
@@ -0,0 +1,30 @@
+/// Snapshot of the `permessage-deflate` compression of a websocket interface, across its reconnections.
+///
+/// Only the messages that were actually compressed are counted, i.e. none when the server declined the extension.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct CompressionMetrics {
+    /// Payload bytes of the compressed messages received, as they were received
+    pub received_compressed: u64,
+    /// Payload bytes of the compressed messages received, once inflated
+    pub received_inflated: u64,
+    /// Payload bytes of the messages sent, before compression
+    pub sent_uncompressed: u64,
+    /// Payload bytes of the messages sent, once compressed
+    pub sent_compressed: u64,
+}
+
+impl CompressionMetrics {
+    /// Compressed over inflated size of the messages received, `None` until one is received
+    pub fn received_ratio(&self) -> Option<f64> {
+        ratio(self.received_compressed, self.received_inflated)
+    }
+
+    /// Compressed over uncompressed size of the messages sent, `None` until one is sent
+    pub fn sent_ratio(&self) -> Option<f64> {
+        ratio(self.sent_compressed, self.sent_uncompressed)
+    }
+}
+
+fn ratio(compressed: u64, uncompressed: u64) -> Option<f64> {
+    (uncompressed > 0).then(|| compressed as f64 / uncompressed as f64)
+}
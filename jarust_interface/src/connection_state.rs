@@ -0,0 +1,159 @@
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::sync::watch;
+
+const TRANSITIONS_CAPACITY: usize = 32;
+
+/// The health of a connection (or a session) with the janus server.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum ConnectionState {
+    /// The transport is being established or re-established
+    Connecting,
+    /// The transport is up and running
+    Connected,
+    /// The transport is up, but requests are failing (e.g. keep-alives)
+    Degraded,
+    /// The transport dropped, it might be re-established
+    Disconnected,
+    /// Closed for good, no more transitions will happen
+    Closed,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct StateTransition {
+    pub from: ConnectionState,
+    pub to: ConnectionState,
+    pub reason: String,
+}
+
+/// Holds the current [`ConnectionState`] and broadcasts its transitions.
+///
+/// Cloning the tracker is cheap, all the clones share the same state.
+#[derive(Clone, Debug)]
+pub struct ConnectionStateTracker {
+    state: Arc<watch::Sender<ConnectionState>>,
+    transitions: broadcast::Sender<StateTransition>,
+}
+
+impl ConnectionStateTracker {
+    pub fn new(initial: ConnectionState) -> Self {
+        let (state, _) = watch::channel(initial);
+        let (transitions, _) = broadcast::channel(TRANSITIONS_CAPACITY);
+        Self {
+            state: Arc::new(state),
+            transitions,
+        }
+    }
+
+    /// Returns the current state
+    pub fn state(&self) -> ConnectionState {
+        *self.state.borrow()
+    }
+
+    /// Returns a receiver that is notified whenever the state changes
+    pub fn watch(&self) -> watch::Receiver<ConnectionState> {
+        self.state.subscribe()
+    }
+
+    /// Returns a receiver of the upcoming transitions along with their reasons
+    pub fn transitions(&self) -> broadcast::Receiver<StateTransition> {
+        self.transitions.subscribe()
+    }
+
+    /// Moves to the given state, [`ConnectionState::Closed`] is terminal and can't be left
+    pub fn set(&self, to: ConnectionState, reason: impl Into<String>) {
+        self.transition(to, reason.into(), |from| from != ConnectionState::Closed);
+    }
+
+    /// Moves from [`ConnectionState::Connected`] to [`ConnectionState::Degraded`], no-op otherwise
+    pub fn degrade(&self, reason: impl Into<String>) {
+        self.transition(ConnectionState::Degraded, reason.into(), |from| {
+            from == ConnectionState::Connected
+        });
+    }
+
+    /// Moves from [`ConnectionState::Degraded`] back to [`ConnectionState::Connected`], no-op otherwise
+    pub fn recover(&self, reason: impl Into<String>) {
+        self.transition(ConnectionState::Connected, reason.into(), |from| {
+            from == ConnectionState::Degraded
+        });
+    }
+
+    fn transition(
+        &self,
+        to: ConnectionState,
+        reason: String,
+        allowed: impl FnOnce(ConnectionState) -> bool,
+    ) {
+        let mut from = to;
+        let modified = self.state.send_if_modified(|state| {
+            if *state == to || !allowed(*state) {
+                return false;
+            }
+            from = std::mem::replace(state, to);
+            true
+        });
+        if modified {
+            tracing::debug!(?from, ?to, reason, "Connection state changed");
+            let _ = self.transitions.send(StateTransition { from, to, reason });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConnectionState;
+    use super::ConnectionStateTracker;
+    use super::StateTransition;
+
+    #[test]
+    fn it_should_broadcast_transitions_with_reasons() {
+        let tracker = ConnectionStateTracker::new(ConnectionState::Connecting);
+        let mut transitions = tracker.transitions();
+
+        tracker.set(ConnectionState::Connected, "connected");
+        tracker.set(ConnectionState::Connected, "still connected");
+        tracker.set(ConnectionState::Disconnected, "socket dropped");
+
+        assert_eq!(tracker.state(), ConnectionState::Disconnected);
+        assert_eq!(
+            transitions.try_recv().unwrap(),
+            StateTransition {
+                from: ConnectionState::Connecting,
+                to: ConnectionState::Connected,
+                reason: "connected".to_string()
+            }
+        );
+        assert_eq!(
+            transitions.try_recv().unwrap(),
+            StateTransition {
+                from: ConnectionState::Connected,
+                to: ConnectionState::Disconnected,
+                reason: "socket dropped".to_string()
+            }
+        );
+        assert!(transitions.try_recv().is_err());
+    }
+
+    #[test]
+    fn it_should_only_degrade_and_recover_a_connected_state() {
+        let tracker = ConnectionStateTracker::new(ConnectionState::Disconnected);
+        tracker.degrade("keep-alive failed");
+        assert_eq!(tracker.state(), ConnectionState::Disconnected);
+
+        tracker.set(ConnectionState::Connected, "reconnected");
+        tracker.degrade("keep-alive failed");
+        assert_eq!(tracker.state(), ConnectionState::Degraded);
+
+        tracker.recover("keep-alive succeeded");
+        assert_eq!(tracker.state(), ConnectionState::Connected);
+    }
+
+    #[test]
+    fn it_should_not_leave_the_closed_state() {
+        let tracker = ConnectionStateTracker::new(ConnectionState::Connected);
+        tracker.set(ConnectionState::Closed, "dropped");
+        tracker.set(ConnectionState::Connected, "reconnected");
+        assert_eq!(tracker.state(), ConnectionState::Closed);
+    }
+}
@@ -0,0 +1,682 @@
+use crate::compression_metrics::CompressionMetrics;
+use crate::connection_state::ConnectionState;
+use crate::connection_state::ConnectionStateTracker;
+use crate::event_channel::EventChannelConfig;
+use crate::event_channel::EventReceiver;
+use crate::handle_msg::HandleMessage;
+use crate::handle_msg::HandleMessageWithJsep;
+use crate::janus_interface::ConnectionParams;
+use crate::janus_interface::JanusInterface;
+use crate::janus_interface::ReconnectConfig;
+use crate::japrotocol::JaResponse;
+use crate::japrotocol::JaSuccessProtocol;
+use crate::japrotocol::ResponseType;
+use crate::japrotocol::ServerInfoRsp;
+use crate::tgenerator::GenerateTransaction;
+use crate::tgenerator::TransactionGenerator;
+use crate::token_provider::TokenProviderImpl;
+use crate::transaction_metrics::TransactionMetrics;
+use crate::websocket::demuxer::Demuxer;
+use crate::websocket::demuxer::Disconnections;
+use crate::websocket::pending::PendingRequest;
+use crate::websocket::pending::PendingRequests;
+use crate::websocket::router::Router;
+use crate::websocket::tmanager::TransactionManager;
+use crate::Error;
+use bytes::Bytes;
+use jarust_rt::Instant;
+use jarust_rt::JaTask;
+use serde_json::json;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::Weak;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::sync::Mutex;
+
+/// A connection to janus carrying whole json messages, e.g. a websocket, a unix socket or the topics of a broker.
+///
+/// The requests are matched to their replies by the [`DemuxedInterface`] on top of it, so a transport only moves bytes.
+#[async_trait::async_trait]
+pub trait DemuxedTransport: Debug + Send + Sync + Sized + 'static {
+    /// Name of the interface built on top of the transport
+    const NAME: &'static str;
+
+    /// Connects to janus, the transport may drive the state itself (e.g. across the reconnections to a broker).
+    async fn connect(
+        conn_params: &ConnectionParams,
+        state: &ConnectionStateTracker,
+    ) -> Result<(Self, TransportReceivers), Error>;
+
+    /// Sends a single message
+    async fn send(&self, data: &[u8]) -> Result<(), Error>;
+
+    /// Opens a new connection once the previous one dropped, messages keep flowing to the same receivers.
+    ///
+    /// Only called for the transports reporting their [`TransportReceivers::disconnections`].
+    async fn reconnect(&self) -> Result<(), Error> {
+        Err(Error::TransportNotOpened)
+    }
+
+    /// Returns the metrics of the compression, for the transports compressing their messages
+    fn compression_metrics(&self) -> Option<CompressionMetrics> {
+        None
+    }
+}
+
+/// Receiving ends of a transport
+#[derive(Debug)]
+pub struct TransportReceivers {
+    /// Incoming messages, the interface is closed once the transport drops its sender
+    pub inbound: mpsc::UnboundedReceiver<Bytes>,
+    /// The reason of each dropped connection, the interface reconnects then claims back the live sessions.
+    ///
+    /// `None` for the transports that can't reconnect, or do it on their own.
+    pub disconnections: Option<mpsc::UnboundedReceiver<String>>,
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    tasks: Vec<JaTask>,
+    state: ConnectionStateTracker,
+    server_root: String,
+    apisecret: Option<String>,
+    token_provider: Option<TokenProviderImpl>,
+    event_channel: EventChannelConfig,
+    transaction_generator: TransactionGenerator,
+    /// Requests waiting on their ack
+    acks: PendingRequests<JaResponse>,
+    /// Requests waiting on their response
+    responses: PendingRequests<JaResponse>,
+    /// Routes of the requests in flight
+    transaction_manager: TransactionManager,
+    transport: T,
+}
+
+#[derive(Debug)]
+struct Exclusive {
+    router: Router,
+    sessions: HashSet<u64>,
+}
+
+#[derive(Debug)]
+struct InnerDemuxedInterface<T> {
+    shared: Shared<T>,
+    exclusive: Mutex<Exclusive>,
+}
+
+/// Talks to janus over a [`DemuxedTransport`], matching the replies to their requests by transaction and routing
+/// the events to the handles.
+#[derive(Debug)]
+pub struct DemuxedInterface<T: DemuxedTransport> {
+    inner: Arc<InnerDemuxedInterface<T>>,
+}
+
+impl<T: DemuxedTransport> Clone for DemuxedInterface<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: DemuxedTransport> DemuxedInterface<T> {
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    pub async fn send(&self, message: Value) -> Result<String, Error> {
+        let (message, transaction) = self.decorate_request(message).await?;
+        self.send_decorated(&message, &transaction).await?;
+        Ok(transaction)
+    }
+
+    /// Sends a request registered beforehand, so its reply can't arrive before anyone waits on it
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn send_registered(
+        &self,
+        message: Value,
+        pending: &PendingRequests<JaResponse>,
+    ) -> Result<PendingRequest<JaResponse>, Error> {
+        let (message, transaction) = self.decorate_request(message).await?;
+        let request = pending.register(&transaction, message["session_id"].as_u64());
+        self.send_decorated(&message, &transaction).await?;
+        Ok(request)
+    }
+
+    async fn send_decorated(&self, message: &Value, transaction: &str) -> Result<(), Error> {
+        let path =
+            Router::path_from_request(message).unwrap_or(self.inner.shared.server_root.clone());
+
+        let transaction_manager = &self.inner.shared.transaction_manager;
+        // Keep-alives and trickles are only ever acked, there's nothing to route back
+        let tracked = !matches!(message["janus"].as_str(), Some("keepalive" | "trickle"));
+        if tracked {
+            transaction_manager.insert(transaction, &path);
+        }
+        let result = self
+            .inner
+            .shared
+            .transport
+            .send(message.to_string().as_bytes())
+            .await;
+        if let Err(what) = result {
+            transaction_manager.remove(transaction);
+            return Err(what);
+        }
+        tracing::trace!("Sending {message:#?}");
+        Ok(())
+    }
+
+    async fn decorate_request(&self, mut request: Value) -> Result<(Value, String), Error> {
+        let transaction = self
+            .inner
+            .shared
+            .transaction_generator
+            .generate_transaction();
+        if let Some(apisecret) = self.inner.shared.apisecret.clone() {
+            request["apisecret"] = apisecret.into();
+        };
+        if let Some(token_provider) = &self.inner.shared.token_provider {
+            request["token"] = token_provider.token().await?.into();
+        };
+        request["transaction"] = transaction.clone().into();
+        Ok((request, transaction))
+    }
+
+    /// Sends a request then waits on its ack, returning its transaction
+    async fn send_waiton_ack(&self, request: Value, timeout: Duration) -> Result<String, Error> {
+        let pending = self
+            .send_registered(request, &self.inner.shared.acks)
+            .await?;
+        let transaction = pending.transaction().to_string();
+        pending.response(timeout).await?;
+        Ok(transaction)
+    }
+
+    /// Sends a request then waits on its response, failing on janus errors
+    async fn send_waiton_data(&self, request: Value, timeout: Duration) -> Result<u64, Error> {
+        let response = self
+            .send_registered(request, &self.inner.shared.responses)
+            .await?
+            .response(timeout)
+            .await?;
+        match response.janus {
+            ResponseType::Success(JaSuccessProtocol::Data { data }) => Ok(data.id),
+            ResponseType::Error { error } => {
+                let what = Error::JanusError {
+                    code: error.code,
+                    reason: error.reason,
+                };
+                tracing::error!("{what}");
+                Err(what)
+            }
+            _ => {
+                tracing::error!("Unexpected response");
+                Err(Error::UnexpectedResponse)
+            }
+        }
+    }
+
+    /// Fails the requests still waiting on a session that's gone
+    fn fail_session(&self, session_id: u64) {
+        self.inner.shared.acks.fail_session(session_id);
+        self.inner.shared.responses.fail_session(session_id);
+    }
+
+    /// Claims back the live sessions after a reconnection.
+    ///
+    /// The router outlives the connection, so the subroutes of the claimed sessions and their handles are kept as is,
+    /// while the subroutes of the sessions that couldn't be claimed are removed.
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn reclaim_sessions(&self, config: &ReconnectConfig, disconnected_at: Instant) {
+        let sessions = self
+            .inner
+            .exclusive
+            .lock()
+            .await
+            .sessions
+            .iter()
+            .copied()
+            .collect::<Vec<_>>();
+        if sessions.is_empty() {
+            return;
+        }
+
+        match self.server_info(config.timeout).await {
+            Ok(info) => {
+                let reclaim_timeout = Duration::from_secs(info.reclaim_session_timeout);
+                if disconnected_at.elapsed() >= reclaim_timeout {
+                    tracing::error!(
+                        "Reclaim session timeout ({reclaim_timeout:?}) elapsed, sessions are lost"
+                    );
+                    for session_id in sessions {
+                        self.forget_session(session_id).await;
+                    }
+                    return;
+                }
+            }
+            Err(what) => {
+                tracing::warn!("Failed to get the reclaim session timeout: {what}");
+            }
+        }
+
+        for session_id in sessions {
+            match self.claim(session_id, config.timeout).await {
+                Ok(()) => tracing::info!(session_id, "Session reclaimed"),
+                Err(what) => {
+                    tracing::error!(session_id, "Failed to reclaim session: {what}");
+                    self.forget_session(session_id).await;
+                }
+            }
+        }
+    }
+
+    async fn claim(&self, session_id: u64, timeout: Duration) -> Result<(), Error> {
+        let request = json!({
+            "janus": "claim",
+            "session_id": session_id
+        });
+        self.send_registered(request, &self.inner.shared.responses)
+            .await?
+            .response(timeout)
+            .await?;
+        Ok(())
+    }
+
+    /// Fails the requests of the session and stops routing its events, it won't be claimed back either
+    pub(crate) async fn forget_session(&self, session_id: u64) {
+        self.fail_session(session_id);
+        let mut guard = self.inner.exclusive.lock().await;
+        guard.sessions.remove(&session_id);
+        guard.router.remove_subroutes(&session_id.to_string()).await;
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: DemuxedTransport> JanusInterface for DemuxedInterface<T> {
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn make_interface(
+        conn_params: ConnectionParams,
+        transaction_generator: impl GenerateTransaction,
+    ) -> Result<Self, Error> {
+        tracing::debug!("Creating {}", T::NAME);
+        let router = Router::new(&conn_params.server_root);
+        let state = ConnectionStateTracker::new(ConnectionState::Connecting);
+        let (transport, receivers) = T::connect(&conn_params, &state).await?;
+        state.set(ConnectionState::Connected, "Connected");
+        let transaction_manager =
+            TransactionManager::new(conn_params.capacity, conn_params.transaction_ttl);
+        let transaction_generator = TransactionGenerator::new(transaction_generator);
+
+        let acks = PendingRequests::new();
+        let responses = PendingRequests::new();
+
+        // The disconnections are relayed by the demuxer, once the messages received before them are demuxed
+        let (disconnections, relayed_disconnections) = match receivers.disconnections {
+            Some(incoming) => {
+                let (outgoing, relayed) = mpsc::unbounded_channel();
+                (Some(Disconnections { incoming, outgoing }), Some(relayed))
+            }
+            None => (None, None),
+        };
+        let demux_task = jarust_rt::spawn("Demultiplexing task", {
+            let router = router.clone();
+            let transaction_manager = transaction_manager.clone();
+            let state = state.clone();
+            let demuxer = Demuxer {
+                inbound_stream: receivers.inbound,
+                router,
+                acks: acks.clone(),
+                responses: responses.clone(),
+                transaction_manager,
+                disconnections,
+            };
+            async move {
+                let result = demuxer.start().await;
+                state.set(ConnectionState::Closed, "Connection closed");
+                result
+            }
+        });
+
+        let inner = Arc::new_cyclic(|this| {
+            let mut tasks = vec![demux_task];
+            if let Some(relayed_disconnections) = relayed_disconnections {
+                tasks.push(jarust_rt::spawn(
+                    "Connection supervision task",
+                    keep_connected(
+                        this.clone(),
+                        relayed_disconnections,
+                        state.clone(),
+                        conn_params.reconnect,
+                    ),
+                ));
+            }
+            let shared = Shared {
+                tasks,
+                state,
+                server_root: conn_params.server_root,
+                apisecret: conn_params.apisecret,
+                token_provider: conn_params.token_provider,
+                event_channel: conn_params.event_channel,
+                transaction_generator,
+                acks,
+                responses,
+                transaction_manager,
+                transport,
+            };
+            let exclusive = Exclusive {
+                router,
+                sessions: HashSet::new(),
+            };
+            InnerDemuxedInterface {
+                shared,
+                exclusive: Mutex::new(exclusive),
+            }
+        });
+        Ok(Self { inner })
+    }
+
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn create(&self, timeout: Duration) -> Result<u64, Error> {
+        let request = json!({
+            "janus": "create"
+        });
+        let session_id = self.send_waiton_data(request, timeout).await?;
+        self.inner
+            .exclusive
+            .lock()
+            .await
+            .sessions
+            .insert(session_id);
+        Ok(session_id)
+    }
+
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn server_info(&self, timeout: Duration) -> Result<ServerInfoRsp, Error> {
+        let request = json!({
+            "janus": "info"
+        });
+        let response = self
+            .send_registered(request, &self.inner.shared.responses)
+            .await?
+            .response(timeout)
+            .await?;
+        match response.janus {
+            ResponseType::ServerInfo(info) => Ok(*info),
+            ResponseType::Error { error } => Err(Error::JanusError {
+                code: error.code,
+                reason: error.reason,
+            }),
+            _ => Err(Error::IncompletePacket),
+        }
+    }
+
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn attach(
+        &self,
+        session_id: u64,
+        plugin_id: String,
+        channel: Option<EventChannelConfig>,
+        timeout: Duration,
+    ) -> Result<(u64, EventReceiver<JaResponse>), Error> {
+        let request = json!({
+            "janus": "attach",
+            "session_id": session_id,
+            "plugin": plugin_id
+        });
+        let handle_id = self.send_waiton_data(request, timeout).await?;
+        let receiver = self
+            .inner
+            .exclusive
+            .lock()
+            .await
+            .router
+            .add_subroute(
+                &format!("{session_id}/{handle_id}"),
+                channel.unwrap_or(self.inner.shared.event_channel),
+            )
+            .await;
+        Ok((handle_id, receiver))
+    }
+
+    fn has_keep_alive(&self) -> bool {
+        true
+    }
+
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn keep_alive(&self, session_id: u64, timeout: Duration) -> Result<(), Error> {
+        let request = json!({
+            "janus": "keepalive",
+            "session_id": session_id
+        });
+        self.send_waiton_ack(request, timeout).await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn destroy(&self, session_id: u64, timeout: Duration) -> Result<(), Error> {
+        let request = json!({
+            "janus": "destroy",
+            "session_id": session_id
+        });
+        self.send_registered(request, &self.inner.shared.responses)
+            .await?
+            .response(timeout)
+            .await?;
+        self.fail_session(session_id);
+        self.inner
+            .exclusive
+            .lock()
+            .await
+            .sessions
+            .remove(&session_id);
+        Ok(())
+    }
+
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn fire_and_forget_msg(&self, message: HandleMessage) -> Result<String, Error> {
+        let request = json!({
+            "janus": "message",
+            "session_id": message.session_id,
+            "handle_id": message.handle_id,
+            "body": message.body
+        });
+        self.send(request).await
+    }
+
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn send_msg_waiton_ack(
+        &self,
+        message: HandleMessage,
+        timeout: Duration,
+    ) -> Result<String, Error> {
+        let request = json!({
+            "janus": "message",
+            "session_id": message.session_id,
+            "handle_id": message.handle_id,
+            "body": message.body
+        });
+        self.send_waiton_ack(request, timeout).await
+    }
+
+    async fn internal_send_msg_waiton_rsp(
+        &self,
+        message: HandleMessage,
+        timeout: Duration,
+    ) -> Result<JaResponse, Error> {
+        let request = json!({
+            "janus": "message",
+            "session_id": message.session_id,
+            "handle_id": message.handle_id,
+            "body": message.body
+        });
+        self.send_registered(request, &self.inner.shared.responses)
+            .await?
+            .response(timeout)
+            .await
+    }
+
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn fire_and_forget_msg_with_jsep(
+        &self,
+        message: HandleMessageWithJsep,
+    ) -> Result<String, Error> {
+        let request = json!({
+            "janus": "message",
+            "session_id": message.session_id,
+            "handle_id": message.handle_id,
+            "body": message.body,
+            "jsep": message.jsep
+        });
+        self.send(request).await
+    }
+
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn send_msg_waiton_ack_with_jsep(
+        &self,
+        message: HandleMessageWithJsep,
+        timeout: Duration,
+    ) -> Result<String, Error> {
+        let request = json!({
+            "janus": "message",
+            "session_id": message.session_id,
+            "handle_id": message.handle_id,
+            "body": message.body,
+            "jsep": message.jsep,
+        });
+        self.send_waiton_ack(request, timeout).await
+    }
+
+    async fn send_handle_request(&self, request: HandleMessage) -> Result<(), Error> {
+        let mut req = request.body;
+        merge_json(
+            &mut req,
+            &json!({
+                "session_id": request.session_id,
+                "handle_id": request.handle_id,
+            }),
+        );
+        _ = self.send(req).await?;
+        Ok(())
+    }
+
+    async fn send_handle_request_waiton_ack(
+        &self,
+        request: HandleMessage,
+        timeout: Duration,
+    ) -> Result<String, Error> {
+        let mut req = request.body;
+        merge_json(
+            &mut req,
+            &json!({
+                "session_id": request.session_id,
+                "handle_id": request.handle_id,
+            }),
+        );
+        self.send_waiton_ack(req, timeout).await
+    }
+
+    fn connection_state(&self) -> Option<ConnectionStateTracker> {
+        Some(self.inner.shared.state.clone())
+    }
+
+    fn transaction_metrics(&self) -> Option<TransactionMetrics> {
+        Some(self.inner.shared.transaction_manager.metrics())
+    }
+
+    fn compression_metrics(&self) -> Option<CompressionMetrics> {
+        self.inner.shared.transport.compression_metrics()
+    }
+
+    fn name(&self) -> Box<str> {
+        T::NAME.to_string().into_boxed_str()
+    }
+}
+
+impl<T> Drop for InnerDemuxedInterface<T> {
+    fn drop(&mut self) {
+        self.shared
+            .state
+            .set(ConnectionState::Closed, "Interface dropped");
+        self.shared.tasks.iter().for_each(|task| {
+            task.cancel();
+        });
+    }
+}
+
+/// Tracks the connection state and, if configured, re-establishes the connection each time it drops
+/// then claims back the live sessions.
+///
+/// Only a weak reference is held in between attempts so the interface can still be dropped while reconnecting.
+async fn keep_connected<T: DemuxedTransport>(
+    interface: Weak<InnerDemuxedInterface<T>>,
+    mut disconnections: mpsc::UnboundedReceiver<String>,
+    state: ConnectionStateTracker,
+    config: Option<ReconnectConfig>,
+) {
+    while let Some(reason) = disconnections.recv().await {
+        let Some(config) = config else {
+            state.set(ConnectionState::Closed, reason);
+            return;
+        };
+        tracing::warn!("Disconnected ({reason}), reconnecting");
+        state.set(ConnectionState::Disconnected, reason);
+        let disconnected_at = Instant::now();
+        let mut attempt = 0;
+        loop {
+            let Some(inner) = interface.upgrade() else {
+                return;
+            };
+            state.set(
+                ConnectionState::Connecting,
+                format!("Reconnection attempt {}", attempt + 1),
+            );
+            let result = inner.shared.transport.reconnect().await;
+            drop(inner);
+            match result {
+                Ok(()) => break,
+                Err(what) => {
+                    attempt += 1;
+                    if config.max_attempts.is_some_and(|max| attempt >= max) {
+                        tracing::error!("Giving up reconnecting after {attempt} attempts: {what}");
+                        state.set(
+                            ConnectionState::Closed,
+                            format!("Gave up reconnecting after {attempt} attempts: {what}"),
+                        );
+                        return;
+                    }
+                    let delay = config.backoff.delay(attempt - 1);
+                    tracing::warn!(
+                        "Reconnection attempt {attempt} failed ({what}), retrying in {delay:?}"
+                    );
+                    state.set(
+                        ConnectionState::Disconnected,
+                        format!("Reconnection attempt {attempt} failed: {what}"),
+                    );
+                    jarust_rt::sleep(delay).await;
+                }
+            }
+        }
+        tracing::info!("Reconnected");
+        state.set(ConnectionState::Connected, "Reconnected");
+        let Some(inner) = interface.upgrade() else {
+            return;
+        };
+        DemuxedInterface { inner }
+            .reclaim_sessions(&config, disconnected_at)
+            .await;
+    }
+}
+
+fn merge_json(a: &mut Value, b: &Value) {
+    match (a, b) {
+        (&mut Value::Object(ref mut a), Value::Object(b)) => {
+            for (k, v) in b {
+                merge_json(a.entry(k.clone()).or_insert(Value::Null), v);
+            }
+        }
+        (a, b) => {
+            *a = b.clone();
+        }
+    }
+}
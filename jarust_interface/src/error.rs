@@ -3,7 +3,7 @@ pub enum Error {
     /* Transformed Errors */
     #[cfg(not(target_family = "wasm"))]
     #[error("WebSocket error: {0}")]
-    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
 
     #[cfg(not(target_family = "wasm"))]
     #[error("InvalidHeaderValue: {0}")]
@@ -15,6 +15,15 @@ pub enum Error {
     IO(#[from] std::io::Error),
     #[error("Reqwest error: {0}")]
     Reqwest(#[from] reqwest::Error),
+    #[cfg(feature = "amqp")]
+    #[error("AMQP error: {0}")]
+    Amqp(#[from] lapin::Error),
+    #[cfg(feature = "mqtt")]
+    #[error("MQTT connection error: {0}")]
+    MqttConnection(Box<rumqttc::ConnectionError>),
+    #[cfg(feature = "mqtt")]
+    #[error("MQTT client error: {0}")]
+    MqttClient(Box<rumqttc::ClientError>),
 
     /* Custom Errors */
     #[error("Error while parsing an incomplete packet")]
@@ -33,4 +42,47 @@ pub enum Error {
     PluginResponseError { error_code: u16, error: String },
     #[error("Request timeout")]
     RequestTimeout,
+    #[error("Session destroyed while waiting on the request {{ session_id: {session_id} }}")]
+    SessionDestroyed { session_id: u64 },
+    #[error("Connection lost while waiting on the request")]
+    ConnectionLost,
+    #[error("Invalid header {{ name: {name}, reason: {reason} }}")]
+    InvalidHeader { name: String, reason: String },
+    #[error("TLS error {{ reason: {reason} }}")]
+    Tls { reason: String },
+    #[error("Proxy error {{ reason: {reason} }}")]
+    Proxy { reason: String },
+    #[error("Invalid url {{ reason: {reason} }}")]
+    InvalidUrl { reason: String },
+    #[error("Failed to provide a token {{ reason: {reason} }}")]
+    TokenProvider { reason: String },
+    #[error("No healthy server is serving the plugin {{ plugin_id: {plugin_id} }}")]
+    NoServerAvailable { plugin_id: String },
+    #[cfg(target_family = "wasm")]
+    #[error("Unsupported {{ reason: {reason} }}")]
+    Unsupported { reason: String },
+    #[cfg(target_family = "wasm")]
+    #[error("Browser WebSocket error {{ reason: {reason} }}")]
+    BrowserWebSocket { reason: String },
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(value: tokio_tungstenite::tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(value))
+    }
+}
+
+#[cfg(feature = "mqtt")]
+impl From<rumqttc::ConnectionError> for Error {
+    fn from(value: rumqttc::ConnectionError) -> Self {
+        Self::MqttConnection(Box::new(value))
+    }
+}
+
+#[cfg(feature = "mqtt")]
+impl From<rumqttc::ClientError> for Error {
+    fn from(value: rumqttc::ClientError) -> Self {
+        Self::MqttClient(Box::new(value))
+    }
 }
@@ -0,0 +1,460 @@
+//! Bounded channels of the handle events.
+//!
+//! Each handle has its own channel with its own [`OverflowPolicy`], deciding what happens to the events
+//! janus keeps sending while the channel is full, e.g. the talking events of a busy room with a slow consumer.
+
+use crate::japrotocol::JaHandleEvent;
+use crate::japrotocol::JaResponse;
+use crate::japrotocol::PluginInnerData;
+use crate::japrotocol::ResponseType;
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+/// What to do with an event sent to a full channel
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum OverflowPolicy {
+    /// Wait for room, holding back the demultiplexing of the whole connection meanwhile, so a single handle
+    /// that isn't read stalls every other handle and the replies of the requests. Opt-in only
+    Block,
+    /// Drop the oldest queued event to make room
+    #[default]
+    DropOldest,
+    /// Drop the event being sent
+    DropNewest,
+    /// Replace the queued talking event of the same participant with the new one, only the latest talking
+    /// state of a participant matters. Other events wait for room like with [`OverflowPolicy::Block`]
+    CoalesceTalking,
+}
+
+/// Configuration of the event channel of a handle
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct EventChannelConfig {
+    /// How many events may be queued before the overflow policy kicks in
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for EventChannelConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            overflow: OverflowPolicy::default(),
+        }
+    }
+}
+
+/// Events that can be coalesced by [`OverflowPolicy::CoalesceTalking`]
+pub trait Coalesce {
+    /// Events with the same key supersede each other, `None` for events that can't be coalesced
+    fn coalesce_key(&self) -> Option<String>;
+}
+
+impl Coalesce for JaResponse {
+    /// The talking and stopped-talking events of the videoroom and audiobridge plugins, by participant
+    fn coalesce_key(&self) -> Option<String> {
+        let ResponseType::Event(JaHandleEvent::PluginEvent { plugin_data }) = &self.janus else {
+            return None;
+        };
+        let PluginInnerData::Data(data) = &plugin_data.data else {
+            return None;
+        };
+        let event = data.get("videoroom").or_else(|| data.get("audiobridge"))?;
+        if event != "talking" && event != "stopped-talking" {
+            return None;
+        }
+        Some(format!(
+            "{}/{}/{}",
+            self.sender.unwrap_or_default(),
+            data.get("room")?,
+            data.get("id")?
+        ))
+    }
+}
+
+#[derive(Debug)]
+struct Channel<T> {
+    queue: Mutex<VecDeque<T>>,
+    config: EventChannelConfig,
+    /// Wakes the receiver on new events and once the last sender is gone
+    receivable: Notify,
+    /// Wakes the blocked senders on room and once the receiver is gone
+    sendable: Notify,
+    senders: AtomicUsize,
+    receiver_alive: AtomicBool,
+    dropped: AtomicU64,
+    /// Set on the first drop while full and cleared once an event fits again, so each overflow is logged once
+    overflowing: AtomicBool,
+}
+
+/// The event that couldn't be sent since the receiver is gone
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("Channel closed")]
+pub struct SendError<T>(pub T);
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    #[error("Channel empty")]
+    Empty,
+    #[error("Channel disconnected")]
+    Disconnected,
+}
+
+/// Creates a bounded event channel
+pub fn event_channel<T>(config: EventChannelConfig) -> (EventSender<T>, EventReceiver<T>) {
+    assert!(config.capacity > 0, "capacity > 0");
+    let channel = Arc::new(Channel {
+        queue: Mutex::new(VecDeque::with_capacity(config.capacity)),
+        config,
+        receivable: Notify::new(),
+        sendable: Notify::new(),
+        senders: AtomicUsize::new(1),
+        receiver_alive: AtomicBool::new(true),
+        dropped: AtomicU64::new(0),
+        overflowing: AtomicBool::new(false),
+    });
+    (
+        EventSender {
+            channel: channel.clone(),
+        },
+        EventReceiver { channel },
+    )
+}
+
+#[derive(Debug)]
+pub struct EventSender<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T> EventSender<T>
+where
+    T: Coalesce,
+{
+    /// Sends an event, applying the overflow policy if the channel is full
+    pub async fn send(&self, event: T) -> Result<(), SendError<T>> {
+        let channel = &self.channel;
+        loop {
+            let sendable = channel.sendable.notified();
+            tokio::pin!(sendable);
+            sendable.as_mut().enable();
+
+            if !channel.receiver_alive.load(Ordering::Acquire) {
+                return Err(SendError(event));
+            }
+            {
+                let mut queue = channel.queue.lock().expect("event queue poisoned");
+                if queue.len() < channel.config.capacity {
+                    queue.push_back(event);
+                    drop(queue);
+                    channel.receivable.notify_one();
+                    self.report_recovery();
+                    return Ok(());
+                }
+                match channel.config.overflow {
+                    OverflowPolicy::Block => {}
+                    OverflowPolicy::DropOldest => {
+                        queue.pop_front();
+                        queue.push_back(event);
+                        drop(queue);
+                        self.report_drop();
+                        channel.receivable.notify_one();
+                        return Ok(());
+                    }
+                    OverflowPolicy::DropNewest => {
+                        drop(queue);
+                        self.report_drop();
+                        return Ok(());
+                    }
+                    OverflowPolicy::CoalesceTalking => {
+                        if let Some(key) = event.coalesce_key() {
+                            let superseded = queue
+                                .iter_mut()
+                                .find(|queued| queued.coalesce_key().as_ref() == Some(&key));
+                            if let Some(superseded) = superseded {
+                                *superseded = event;
+                                drop(queue);
+                                self.report_drop();
+                                return Ok(());
+                            }
+                        }
+                    }
+                }
+            }
+            tracing::trace!("Channel full, waiting for room");
+            sendable.await;
+        }
+    }
+
+    fn report_drop(&self) {
+        let dropped = self.channel.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+        if !self.channel.overflowing.swap(true, Ordering::Relaxed) {
+            tracing::warn!(
+                dropped,
+                policy = ?self.channel.config.overflow,
+                "Event channel full, dropping events"
+            );
+        }
+    }
+
+    fn report_recovery(&self) {
+        if self.channel.overflowing.swap(false, Ordering::Relaxed) {
+            tracing::info!(
+                dropped = self.channel.dropped.load(Ordering::Relaxed),
+                "Event channel has room again"
+            );
+        }
+    }
+}
+
+impl<T> EventSender<T> {
+    /// Whether the receiver is gone
+    pub fn is_closed(&self) -> bool {
+        !self.channel.receiver_alive.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Clone for EventSender<T> {
+    fn clone(&self) -> Self {
+        self.channel.senders.fetch_add(1, Ordering::AcqRel);
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> Drop for EventSender<T> {
+    fn drop(&mut self) {
+        if self.channel.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.channel.receivable.notify_one();
+        }
+    }
+}
+
+/// The receiving half of an event channel, closed once all of its senders are gone,
+/// e.g. when the handle is detached or its session destroyed
+#[derive(Debug)]
+pub struct EventReceiver<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T> EventReceiver<T> {
+    /// Receives the next event, `None` once the channel is closed and drained
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            match self.try_recv() {
+                Ok(event) => return Some(event),
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => self.channel.receivable.notified().await,
+            }
+        }
+    }
+
+    /// Receives up to `limit` events into the buffer, waiting for at least one,
+    /// returns how many were received, `0` once the channel is closed and drained
+    pub async fn recv_many(&mut self, buffer: &mut Vec<T>, limit: usize) -> usize {
+        if limit == 0 {
+            return 0;
+        }
+        let Some(first) = self.recv().await else {
+            return 0;
+        };
+        buffer.push(first);
+        let mut received = 1;
+        while received < limit {
+            let Ok(event) = self.try_recv() else {
+                break;
+            };
+            buffer.push(event);
+            received += 1;
+        }
+        received
+    }
+
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let event = self
+            .channel
+            .queue
+            .lock()
+            .expect("event queue poisoned")
+            .pop_front();
+        match event {
+            Some(event) => {
+                self.channel.sendable.notify_one();
+                Ok(event)
+            }
+            None if self.channel.senders.load(Ordering::Acquire) == 0 => {
+                Err(TryRecvError::Disconnected)
+            }
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// How many events were dropped by the overflow policy so far
+    pub fn dropped(&self) -> u64 {
+        self.channel.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.channel.config.capacity
+    }
+
+    /// Whether all the senders are gone, queued events may still be received
+    pub fn is_closed(&self) -> bool {
+        self.channel.senders.load(Ordering::Acquire) == 0
+    }
+}
+
+impl<T> Drop for EventReceiver<T> {
+    fn drop(&mut self) {
+        self.channel.receiver_alive.store(false, Ordering::Release);
+        self.channel.sendable.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::event_channel;
+    use super::Coalesce;
+    use super::EventChannelConfig;
+    use super::OverflowPolicy;
+    use super::TryRecvError;
+    use crate::japrotocol::JaResponse;
+    use serde_json::json;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    fn talking(id: u64, talking: bool) -> JaResponse {
+        let event = if talking {
+            "talking"
+        } else {
+            "stopped-talking"
+        };
+        serde_json::from_value(json!({
+            "janus": "event",
+            "session_id": 1,
+            "sender": 2,
+            "plugindata": {
+                "plugin": "janus.plugin.videoroom",
+                "data": {"videoroom": event, "room": 1234, "id": id, "audio-level-dBov-avg": 40}
+            }
+        }))
+        .unwrap()
+    }
+
+    fn joined() -> JaResponse {
+        serde_json::from_value(json!({
+            "janus": "event",
+            "session_id": 1,
+            "sender": 2,
+            "plugindata": {"plugin": "janus.plugin.videoroom", "data": {"videoroom": "joined", "room": 1234}}
+        }))
+        .unwrap()
+    }
+
+    fn config(capacity: usize, overflow: OverflowPolicy) -> EventChannelConfig {
+        EventChannelConfig { capacity, overflow }
+    }
+
+    #[tokio::test]
+    async fn it_should_drop_by_policy_and_count_the_drops() {
+        let (tx, mut rx) = event_channel(config(2, OverflowPolicy::DropOldest));
+        for id in 1..=3 {
+            tx.send(talking(id, true)).await.unwrap();
+        }
+        assert_eq!(rx.dropped(), 1);
+        assert_eq!(rx.recv().await.unwrap(), talking(2, true));
+        assert_eq!(rx.recv().await.unwrap(), talking(3, true));
+
+        let (tx, mut rx) = event_channel(config(2, OverflowPolicy::DropNewest));
+        for id in 1..=3 {
+            tx.send(talking(id, true)).await.unwrap();
+        }
+        assert_eq!(rx.dropped(), 1);
+        assert_eq!(rx.recv().await.unwrap(), talking(1, true));
+        assert_eq!(rx.recv().await.unwrap(), talking(2, true));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[tokio::test]
+    async fn it_should_not_hold_back_the_sender_by_default() {
+        let (tx, mut rx) = event_channel(EventChannelConfig {
+            capacity: 1,
+            ..Default::default()
+        });
+        let sent = tokio::time::timeout(Duration::from_secs(1), async {
+            tx.send(joined()).await.unwrap();
+            tx.send(talking(1, true)).await.unwrap();
+        })
+        .await;
+        assert!(sent.is_ok());
+        assert_eq!(rx.dropped(), 1);
+        assert_eq!(rx.recv().await.unwrap(), talking(1, true));
+    }
+
+    #[tokio::test]
+    async fn it_should_coalesce_the_talking_events_of_a_participant() {
+        let (tx, mut rx) = event_channel(config(2, OverflowPolicy::CoalesceTalking));
+        tx.send(talking(1, true)).await.unwrap();
+        tx.send(joined()).await.unwrap();
+        tx.send(talking(1, false)).await.unwrap();
+        assert_eq!(rx.dropped(), 1);
+
+        // No queued event of the participant, so it waits for room
+        let blocked = tokio::spawn(async move {
+            tx.send(talking(2, true)).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!blocked.is_finished());
+
+        assert_eq!(rx.recv().await.unwrap(), talking(1, false));
+        blocked.await.unwrap();
+        assert_eq!(rx.recv().await.unwrap(), joined());
+        assert_eq!(rx.recv().await.unwrap(), talking(2, true));
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn it_should_close_once_either_side_is_gone() {
+        let (tx, rx) = event_channel::<JaResponse>(config(1, OverflowPolicy::Block));
+        tx.send(joined()).await.unwrap();
+        let blocked = tokio::spawn(async move { tx.send(joined()).await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(rx);
+        assert!(blocked.await.unwrap().is_err());
+    }
+
+    #[test]
+    fn it_should_key_the_talking_events_by_participant() {
+        assert_eq!(joined().coalesce_key(), None);
+        assert_eq!(
+            talking(3, true).coalesce_key(),
+            talking(3, false).coalesce_key()
+        );
+        assert_ne!(
+            talking(3, true).coalesce_key(),
+            talking(4, true).coalesce_key()
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_track_an_overflow_until_there_is_room_again() {
+        let (tx, mut rx) = event_channel(config(1, OverflowPolicy::DropOldest));
+        tx.send(talking(1, true)).await.unwrap();
+        assert!(!rx.channel.overflowing.load(Ordering::Relaxed));
+
+        tx.send(talking(2, true)).await.unwrap();
+        tx.send(talking(3, true)).await.unwrap();
+        assert!(rx.channel.overflowing.load(Ordering::Relaxed));
+        assert_eq!(rx.dropped(), 2);
+
+        rx.recv().await.unwrap();
+        tx.send(talking(4, true)).await.unwrap();
+        assert!(!rx.channel.overflowing.load(Ordering::Relaxed));
+        assert_eq!(rx.dropped(), 2);
+    }
+}
@@ -0,0 +1,440 @@
+//! Events janus pushes to its event handlers, full docs: <https://janus.conf.meetecho.com/docs/eventhandlers.html>
+
+use crate::japrotocol::Jsep;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Map;
+use serde_json::Value;
+
+/// An event emitted by janus to its event handlers
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[serde(try_from = "RawEvent")]
+pub struct JanusEvent {
+    /// The name of the janus instance that emitted the event (`server_name` in janus config)
+    pub emitter: Option<String>,
+    /// Microseconds since the unix epoch
+    pub timestamp: u64,
+    pub session_id: Option<u64>,
+    pub handle_id: Option<u64>,
+    pub opaque_id: Option<String>,
+    pub event: EventType,
+}
+
+/// The events keyed by their `type`
+#[derive(Clone, PartialEq, Debug)]
+pub enum EventType {
+    Session(SessionEvent),
+    Handle(HandleEvent),
+    External(ExternalEvent),
+    Jsep(JsepEvent),
+    WebRtc(WebRtcEvent),
+    Media(MediaEvent),
+    Plugin(PluginEvent),
+    Transport(TransportEvent),
+    Core(CoreEvent),
+    /// Events of a type unknown to jarust, e.g. added by a newer janus version
+    Unknown {
+        event_type: u32,
+        subtype: Option<u32>,
+        event: Value,
+    },
+}
+
+/// Type 1, a session was created, destroyed or timed out
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SessionEvent {
+    pub name: SessionEventName,
+    /// The transport the session was created over
+    pub transport: Option<Value>,
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionEventName {
+    Created,
+    Destroyed,
+    Timeout,
+    #[serde(other)]
+    Other,
+}
+
+/// Type 2, a handle was attached to or detached from a plugin
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct HandleEvent {
+    pub name: HandleEventName,
+    pub plugin: String,
+    pub opaque_id: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HandleEventName {
+    Attached,
+    Detached,
+    #[serde(other)]
+    Other,
+}
+
+/// Type 4, an event pushed through the admin api (`custom_event`)
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ExternalEvent {
+    pub schema: String,
+    pub data: Value,
+}
+
+/// Type 8, an SDP was sent or received
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct JsepEvent {
+    /// `local` for SDPs sent by janus, `remote` for SDPs received
+    pub owner: String,
+    pub jsep: Jsep,
+}
+
+/// Type 16, ICE and DTLS progress of a PeerConnection, keyed by the `subtype`
+#[derive(Clone, PartialEq, Debug)]
+pub enum WebRtcEvent {
+    IceState(IceStateEvent),
+    LocalCandidate(CandidateEvent),
+    RemoteCandidate(CandidateEvent),
+    SelectedPair(SelectedPairEvent),
+    DtlsState(DtlsStateEvent),
+    PeerConnection(PeerConnectionEvent),
+    Other { subtype: Option<u32>, event: Value },
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct IceStateEvent {
+    pub ice: String,
+    pub stream_id: Option<u64>,
+    pub component_id: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct CandidateEvent {
+    #[serde(alias = "local-candidate", alias = "remote-candidate")]
+    pub candidate: String,
+    pub stream_id: Option<u64>,
+    pub component_id: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct SelectedPairEvent {
+    #[serde(rename = "selected-pair")]
+    pub selected_pair: String,
+    pub stream_id: Option<u64>,
+    pub component_id: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct DtlsStateEvent {
+    pub dtls: String,
+    pub stream_id: Option<u64>,
+    pub component_id: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct PeerConnectionEvent {
+    /// `webrtcup` or `hangup`
+    pub connection: String,
+    pub reason: Option<String>,
+}
+
+/// Type 32, media state and statistics of a PeerConnection, keyed by the `subtype`
+#[derive(Clone, PartialEq, Debug)]
+pub enum MediaEvent {
+    State(MediaStateEvent),
+    SlowLink(SlowLinkEvent),
+    Stats(MediaStatsEvent),
+    Other { subtype: Option<u32>, event: Value },
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct MediaStateEvent {
+    pub mid: Option<String>,
+    pub media: String,
+    pub receiving: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct SlowLinkEvent {
+    pub mid: Option<String>,
+    pub media: String,
+    /// `uplink` or `downlink`
+    pub slow_link: String,
+    pub lost_lastsec: Option<u64>,
+}
+
+/// Periodic RTCP statistics, only the common fields are typed as the rest varies across janus versions
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct MediaStatsEvent {
+    pub mid: Option<String>,
+    pub mindex: Option<u64>,
+    pub media: Option<String>,
+    pub rtt: Option<u64>,
+    pub lost: Option<u64>,
+    #[serde(rename = "lost-by-remote")]
+    pub lost_by_remote: Option<u64>,
+    #[serde(rename = "packets-received")]
+    pub packets_received: Option<u64>,
+    #[serde(rename = "packets-sent")]
+    pub packets_sent: Option<u64>,
+    #[serde(rename = "bytes-received")]
+    pub bytes_received: Option<u64>,
+    #[serde(rename = "bytes-sent")]
+    pub bytes_sent: Option<u64>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+/// Type 64, an event of a plugin, the data is plugin specific
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PluginEvent {
+    pub plugin: String,
+    pub data: Value,
+}
+
+/// Type 128, an event of a transport, the data is transport specific
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TransportEvent {
+    pub transport: String,
+    pub id: Option<String>,
+    pub data: Value,
+}
+
+/// Type 256, the server started, is shutting down, or reports its status
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct CoreEvent {
+    pub status: CoreStatus,
+    pub info: Option<Value>,
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoreStatus {
+    Started,
+    Shutdown,
+    Update,
+    #[serde(other)]
+    Other,
+}
+
+/// Decodes a payload pushed by janus, be it a single event or an array of them when `grouping` is enabled.
+///
+/// The events are decoded one by one, so a malformed event only fails itself and not the rest of the batch,
+/// the outer error is for a payload that isn't json at all
+pub fn decode_events(
+    payload: &[u8],
+) -> Result<Vec<Result<JanusEvent, serde_json::Error>>, serde_json::Error> {
+    let events = match serde_json::from_slice::<Value>(payload)? {
+        Value::Array(events) => events,
+        event => vec![event],
+    };
+    Ok(events.into_iter().map(serde_json::from_value).collect())
+}
+
+#[derive(Deserialize)]
+struct RawEvent {
+    emitter: Option<String>,
+    #[serde(rename = "type")]
+    event_type: u32,
+    subtype: Option<u32>,
+    timestamp: u64,
+    session_id: Option<u64>,
+    handle_id: Option<u64>,
+    opaque_id: Option<String>,
+    #[serde(default)]
+    event: Value,
+}
+
+impl TryFrom<RawEvent> for JanusEvent {
+    type Error = serde_json::Error;
+
+    fn try_from(raw: RawEvent) -> Result<Self, Self::Error> {
+        use serde_json::from_value;
+
+        let RawEvent {
+            emitter,
+            event_type,
+            subtype,
+            timestamp,
+            session_id,
+            handle_id,
+            opaque_id,
+            event,
+        } = raw;
+        let event = match (event_type, subtype) {
+            (1, _) => EventType::Session(from_value(event)?),
+            (2, _) => EventType::Handle(from_value(event)?),
+            (4, _) => EventType::External(from_value(event)?),
+            (8, _) => EventType::Jsep(from_value(event)?),
+            (16, Some(1)) => EventType::WebRtc(WebRtcEvent::IceState(from_value(event)?)),
+            (16, Some(2)) => EventType::WebRtc(WebRtcEvent::LocalCandidate(from_value(event)?)),
+            (16, Some(3)) => EventType::WebRtc(WebRtcEvent::RemoteCandidate(from_value(event)?)),
+            (16, Some(4)) => EventType::WebRtc(WebRtcEvent::SelectedPair(from_value(event)?)),
+            (16, Some(5)) => EventType::WebRtc(WebRtcEvent::DtlsState(from_value(event)?)),
+            (16, Some(6)) => EventType::WebRtc(WebRtcEvent::PeerConnection(from_value(event)?)),
+            (16, subtype) => EventType::WebRtc(WebRtcEvent::Other { subtype, event }),
+            (32, Some(1)) => EventType::Media(MediaEvent::State(from_value(event)?)),
+            (32, Some(2)) => EventType::Media(MediaEvent::SlowLink(from_value(event)?)),
+            (32, Some(3)) => EventType::Media(MediaEvent::Stats(from_value(event)?)),
+            (32, subtype) => EventType::Media(MediaEvent::Other { subtype, event }),
+            (64, _) => EventType::Plugin(from_value(event)?),
+            (128, _) => EventType::Transport(from_value(event)?),
+            (256, _) => EventType::Core(from_value(event)?),
+            (event_type, subtype) => EventType::Unknown {
+                event_type,
+                subtype,
+                event,
+            },
+        };
+        Ok(Self {
+            emitter,
+            timestamp,
+            session_id,
+            handle_id,
+            opaque_id,
+            event,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_events;
+    use super::EventType;
+    use super::HandleEvent;
+    use super::HandleEventName;
+    use super::MediaEvent;
+    use super::PeerConnectionEvent;
+    use super::SessionEventName;
+    use super::WebRtcEvent;
+    use serde_json::json;
+
+    #[test]
+    fn it_should_decode_a_batch_of_events() {
+        let payload = json!([
+            {
+                "emitter": "MyJanusInstance",
+                "type": 1,
+                "timestamp": 1_700_000_000_000_000u64,
+                "session_id": 1,
+                "event": {"name": "created", "transport": {"transport": "janus.transport.http"}}
+            },
+            {
+                "emitter": "MyJanusInstance",
+                "type": 2,
+                "timestamp": 1_700_000_000_000_001u64,
+                "session_id": 1,
+                "handle_id": 2,
+                "opaque_id": "echotest-abc",
+                "event": {"name": "attached", "plugin": "janus.plugin.echotest", "opaque_id": "echotest-abc"}
+            },
+            {
+                "emitter": "MyJanusInstance",
+                "type": 16,
+                "subtype": 6,
+                "timestamp": 1_700_000_000_000_002u64,
+                "session_id": 1,
+                "handle_id": 2,
+                "event": {"connection": "webrtcup"}
+            },
+            {
+                "emitter": "MyJanusInstance",
+                "type": 32,
+                "subtype": 3,
+                "timestamp": 1_700_000_000_000_003u64,
+                "session_id": 1,
+                "handle_id": 2,
+                "event": {"mid": "0", "mindex": 0, "media": "audio", "base": 48000, "rtt": 12, "lost": 0, "jitter-local": 1}
+            },
+            {
+                "type": 1024,
+                "timestamp": 1_700_000_000_000_004u64,
+                "event": {}
+            }
+        ]);
+
+        let events = decode_events(payload.to_string().as_bytes())
+            .unwrap()
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(events.len(), 5);
+        assert!(
+            matches!(&events[0].event, EventType::Session(session) if session.name == SessionEventName::Created)
+        );
+        assert_eq!(
+            events[1].event,
+            EventType::Handle(HandleEvent {
+                name: HandleEventName::Attached,
+                plugin: "janus.plugin.echotest".to_string(),
+                opaque_id: Some("echotest-abc".to_string())
+            })
+        );
+        assert_eq!(
+            events[2].event,
+            EventType::WebRtc(WebRtcEvent::PeerConnection(PeerConnectionEvent {
+                connection: "webrtcup".to_string(),
+                reason: None
+            }))
+        );
+        let EventType::Media(MediaEvent::Stats(stats)) = &events[3].event else {
+            panic!("Expected media stats, got {:?}", events[3].event);
+        };
+        assert_eq!(stats.rtt, Some(12));
+        assert_eq!(stats.other["jitter-local"], 1);
+        assert!(matches!(
+            events[4].event,
+            EventType::Unknown {
+                event_type: 1024,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn it_should_decode_a_single_event() {
+        let payload = json!({
+            "emitter": "MyJanusInstance",
+            "type": 64,
+            "timestamp": 1_700_000_000_000_000u64,
+            "session_id": 1,
+            "handle_id": 2,
+            "event": {"plugin": "janus.plugin.videoroom", "data": {"event": "joined", "room": 1234}}
+        });
+
+        let events = decode_events(payload.to_string().as_bytes())
+            .unwrap()
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].handle_id, Some(2));
+        assert!(
+            matches!(&events[0].event, EventType::Plugin(plugin) if plugin.data["room"] == 1234)
+        );
+    }
+
+    #[test]
+    fn it_should_decode_the_rest_of_a_batch_around_a_malformed_event() {
+        let payload = json!([
+            {"type": 1, "timestamp": 1, "session_id": 1, "event": {"name": "created"}},
+            {"type": 1},
+            {"type": 1, "timestamp": 2, "session_id": 2, "event": {"name": "destroyed"}}
+        ]);
+
+        let events = decode_events(payload.to_string().as_bytes()).unwrap();
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].as_ref().unwrap().session_id, Some(1));
+        assert!(events[1].is_err());
+        assert_eq!(events[2].as_ref().unwrap().session_id, Some(2));
+    }
+
+    #[test]
+    fn it_should_fail_a_payload_that_is_not_json() {
+        assert!(decode_events(b"not json").is_err());
+    }
+}
@@ -0,0 +1,420 @@
+use super::evh_protocol::decode_events;
+use super::evh_protocol::JanusEvent;
+use crate::event_channel::event_channel;
+use crate::event_channel::Coalesce;
+use crate::event_channel::EventChannelConfig;
+use crate::event_channel::EventReceiver;
+use crate::event_channel::EventSender;
+use crate::event_channel::OverflowPolicy;
+use crate::Error;
+use axum::body::Bytes;
+use axum::extract::ws::Message;
+use axum::extract::ws::WebSocket;
+use axum::extract::State;
+use axum::extract::WebSocketUpgrade;
+use axum::http::header;
+use axum::http::HeaderMap;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::get;
+use axum::Router;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures_util::stream::BoxStream;
+use futures_util::Stream;
+use futures_util::StreamExt;
+use jarust_rt::JaTask;
+use std::fmt;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use tokio::net::TcpListener;
+use tokio::net::ToSocketAddrs;
+
+/// Configuration of the event handler receiver
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct EventHandlerConfig {
+    /// The path janus pushes the events to, i.e. the path of the `backend` url in the event handler config
+    pub path: String,
+    /// Basic auth credentials expected from the HTTP event handler (`backend_user` and `backend_pwd`)
+    pub credentials: Option<(String, String)>,
+    /// The channel the events are queued in until read from the [`JanusEventStream`]. Blocks by default,
+    /// holding back janus' requests while the channel is full
+    pub event_channel: EventChannelConfig,
+}
+
+impl Default for EventHandlerConfig {
+    fn default() -> Self {
+        Self {
+            path: "/".to_string(),
+            credentials: None,
+            event_channel: EventChannelConfig {
+                overflow: OverflowPolicy::Block,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    authorization: Option<String>,
+    sender: EventSender<JanusEvent>,
+}
+
+impl Coalesce for JanusEvent {
+    fn coalesce_key(&self) -> Option<String> {
+        None
+    }
+}
+
+/// An embeddable receiver of the janus event handlers.
+///
+/// It accepts the events posted by the HTTP event handler (`janus.eventhandler.sampleevh`) and the events
+/// sent by the WebSocket event handler (`janus.eventhandler.wsevh`) on the same path, and forwards them
+/// to a [`JanusEventStream`]. The server stops once dropped.
+#[derive(Debug)]
+pub struct EventHandlerServer {
+    local_addr: SocketAddr,
+    _task: JaTask,
+}
+
+impl EventHandlerServer {
+    /// Binds the receiver to the given address
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    pub async fn bind(
+        addr: impl ToSocketAddrs,
+        config: EventHandlerConfig,
+    ) -> Result<(Self, JanusEventStream), Error> {
+        let listener = TcpListener::bind(addr).await?;
+        Ok(Self::serve(listener, config)?)
+    }
+
+    /// Serves the receiver on an already bound listener
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    pub fn serve(
+        listener: TcpListener,
+        config: EventHandlerConfig,
+    ) -> std::io::Result<(Self, JanusEventStream)> {
+        let local_addr = listener.local_addr()?;
+        let (sender, receiver) = event_channel(config.event_channel);
+        let authorization = config.credentials.map(|(user, password)| {
+            format!("Basic {}", STANDARD.encode(format!("{user}:{password}")))
+        });
+        let shared = Arc::new(Shared {
+            authorization,
+            sender,
+        });
+        let router = Router::new()
+            .route(&config.path, get(receive_ws).post(receive_http))
+            .with_state(shared);
+
+        tracing::info!("Receiving janus events on {local_addr}{}", config.path);
+        let task = jarust_rt::spawn("Event handler server", async move {
+            if let Err(what) = axum::serve(listener, router).await {
+                tracing::error!("Event handler server stopped: {what}");
+            }
+        });
+        Ok((
+            Self {
+                local_addr,
+                _task: task,
+            },
+            JanusEventStream::new(receiver),
+        ))
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+/// The events received by an [`EventHandlerServer`], in the order janus emitted them
+pub struct JanusEventStream {
+    events: BoxStream<'static, JanusEvent>,
+}
+
+impl JanusEventStream {
+    fn new(receiver: EventReceiver<JanusEvent>) -> Self {
+        let events = futures_util::stream::unfold(receiver, |mut receiver| async move {
+            let event = receiver.recv().await?;
+            Some((event, receiver))
+        });
+        Self {
+            events: events.boxed(),
+        }
+    }
+
+    /// Receives the next event, `None` once the server is dropped
+    pub async fn recv(&mut self) -> Option<JanusEvent> {
+        self.events.next().await
+    }
+}
+
+impl fmt::Debug for JanusEventStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JanusEventStream").finish_non_exhaustive()
+    }
+}
+
+impl Stream for JanusEventStream {
+    type Item = JanusEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_next_unpin(cx)
+    }
+}
+
+async fn receive_http(
+    State(shared): State<Arc<Shared>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    if let Some(authorization) = &shared.authorization {
+        if headers
+            .get(header::AUTHORIZATION)
+            .map(|value| value.as_bytes())
+            != Some(authorization.as_bytes())
+        {
+            tracing::warn!("Rejected events with missing or wrong credentials");
+            return StatusCode::UNAUTHORIZED;
+        }
+    }
+    match forward(&shared, &body).await {
+        Ok(()) => StatusCode::OK,
+        Err(()) => StatusCode::BAD_REQUEST,
+    }
+}
+
+async fn receive_ws(
+    State(shared): State<Arc<Shared>>,
+    headers: HeaderMap,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    // Janus lets the subprotocol be configured, so whatever it asks for is accepted
+    let protocols = headers
+        .get(header::SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .split(',')
+                .map(|protocol| protocol.trim().to_string())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    upgrade
+        .protocols(protocols)
+        .on_upgrade(move |socket| receive_ws_messages(shared, socket))
+        .into_response()
+}
+
+async fn receive_ws_messages(shared: Arc<Shared>, mut socket: WebSocket) {
+    tracing::debug!("Event handler connected");
+    while let Some(Ok(message)) = socket.recv().await {
+        match message {
+            Message::Text(text) => {
+                let _ = forward(&shared, text.as_bytes()).await;
+            }
+            Message::Binary(data) => {
+                let _ = forward(&shared, &data).await;
+            }
+            Message::Close(_) => break,
+            Message::Ping(_) | Message::Pong(_) => {}
+        }
+    }
+    tracing::debug!("Event handler disconnected");
+}
+
+/// Waits for room in the event channel, so a slow consumer holds back janus rather than queueing without bound
+async fn forward(shared: &Shared, payload: &[u8]) -> Result<(), ()> {
+    let events = match decode_events(payload) {
+        Ok(events) => events,
+        Err(what) => {
+            tracing::error!("Failed to parse events: {what}");
+            return Err(());
+        }
+    };
+    for event in events {
+        match event {
+            Ok(event) => {
+                if shared.sender.send(event).await.is_err() {
+                    tracing::debug!("Event stream dropped, discarding the events");
+                    break;
+                }
+            }
+            Err(what) => tracing::error!("Failed to decode event: {what}"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EventHandlerConfig;
+    use super::EventHandlerServer;
+    use crate::event_channel::EventChannelConfig;
+    use crate::event_channel::OverflowPolicy;
+    use crate::event_handler::evh_protocol::EventType;
+    use futures_util::SinkExt;
+    use futures_util::StreamExt;
+    use serde_json::json;
+    use std::time::Duration;
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::Message;
+
+    fn session_created(session_id: u64) -> serde_json::Value {
+        json!({
+            "emitter": "MyJanusInstance",
+            "type": 1,
+            "timestamp": 1_700_000_000_000_000u64,
+            "session_id": session_id,
+            "event": {"name": "created"}
+        })
+    }
+
+    #[tokio::test]
+    async fn it_should_receive_posted_events() {
+        let config = EventHandlerConfig {
+            path: "/events".to_string(),
+            credentials: Some(("janus".to_string(), "secret".to_string())),
+            ..Default::default()
+        };
+        let (server, mut events) = EventHandlerServer::bind("127.0.0.1:0", config)
+            .await
+            .unwrap();
+
+        let body = json!([session_created(1), {"type": 1}, session_created(2)]).to_string();
+        let mut stream = TcpStream::connect(server.local_addr()).await.unwrap();
+        stream
+            .write_all(
+                format!(
+                    "POST /events HTTP/1.1\r\nhost: localhost\r\nauthorization: Basic amFudXM6c2VjcmV0\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+        let timeout = Duration::from_secs(5);
+        let first = tokio::time::timeout(timeout, events.next())
+            .await
+            .unwrap()
+            .unwrap();
+        let second = tokio::time::timeout(timeout, events.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.session_id, Some(1));
+        assert_eq!(second.session_id, Some(2));
+        assert!(matches!(first.event, EventType::Session(_)));
+    }
+
+    #[tokio::test]
+    async fn it_should_hold_back_janus_while_the_stream_is_full() {
+        let config = EventHandlerConfig {
+            event_channel: EventChannelConfig {
+                capacity: 1,
+                overflow: OverflowPolicy::Block,
+            },
+            ..Default::default()
+        };
+        let (server, mut events) = EventHandlerServer::bind("127.0.0.1:0", config)
+            .await
+            .unwrap();
+
+        let body = json!([session_created(1), session_created(2)]).to_string();
+        let mut stream = TcpStream::connect(server.local_addr()).await.unwrap();
+        stream
+            .write_all(
+                format!(
+                    "POST / HTTP/1.1\r\nhost: localhost\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+        let mut response = String::new();
+        let pending = tokio::time::timeout(
+            Duration::from_millis(200),
+            stream.read_to_string(&mut response),
+        )
+        .await;
+        assert!(pending.is_err(), "{response}");
+
+        assert_eq!(events.next().await.unwrap().session_id, Some(1));
+        tokio::time::timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert_eq!(events.next().await.unwrap().session_id, Some(2));
+    }
+
+    #[tokio::test]
+    async fn it_should_reject_wrong_credentials() {
+        let config = EventHandlerConfig {
+            credentials: Some(("janus".to_string(), "secret".to_string())),
+            ..Default::default()
+        };
+        let (server, _events) = EventHandlerServer::bind("127.0.0.1:0", config)
+            .await
+            .unwrap();
+
+        let body = session_created(1).to_string();
+        let mut stream = TcpStream::connect(server.local_addr()).await.unwrap();
+        stream
+            .write_all(
+                format!(
+                    "POST / HTTP/1.1\r\nhost: localhost\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 401"), "{response}");
+    }
+
+    #[tokio::test]
+    async fn it_should_receive_websocket_events() {
+        let (server, mut events) =
+            EventHandlerServer::bind("127.0.0.1:0", EventHandlerConfig::default())
+                .await
+                .unwrap();
+
+        let mut request = format!("ws://{}/", server.local_addr())
+            .into_client_request()
+            .unwrap();
+        request.headers_mut().insert(
+            "Sec-WebSocket-Protocol",
+            "janus-event-handlers".parse().unwrap(),
+        );
+        let (mut ws, response) = tokio_tungstenite::connect_async(request).await.unwrap();
+        assert_eq!(
+            response.headers()["Sec-WebSocket-Protocol"],
+            "janus-event-handlers"
+        );
+        ws.send(Message::Text(session_created(7).to_string().into()))
+            .await
+            .unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.session_id, Some(7));
+    }
+}
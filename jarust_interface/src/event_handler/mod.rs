@@ -0,0 +1,10 @@
+pub mod evh_protocol;
+#[cfg(feature = "event-handler")]
+pub mod evh_server;
+
+#[cfg(feature = "event-handler")]
+pub use evh_server::EventHandlerConfig;
+#[cfg(feature = "event-handler")]
+pub use evh_server::EventHandlerServer;
+#[cfg(feature = "event-handler")]
+pub use evh_server::JanusEventStream;
@@ -0,0 +1,635 @@
+use crate::compression_metrics::CompressionMetrics;
+use crate::connection_state::ConnectionState;
+use crate::connection_state::ConnectionStateTracker;
+use crate::connection_state::StateTransition;
+use crate::event_channel::EventChannelConfig;
+use crate::event_channel::EventReceiver;
+use crate::fallback::Transport;
+use crate::handle_msg::HandleMessage;
+use crate::handle_msg::HandleMessageWithJsep;
+use crate::janus_interface::ConnectionParams;
+use crate::janus_interface::JanusInterface;
+use crate::japrotocol::JaResponse;
+use crate::japrotocol::ServerInfoRsp;
+use crate::restful::RestfulInterface;
+use crate::tgenerator::GenerateTransaction;
+use crate::transaction_metrics::TransactionMetrics;
+use crate::websocket::WebSocketInterface;
+use crate::Error;
+use jarust_rt::JaTask;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::Weak;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+/// The interface only falls back once, so only a single batch of sessions is ever lost
+const LOST_SESSIONS_CAPACITY: usize = 1;
+
+#[derive(Debug)]
+struct Shared {
+    restful: RestfulInterface,
+    state: ConnectionStateTracker,
+    lost_sessions: broadcast::Sender<Vec<u64>>,
+}
+
+#[derive(Debug)]
+struct Exclusive {
+    /// `None` if the upgrade failed, or once fallen back
+    websocket: Option<WebSocketInterface>,
+    active: Transport,
+    /// The transport each session was created with
+    sessions: HashMap<u64, Transport>,
+    tasks: Vec<JaTask>,
+}
+
+#[derive(Debug)]
+struct InnerFallbackInterface {
+    shared: Shared,
+    exclusive: Mutex<Exclusive>,
+}
+
+/// Goes through websocket, and falls back to restful (with long polling) if the upgrade fails or the
+/// connection keeps dropping, see [`FallbackConfig`](crate::janus_interface::FallbackConfig).
+///
+/// New sessions are created with the active transport, the requests of a session then go through the
+/// transport it was created with. The websocket sessions don't survive the fall back, their pending requests
+/// are failed and they're reported through [`lost_sessions`](JanusInterface::lost_sessions).
+#[derive(Debug, Clone)]
+pub struct FallbackInterface {
+    inner: Arc<InnerFallbackInterface>,
+}
+
+impl FallbackInterface {
+    fn exclusive(&self) -> MutexGuard<'_, Exclusive> {
+        self.inner.exclusive()
+    }
+
+    /// The transport of the session, the active one for the sessions it didn't create
+    fn route(&self, session_id: u64) -> Box<dyn JanusInterface> {
+        let transport = {
+            let exclusive = self.exclusive();
+            exclusive
+                .sessions
+                .get(&session_id)
+                .copied()
+                .unwrap_or(exclusive.active)
+        };
+        self.inner.interface(transport)
+    }
+}
+
+impl InnerFallbackInterface {
+    fn exclusive(&self) -> MutexGuard<'_, Exclusive> {
+        self.exclusive
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// The interface of the transport, cloned so the lock isn't held across the requests
+    fn interface(&self, transport: Transport) -> Box<dyn JanusInterface> {
+        match (transport, &self.exclusive().websocket) {
+            (Transport::WebSocket, Some(websocket)) => Box::new(websocket.clone()),
+            _ => Box::new(self.shared.restful.clone()),
+        }
+    }
+
+    /// Switches to restful, the websocket sessions are lost along with their socket. The websocket interface
+    /// is dropped, which stops it from reconnecting.
+    async fn fall_back(&self, reason: String) {
+        tracing::warn!("Falling back to restful: {reason}");
+        let (websocket, lost) = {
+            let mut exclusive = self.exclusive();
+            exclusive.active = Transport::Restful;
+            let websocket = exclusive.websocket.take();
+            let lost = exclusive
+                .sessions
+                .iter()
+                .filter(|(_, transport)| **transport == Transport::WebSocket)
+                .map(|(session_id, _)| *session_id)
+                .collect::<Vec<_>>();
+            exclusive
+                .sessions
+                .retain(|_, transport| *transport != Transport::WebSocket);
+            (websocket, lost)
+        };
+        if !lost.is_empty() {
+            tracing::warn!(sessions = ?lost, "Losing the websocket sessions");
+            if let Some(websocket) = &websocket {
+                for &session_id in &lost {
+                    websocket.forget_session(session_id).await;
+                }
+            }
+            let _ = self.shared.lost_sessions.send(lost);
+        }
+        self.shared.state.set(
+            ConnectionState::Connecting,
+            format!("Falling back to restful: {reason}"),
+        );
+        self.shared
+            .state
+            .set(ConnectionState::Connected, "Fell back to restful");
+    }
+}
+
+#[async_trait::async_trait]
+impl JanusInterface for FallbackInterface {
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn make_interface(
+        conn_params: ConnectionParams,
+        transaction_generator: impl GenerateTransaction,
+    ) -> Result<Self, Error> {
+        tracing::debug!("Creating new Fallback Interface");
+        let fallback = conn_params.fallback.clone();
+        if fallback.url.is_empty() {
+            return Err(Error::InvalidUrl {
+                reason: "No restful url to fall back to".to_string(),
+            });
+        }
+        let transaction_generator = Arc::new(transaction_generator);
+        let restful_params = ConnectionParams {
+            url: fallback.url,
+            ..conn_params.clone()
+        };
+        let restful =
+            RestfulInterface::make_interface(restful_params, transaction_generator.clone()).await?;
+
+        let upgrade = jarust_rt::timeout(
+            fallback.upgrade_timeout,
+            WebSocketInterface::make_interface(conn_params, transaction_generator),
+        )
+        .await;
+        let (websocket, active) = match upgrade {
+            Ok(Ok(websocket)) => (Some(websocket), Transport::WebSocket),
+            Ok(Err(e)) => {
+                tracing::warn!("WebSocket upgrade failed, falling back to restful: {e}");
+                (None, Transport::Restful)
+            }
+            Err(_) => {
+                tracing::warn!("WebSocket upgrade timed out, falling back to restful");
+                (None, Transport::Restful)
+            }
+        };
+        let transitions = websocket
+            .as_ref()
+            .and_then(JanusInterface::connection_state)
+            .map(|state| state.transitions());
+
+        let shared = Shared {
+            restful,
+            state: ConnectionStateTracker::new(ConnectionState::Connected),
+            lost_sessions: broadcast::channel(LOST_SESSIONS_CAPACITY).0,
+        };
+        let exclusive = Mutex::new(Exclusive {
+            websocket,
+            active,
+            sessions: HashMap::new(),
+            tasks: Vec::new(),
+        });
+        let inner = Arc::new(InnerFallbackInterface { shared, exclusive });
+        if let Some(transitions) = transitions {
+            let task = jarust_rt::spawn(
+                "Fallback task",
+                watch_websocket(Arc::downgrade(&inner), transitions, fallback.max_drops),
+            );
+            inner.exclusive().tasks.push(task);
+        }
+        Ok(Self { inner })
+    }
+
+    async fn create(&self, timeout: Duration) -> Result<u64, Error> {
+        let active = self.exclusive().active;
+        let session_id = self.inner.interface(active).create(timeout).await?;
+        tracing::debug!(session_id, ?active, "Session created");
+        self.exclusive().sessions.insert(session_id, active);
+        Ok(session_id)
+    }
+
+    async fn server_info(&self, timeout: Duration) -> Result<ServerInfoRsp, Error> {
+        let active = self.exclusive().active;
+        self.inner.interface(active).server_info(timeout).await
+    }
+
+    async fn attach(
+        &self,
+        session_id: u64,
+        plugin_id: String,
+        channel: Option<EventChannelConfig>,
+        timeout: Duration,
+    ) -> Result<(u64, EventReceiver<JaResponse>), Error> {
+        self.route(session_id)
+            .attach(session_id, plugin_id, channel, timeout)
+            .await
+    }
+
+    fn has_keep_alive(&self) -> bool {
+        // Only the websocket sessions need them, the restful interface ignores them
+        self.exclusive().websocket.is_some()
+    }
+
+    async fn keep_alive(&self, session_id: u64, timeout: Duration) -> Result<(), Error> {
+        self.route(session_id).keep_alive(session_id, timeout).await
+    }
+
+    async fn destroy(&self, session_id: u64, timeout: Duration) -> Result<(), Error> {
+        self.route(session_id).destroy(session_id, timeout).await?;
+        self.exclusive().sessions.remove(&session_id);
+        Ok(())
+    }
+
+    async fn fire_and_forget_msg(&self, message: HandleMessage) -> Result<String, Error> {
+        self.route(message.session_id)
+            .fire_and_forget_msg(message)
+            .await
+    }
+
+    async fn send_msg_waiton_ack(
+        &self,
+        message: HandleMessage,
+        timeout: Duration,
+    ) -> Result<String, Error> {
+        self.route(message.session_id)
+            .send_msg_waiton_ack(message, timeout)
+            .await
+    }
+
+    async fn internal_send_msg_waiton_rsp(
+        &self,
+        message: HandleMessage,
+        timeout: Duration,
+    ) -> Result<JaResponse, Error> {
+        self.route(message.session_id)
+            .internal_send_msg_waiton_rsp(message, timeout)
+            .await
+    }
+
+    async fn fire_and_forget_msg_with_jsep(
+        &self,
+        message: HandleMessageWithJsep,
+    ) -> Result<String, Error> {
+        self.route(message.session_id)
+            .fire_and_forget_msg_with_jsep(message)
+            .await
+    }
+
+    async fn send_msg_waiton_ack_with_jsep(
+        &self,
+        message: HandleMessageWithJsep,
+        timeout: Duration,
+    ) -> Result<String, Error> {
+        self.route(message.session_id)
+            .send_msg_waiton_ack_with_jsep(message, timeout)
+            .await
+    }
+
+    async fn send_handle_request(&self, request: HandleMessage) -> Result<(), Error> {
+        self.route(request.session_id)
+            .send_handle_request(request)
+            .await
+    }
+
+    async fn send_handle_request_waiton_ack(
+        &self,
+        request: HandleMessage,
+        timeout: Duration,
+    ) -> Result<String, Error> {
+        self.route(request.session_id)
+            .send_handle_request_waiton_ack(request, timeout)
+            .await
+    }
+
+    fn connection_state(&self) -> Option<ConnectionStateTracker> {
+        Some(self.inner.shared.state.clone())
+    }
+
+    fn transaction_metrics(&self) -> Option<TransactionMetrics> {
+        self.exclusive().websocket.as_ref()?.transaction_metrics()
+    }
+
+    fn compression_metrics(&self) -> Option<CompressionMetrics> {
+        self.exclusive().websocket.as_ref()?.compression_metrics()
+    }
+
+    fn active_transport(&self) -> Option<Transport> {
+        Some(self.exclusive().active)
+    }
+
+    fn lost_sessions(&self) -> Option<broadcast::Receiver<Vec<u64>>> {
+        Some(self.inner.shared.lost_sessions.subscribe())
+    }
+
+    fn name(&self) -> Box<str> {
+        "Fallback Interface".to_string().into_boxed_str()
+    }
+}
+
+impl Drop for InnerFallbackInterface {
+    fn drop(&mut self) {
+        self.shared
+            .state
+            .set(ConnectionState::Closed, "Interface dropped");
+        self.exclusive().tasks.drain(..).for_each(|task| {
+            task.cancel();
+        });
+    }
+}
+
+/// Mirrors the websocket connection state until it's closed or it dropped `max_drops` times, then falls back
+async fn watch_websocket(
+    interface: Weak<InnerFallbackInterface>,
+    mut transitions: broadcast::Receiver<StateTransition>,
+    max_drops: u32,
+) {
+    let mut drops = 0;
+    loop {
+        let transition = match transitions.recv().await {
+            Ok(transition) => transition,
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return,
+        };
+        let Some(inner) = interface.upgrade() else {
+            return;
+        };
+        let StateTransition { from, to, reason } = transition;
+        if to == ConnectionState::Closed {
+            inner
+                .fall_back(format!("WebSocket closed ({reason})"))
+                .await;
+            return;
+        }
+        if to == ConnectionState::Disconnected
+            && matches!(from, ConnectionState::Connected | ConnectionState::Degraded)
+        {
+            drops += 1;
+            if drops >= max_drops {
+                inner
+                    .fall_back(format!("WebSocket dropped {drops} times ({reason})"))
+                    .await;
+                return;
+            }
+        }
+        inner.shared.state.set(to, reason);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FallbackInterface;
+    use super::Transport;
+    use crate::backoff::Backoff;
+    use crate::connection_state::ConnectionState;
+    use crate::janus_interface::ConnectionParams;
+    use crate::janus_interface::FallbackConfig;
+    use crate::janus_interface::JanusInterface;
+    use crate::janus_interface::ReconnectConfig;
+    use crate::restful::restful_interface::tests::serve;
+    use crate::tgenerator::RandomTransactionGenerator;
+    use crate::websocket::handshake::Accept;
+    use futures_util::SinkExt;
+    use futures_util::StreamExt;
+    use serde_json::json;
+    use serde_json::Value;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::handshake::server::Request;
+    use tokio_tungstenite::tungstenite::handshake::server::Response;
+    use tokio_tungstenite::tungstenite::Message;
+
+    const SESSION_ID: u64 = 1;
+
+    /// Serves the restful api, hanging the long polls and refusing the websocket upgrades
+    async fn serve_restful() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(serve(
+            listener,
+            |request_line: String, body: Value| async move {
+                if request_line.starts_with("GET /janus/") {
+                    std::future::pending::<()>().await;
+                }
+                if body["janus"] == "create" {
+                    return json!({"janus": "success", "transaction": body["transaction"], "data": {"id": SESSION_ID}});
+                }
+                json!({"janus": "error", "error": {"code": 404, "reason": "Not found"}})
+            },
+        ));
+        url
+    }
+
+    #[tokio::test]
+    async fn it_should_fall_back_when_the_upgrade_is_refused() {
+        let url = serve_restful().await;
+        let conn_params = ConnectionParams {
+            url: url.replace("http", "ws"),
+            fallback: FallbackConfig {
+                url,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let interface = FallbackInterface::make_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap();
+
+        assert_eq!(interface.active_transport(), Some(Transport::Restful));
+        assert!(!interface.has_keep_alive());
+        let session_id = interface.create(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(session_id, SESSION_ID);
+    }
+
+    #[tokio::test]
+    async fn it_should_fall_back_once_the_websocket_is_closed() {
+        let url = serve_restful().await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_url = format!("ws://{}", listener.local_addr().unwrap());
+        let (close_tx, close_rx) = tokio::sync::oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let callback = Accept(|_: &Request, response: &mut Response| {
+                response
+                    .headers_mut()
+                    .insert("Sec-Websocket-Protocol", "janus-protocol".parse().unwrap());
+            });
+            let mut ws = tokio_tungstenite::accept_hdr_async(stream, callback)
+                .await
+                .unwrap();
+            close_rx.await.unwrap();
+            ws.close(None).await.unwrap();
+            while ws.next().await.is_some() {}
+        });
+
+        let conn_params = ConnectionParams {
+            url: ws_url,
+            fallback: FallbackConfig {
+                url,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let interface = FallbackInterface::make_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap();
+        assert_eq!(interface.active_transport(), Some(Transport::WebSocket));
+
+        let mut transitions = interface.connection_state().unwrap().transitions();
+        close_tx.send(()).unwrap();
+        let timeout = Duration::from_secs(5);
+        let falling_back = tokio::time::timeout(timeout, transitions.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let fell_back = tokio::time::timeout(timeout, transitions.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(falling_back.to, ConnectionState::Connecting);
+        assert!(falling_back.reason.starts_with("Falling back to restful"));
+        assert_eq!(fell_back.to, ConnectionState::Connected);
+        assert_eq!(interface.active_transport(), Some(Transport::Restful));
+        let session_id = interface.create(timeout).await.unwrap();
+        assert_eq!(session_id, SESSION_ID);
+    }
+
+    #[tokio::test]
+    async fn it_should_lose_the_websocket_sessions_when_falling_back() {
+        const WS_SESSION_ID: u64 = 7;
+        let url = serve_restful().await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_url = format!("ws://{}", listener.local_addr().unwrap());
+        let (close_tx, close_rx) = tokio::sync::oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let callback = Accept(|_: &Request, response: &mut Response| {
+                response
+                    .headers_mut()
+                    .insert("Sec-Websocket-Protocol", "janus-protocol".parse().unwrap());
+            });
+            let mut ws = tokio_tungstenite::accept_hdr_async(stream, callback)
+                .await
+                .unwrap();
+            let message = ws.next().await.unwrap().unwrap();
+            let request: Value = serde_json::from_slice(&message.into_data()).unwrap();
+            let response = json!({"janus": "success", "transaction": request["transaction"], "data": {"id": WS_SESSION_ID}});
+            ws.send(Message::Text(response.to_string().into()))
+                .await
+                .unwrap();
+            // The keep-alive is left unanswered
+            ws.next().await.unwrap().unwrap();
+            close_rx.await.unwrap();
+            ws.close(None).await.unwrap();
+            while ws.next().await.is_some() {}
+        });
+
+        let conn_params = ConnectionParams {
+            url: ws_url,
+            fallback: FallbackConfig {
+                url,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let interface = FallbackInterface::make_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap();
+        let timeout = Duration::from_secs(5);
+        let session_id = interface.create(timeout).await.unwrap();
+        assert_eq!(session_id, WS_SESSION_ID);
+        let mut lost_sessions = interface.lost_sessions().unwrap();
+        let keep_alive = tokio::spawn({
+            let interface = interface.clone();
+            async move {
+                interface
+                    .keep_alive(WS_SESSION_ID, Duration::from_secs(60))
+                    .await
+            }
+        });
+
+        close_tx.send(()).unwrap();
+        let lost = tokio::time::timeout(timeout, lost_sessions.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lost, [WS_SESSION_ID]);
+        let keep_alive = tokio::time::timeout(timeout, keep_alive)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(keep_alive.is_err());
+        assert!(interface.exclusive().sessions.is_empty());
+        assert_eq!(interface.active_transport(), Some(Transport::Restful));
+    }
+
+    #[tokio::test]
+    async fn it_should_stop_reconnecting_once_fallen_back() {
+        let url = serve_restful().await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_url = format!("ws://{}", listener.local_addr().unwrap());
+        let attempts = Arc::new(AtomicUsize::new(0));
+        tokio::spawn({
+            let attempts = attempts.clone();
+            async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                let callback = Accept(|_: &Request, response: &mut Response| {
+                    response
+                        .headers_mut()
+                        .insert("Sec-Websocket-Protocol", "janus-protocol".parse().unwrap());
+                });
+                let ws = tokio_tungstenite::accept_hdr_async(stream, callback)
+                    .await
+                    .unwrap();
+                drop(ws);
+                // The reconnection attempts are refused
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    attempts.fetch_add(1, Ordering::Relaxed);
+                    drop(stream);
+                }
+            }
+        });
+
+        let conn_params = ConnectionParams {
+            url: ws_url,
+            reconnect: Some(ReconnectConfig {
+                backoff: Backoff {
+                    initial_delay: Duration::from_millis(10),
+                    max_delay: Duration::from_millis(10),
+                },
+                ..Default::default()
+            }),
+            fallback: FallbackConfig {
+                url,
+                max_drops: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let interface = FallbackInterface::make_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap();
+        let mut transitions = interface.connection_state().unwrap().transitions();
+        let fell_back = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let transition = transitions.recv().await.unwrap();
+                if transition.reason == "Fell back to restful" {
+                    break;
+                }
+            }
+        })
+        .await;
+        assert!(fell_back.is_ok());
+        assert_eq!(interface.active_transport(), Some(Transport::Restful));
+
+        // An attempt might have been under way while falling back
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let after_falling_back = attempts.load(Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(attempts.load(Ordering::Relaxed), after_falling_back);
+    }
+}
@@ -0,0 +1,12 @@
+#[cfg(not(target_family = "wasm"))]
+pub mod fallback_interface;
+
+#[cfg(not(target_family = "wasm"))]
+pub use fallback_interface::FallbackInterface;
+
+/// The transports the [`FallbackInterface`](fallback_interface::FallbackInterface) switches between
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Transport {
+    WebSocket,
+    Restful,
+}
@@ -2,18 +2,48 @@
 //!
 //! Jarust interface contains:
 //!
-//! - Transport abstraction, you can use the built-in WebSocket interface, restful interface, or bring your own.
+//! - Transport abstraction, you can use the built-in WebSocket interface, restful interface, unix socket interface,
+//!   MQTT interface (behind the `mqtt` feature), AMQP interface (behind the `amqp` feature), or bring your own.
+//!   The fallback interface goes through WebSocket and falls back to restful when WebSocket is blocked.
 //! - Transaction generation abstraction, you can use the built-in transaction generator or bring your own.
-//! - DTOs for the Janus API.
+//! - Token provider abstraction, to authenticate the requests when janus runs with `token_auth`.
+//! - Signed tokens, to mint and verify the stateless tokens of janus (`token_auth_secret`).
+//! - Admin/Monitor API interfaces, over HTTP or WebSocket.
+//! - DTOs for the Janus API, and for the events pushed to the event handlers.
+//! - Event handler receiver (behind the `event-handler` feature), an embeddable HTTP/WebSocket server
+//!   exposing the events pushed by janus as a stream.
 //! - Errors
 //!
+//! When targeting wasm, only the WebSocket interfaces are available, on top of the browser's WebSocket.
+//!
 
+pub mod admin;
+#[cfg(feature = "amqp")]
+pub mod amqp;
+pub mod backoff;
+pub mod compression_metrics;
+pub mod connection_state;
+pub mod demuxed_interface;
 pub mod error;
+pub mod event_channel;
+pub mod event_handler;
+pub mod fallback;
 pub mod handle_msg;
 pub mod janus_interface;
 pub mod japrotocol;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod plugin_id;
+#[cfg(not(target_family = "wasm"))]
 pub mod restful;
+pub mod signed_token;
 pub mod tgenerator;
+#[cfg(not(target_family = "wasm"))]
+pub(crate) mod tls;
+pub mod token_provider;
+pub mod transaction_metrics;
+#[cfg(unix)]
+pub mod unix_socket;
 pub mod websocket;
 
 pub type Error = error::Error;
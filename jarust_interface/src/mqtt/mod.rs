@@ -0,0 +1,6 @@
+mod mqtt_client;
+
+pub mod mqtt_interface;
+
+pub use mqtt_client::MqttClient;
+pub use mqtt_interface::MqttInterface;
@@ -0,0 +1,6 @@
+//! Package names of the janus plugins, used to attach to a plugin and to scope signed tokens.
+
+pub const ECHO_TEST: &str = "janus.plugin.echotest";
+pub const AUDIO_BRIDGE: &str = "janus.plugin.audiobridge";
+pub const VIDEO_ROOM: &str = "janus.plugin.videoroom";
+pub const STREAMING: &str = "janus.plugin.streaming";
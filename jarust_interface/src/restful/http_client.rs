@@ -0,0 +1,287 @@
+use crate::connection_state::ConnectionState;
+use crate::connection_state::ConnectionStateTracker;
+use crate::janus_interface::ConnectionParams;
+use crate::janus_interface::ProxyConfig;
+use crate::janus_interface::TlsConfig;
+#[cfg(feature = "use-rustls")]
+use crate::tls::make_tls_client_config;
+use crate::Error;
+#[cfg(feature = "use-native-tls")]
+use reqwest::Certificate;
+use reqwest::ClientBuilder;
+#[cfg(feature = "use-native-tls")]
+use reqwest::Identity;
+use reqwest::IntoUrl;
+use reqwest::NoProxy;
+use reqwest::Proxy;
+use reqwest::RequestBuilder;
+use reqwest::Response;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Http client of the restful interfaces, sending the configured headers with every request. The pinned server
+/// key (if any) is checked during the TLS handshake, before anything is sent, which needs `use-rustls`.
+///
+/// There's no connection to follow over http, so the connection state is driven by the outcome of the requests.
+#[derive(Clone, Debug)]
+pub(crate) struct HttpClient {
+    client: reqwest::Client,
+    state: ConnectionStateTracker,
+}
+
+/// A request of an [`HttpClient`], with the subset of the [`RequestBuilder`] api the interfaces rely on
+pub(crate) struct HttpRequest {
+    builder: RequestBuilder,
+    state: ConnectionStateTracker,
+}
+
+impl HttpClient {
+    pub(crate) fn get(&self, url: impl IntoUrl) -> HttpRequest {
+        self.request(self.client.get(url))
+    }
+
+    pub(crate) fn post(&self, url: impl IntoUrl) -> HttpRequest {
+        self.request(self.client.post(url))
+    }
+
+    /// The state of the server as seen by the requests, shared by all the clones of the client
+    pub(crate) fn state(&self) -> ConnectionStateTracker {
+        self.state.clone()
+    }
+
+    fn request(&self, builder: RequestBuilder) -> HttpRequest {
+        HttpRequest {
+            builder,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            state: ConnectionStateTracker::new(ConnectionState::Connected),
+        }
+    }
+}
+
+impl HttpRequest {
+    pub(crate) fn json<T: Serialize + ?Sized>(self, json: &T) -> Self {
+        self.map(|builder| builder.json(json))
+    }
+
+    pub(crate) fn query<T: Serialize + ?Sized>(self, query: &T) -> Self {
+        self.map(|builder| builder.query(query))
+    }
+
+    pub(crate) fn timeout(self, timeout: Duration) -> Self {
+        self.map(|builder| builder.timeout(timeout))
+    }
+
+    /// Sends the request, unreachable servers mark the state as disconnected, while timeouts and server errors
+    /// degrade it. Any other response means janus is back.
+    pub(crate) async fn send(self) -> Result<Response, Error> {
+        let response = match self.builder.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                if e.is_connect() {
+                    self.state.set(
+                        ConnectionState::Disconnected,
+                        format!("Request failed: {e}"),
+                    );
+                } else {
+                    self.state.degrade(format!("Request failed: {e}"));
+                }
+                return Err(e.into());
+            }
+        };
+        if response.status().is_server_error() {
+            self.state
+                .degrade(format!("Server error: {}", response.status()));
+        } else {
+            self.state
+                .set(ConnectionState::Connected, "Request succeeded");
+        }
+        Ok(response)
+    }
+
+    fn map(self, f: impl FnOnce(RequestBuilder) -> RequestBuilder) -> Self {
+        Self {
+            builder: f(self.builder),
+            state: self.state,
+        }
+    }
+}
+
+/// Builds the http client of the restful interfaces out of the configured headers, tls settings and proxy.
+pub(crate) fn make_client(conn_params: &ConnectionParams) -> Result<HttpClient, Error> {
+    let builder = reqwest::Client::builder().default_headers(conn_params.headers.header_map()?);
+    let mut builder = tls(builder, &conn_params.tls)?;
+    builder = match &conn_params.proxy {
+        // reqwest picks the proxy from the environment by default
+        ProxyConfig::Environment => builder,
+        ProxyConfig::Direct => builder.no_proxy(),
+        ProxyConfig::Custom { url, no_proxy } => {
+            let proxy =
+                Proxy::all(url)?.no_proxy(no_proxy.as_deref().and_then(NoProxy::from_string));
+            builder.proxy(proxy)
+        }
+    };
+    Ok(HttpClient {
+        client: builder.build()?,
+        state: ConnectionStateTracker::new(ConnectionState::Connected),
+    })
+}
+
+#[cfg(feature = "use-rustls")]
+fn tls(builder: ClientBuilder, tls: &TlsConfig) -> Result<ClientBuilder, Error> {
+    Ok(builder.use_preconfigured_tls(make_tls_client_config(tls)?))
+}
+
+/// Native TLS can't check the pin during the handshake, and checking it once the request is sent would be too late
+#[cfg(feature = "use-native-tls")]
+fn tls(mut builder: ClientBuilder, tls: &TlsConfig) -> Result<ClientBuilder, Error> {
+    if tls.spki_pin.is_some() {
+        return Err(Error::Tls {
+            reason: "Pinning the key of the restful servers needs the use-rustls feature"
+                .to_string(),
+        });
+    }
+    for pem in &tls.root_certificates {
+        builder = builder.add_root_certificate(Certificate::from_pem(pem)?);
+    }
+    if let Some(identity) = &tls.client_identity {
+        let identity =
+            Identity::from_pkcs8_pem(&identity.certificate_chain, &identity.private_key)?;
+        builder = builder.identity(identity);
+    }
+    if tls.danger_accept_invalid_certs {
+        tracing::warn!("Accepting invalid server certificates");
+        builder = builder
+            .danger_accept_invalid_certs(true)
+            .danger_accept_invalid_hostnames(true);
+    }
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::make_client;
+    use crate::janus_interface::ConnectionParams;
+    use crate::janus_interface::TlsConfig;
+    #[cfg(feature = "use-rustls")]
+    use crate::tls::tests::pin;
+    #[cfg(feature = "use-rustls")]
+    use crate::tls::tests::CERTIFICATE;
+    #[cfg(feature = "use-rustls")]
+    use crate::tls::tests::PRIVATE_KEY;
+    #[cfg(feature = "use-native-tls")]
+    use crate::Error;
+    #[cfg(feature = "use-rustls")]
+    use rustls::pki_types::pem::PemObject;
+    #[cfg(feature = "use-rustls")]
+    use rustls::pki_types::CertificateDer;
+    #[cfg(feature = "use-rustls")]
+    use rustls::pki_types::PrivateKeyDer;
+    #[cfg(feature = "use-rustls")]
+    use std::sync::Arc;
+    #[cfg(feature = "use-rustls")]
+    use tokio::io::AsyncReadExt;
+    #[cfg(feature = "use-rustls")]
+    use tokio::io::AsyncWriteExt;
+    #[cfg(feature = "use-rustls")]
+    use tokio::net::TcpListener;
+    #[cfg(feature = "use-rustls")]
+    use tokio::sync::mpsc;
+
+    /// Answers every request over TLS with the test certificate, reporting the requests it got
+    #[cfg(feature = "use-rustls")]
+    async fn serve() -> (String, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "https://localhost:{}",
+            listener.local_addr().unwrap().port()
+        );
+        let certificates = CertificateDer::pem_slice_iter(CERTIFICATE.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let private_key = PrivateKeyDer::from_pem_slice(PRIVATE_KEY.as_bytes()).unwrap();
+        let config = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(certificates, private_key)
+            .unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+        let (requests_tx, requests_rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let Ok(mut stream) = acceptor.accept(socket).await else {
+                    continue;
+                };
+                let mut buffer = vec![0; 4096];
+                let size = stream.read(&mut buffer).await.unwrap_or(0);
+                if size > 0 {
+                    requests_tx
+                        .send(String::from_utf8_lossy(&buffer[..size]).to_string())
+                        .unwrap();
+                }
+                let _ = stream
+                    .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                    .await;
+            }
+        });
+        (url, requests_rx)
+    }
+
+    #[cfg(feature = "use-rustls")]
+    fn client(spki_pin: [u8; 32]) -> super::HttpClient {
+        make_client(&ConnectionParams {
+            // The test certificate is its own CA, which webpki refuses, so only the pin is checked
+            tls: TlsConfig {
+                spki_pin: Some(spki_pin),
+                danger_accept_invalid_certs: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "use-rustls")]
+    async fn it_should_check_the_pin_before_sending_the_request() {
+        let (url, mut requests) = serve().await;
+
+        let result = client([0; 32])
+            .post(&url)
+            .json(&serde_json::json!({"apisecret": "leaked"}))
+            .send()
+            .await;
+        assert!(result.is_err());
+
+        let response = client(pin())
+            .post(&url)
+            .json(&serde_json::json!({"apisecret": "secret"}))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+        // The server is sequential, the mispinned request would have come first
+        let request = requests.recv().await.unwrap();
+        assert!(request.contains("secret"));
+        assert!(!request.contains("leaked"));
+    }
+
+    #[test]
+    #[cfg(feature = "use-native-tls")]
+    fn it_should_refuse_to_pin_without_rustls() {
+        let result = make_client(&ConnectionParams {
+            tls: TlsConfig {
+                spki_pin: Some([0; 32]),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        assert!(matches!(result, Err(Error::Tls { reason }) if reason.contains("use-rustls")));
+    }
+}
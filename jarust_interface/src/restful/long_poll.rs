@@ -0,0 +1,270 @@
+use super::http_client::HttpClient;
+use crate::janus_interface::LongPollConfig;
+use crate::japrotocol::ErrorResponse;
+use crate::japrotocol::JaResponse;
+use crate::japrotocol::ResponseType;
+use crate::token_provider::TokenProviderImpl;
+use crate::websocket::pending::PendingRequests;
+use crate::websocket::router::Router;
+use crate::Error;
+use serde_json::Value;
+
+/// Janus error code of an unknown session
+const SESSION_NOT_FOUND: u16 = 458;
+/// Janus error code of transport specific errors, used to report the failures that didn't come from janus
+const TRANSPORT_SPECIFIC_ERROR: u16 = 450;
+
+/// Polls the events of a session and routes them to its handles by their `sender`.
+///
+/// Events carrying a transaction are the asynchronous responses of requests that were only acked on the POST,
+/// so they're also handed to the requests waiting on them.
+///
+/// Failed polls are reported on the event streams of the handles, and retried with a backoff.
+/// Polling stops once janus no longer knows the session.
+#[derive(Debug)]
+pub(crate) struct LongPoll {
+    pub(crate) client: HttpClient,
+    /// The url of the session
+    pub(crate) url: String,
+    pub(crate) session_id: u64,
+    pub(crate) apisecret: Option<String>,
+    pub(crate) token_provider: Option<TokenProviderImpl>,
+    pub(crate) router: Router,
+    pub(crate) responses: PendingRequests<JaResponse>,
+    pub(crate) config: LongPollConfig,
+}
+
+impl LongPoll {
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all, fields(session_id = self.session_id))]
+    pub(crate) async fn run(self) {
+        let mut failures = 0;
+        loop {
+            let events = match self.poll().await {
+                Ok(events) => events,
+                Err(what) => {
+                    let delay = self.config.backoff.delay(failures);
+                    tracing::error!("Long poll failed, retrying in {delay:?}: {what}");
+                    let code = match what {
+                        Error::JanusError { code, .. } => code,
+                        _ => TRANSPORT_SPECIFIC_ERROR,
+                    };
+                    self.report(None, code, what.to_string()).await;
+                    failures = failures.saturating_add(1);
+                    jarust_rt::sleep(delay).await;
+                    continue;
+                }
+            };
+            failures = 0;
+            for event in events {
+                if let Err(reason) = self.forward(event).await {
+                    tracing::warn!("Session not found, stopping the long poll");
+                    self.report(None, SESSION_NOT_FOUND, reason).await;
+                    self.responses.fail_session(self.session_id);
+                    self.router
+                        .remove_subroutes(&self.session_id.to_string())
+                        .await;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Polls the events of the session, they're decoded one by one by [`LongPoll::forward`]
+    async fn poll(&self) -> Result<Vec<Value>, Error> {
+        let query = self.query().await?;
+        let response = self
+            .client
+            .get(&self.url)
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await?;
+        // A single event is returned unwrapped, e.g. the keepalive janus sends when no event happened in a while
+        let events = match response {
+            Value::Array(events) => events,
+            event => vec![event],
+        };
+        Ok(events)
+    }
+
+    /// Decodes and routes a single event, so a bad one doesn't drop the rest of the batch. Events that can't be
+    /// decoded are reported to their handle, or to all the handles of the session when there's no telling.
+    ///
+    /// Fails with the reason janus gave when it no longer knows the session.
+    async fn forward(&self, event: Value) -> Result<(), String> {
+        if event["janus"] == "keepalive" {
+            return Ok(());
+        }
+        let sender = event["sender"].as_u64();
+        let event = match serde_json::from_value::<JaResponse>(event) {
+            Ok(event) => event,
+            Err(what) => {
+                tracing::error!("Failed to decode event: {what}");
+                let reason = format!("Failed to decode event: {what}");
+                self.report(sender, TRANSPORT_SPECIFIC_ERROR, reason).await;
+                return Ok(());
+            }
+        };
+        if let ResponseType::Error { error } = &event.janus {
+            if error.code == SESSION_NOT_FOUND {
+                return Err(error.reason.clone());
+            }
+            tracing::error!("{error:#?}");
+            if event.transaction.is_none() && event.sender.is_none() {
+                self.report(None, error.code, error.reason.clone()).await;
+                return Ok(());
+            }
+        }
+        if let Some(transaction) = event.transaction.clone() {
+            self.responses.resolve(&transaction, event.clone());
+        }
+        if let Some(path) = Router::path_from_response(&event) {
+            let _ = self.router.pub_subroute(&path, event).await;
+        }
+        Ok(())
+    }
+
+    /// The GETs have no body, so they're authenticated through the query string
+    async fn query(&self) -> Result<Vec<(&'static str, String)>, Error> {
+        let mut query = vec![("maxev", self.config.maxev.to_string())];
+        if let Some(apisecret) = &self.apisecret {
+            query.push(("apisecret", apisecret.clone()));
+        }
+        if let Some(token_provider) = &self.token_provider {
+            query.push(("token", token_provider.token().await?));
+        }
+        Ok(query)
+    }
+
+    /// Reports an error on the event stream of a handle, or of all the handles of the session
+    async fn report(&self, sender: Option<u64>, code: u16, reason: String) {
+        let response = JaResponse {
+            janus: ResponseType::Error {
+                error: ErrorResponse { code, reason },
+            },
+            transaction: None,
+            session_id: Some(self.session_id),
+            sender,
+            jsep: None,
+        };
+        match sender {
+            Some(sender) => {
+                let path = format!("{}/{sender}", self.session_id);
+                let _ = self.router.pub_subroute(&path, response).await;
+            }
+            None => {
+                self.router
+                    .pub_nested_subroutes(&self.session_id.to_string(), response)
+                    .await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LongPoll;
+    use super::TRANSPORT_SPECIFIC_ERROR;
+    use crate::event_channel::EventChannelConfig;
+    use crate::janus_interface::LongPollConfig;
+    use crate::japrotocol::ResponseType;
+    use crate::restful::http_client::HttpClient;
+    use crate::restful::restful_interface::tests::serve;
+    use crate::token_provider::StaticTokenProvider;
+    use crate::token_provider::TokenProviderImpl;
+    use crate::websocket::pending::PendingRequests;
+    use crate::websocket::router::Router;
+    use serde_json::json;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn it_should_authenticate_through_the_query() {
+        let long_poll = LongPoll {
+            client: HttpClient::default(),
+            url: "http://localhost/janus/1".to_string(),
+            session_id: 1,
+            apisecret: Some("secret".to_string()),
+            token_provider: Some(TokenProviderImpl::new(StaticTokenProvider(
+                "token".to_string(),
+            ))),
+            router: Router::new("janus"),
+            responses: PendingRequests::new(),
+            config: LongPollConfig {
+                maxev: 3,
+                ..Default::default()
+            },
+        };
+
+        let query = long_poll.query().await.unwrap();
+
+        assert_eq!(
+            query,
+            vec![
+                ("maxev", "3".to_string()),
+                ("apisecret", "secret".to_string()),
+                ("token", "token".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_forward_the_rest_of_the_batch_past_a_bad_event() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/janus/1", listener.local_addr().unwrap());
+        let polled = Arc::new(AtomicBool::new(false));
+        tokio::spawn(serve(listener, move |_, _| {
+            let polled = polled.clone();
+            async move {
+                if polled.swap(true, Ordering::Relaxed) {
+                    std::future::pending::<()>().await;
+                }
+                json!([
+                    {"janus": "event", "session_id": 1, "sender": 2, "plugindata": {"plugin": 5}},
+                    {"janus": "event", "session_id": 1, "sender": 3, "plugindata": {"plugin": "janus.plugin.echotest", "data": {}}}
+                ])
+            }
+        }));
+
+        let mut router = Router::new("janus");
+        let mut broken = router
+            .add_subroute("1/2", EventChannelConfig::default())
+            .await;
+        let mut healthy = router
+            .add_subroute("1/3", EventChannelConfig::default())
+            .await;
+        let long_poll = LongPoll {
+            client: HttpClient::default(),
+            url,
+            session_id: 1,
+            apisecret: None,
+            token_provider: None,
+            router,
+            responses: PendingRequests::new(),
+            config: LongPollConfig::default(),
+        };
+        let task = tokio::spawn(long_poll.run());
+
+        let timeout = Duration::from_secs(5);
+        let event = tokio::time::timeout(timeout, healthy.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.sender, Some(3));
+        assert!(matches!(event.janus, ResponseType::Event(_)));
+        let report = tokio::time::timeout(timeout, broken.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            report.janus,
+            ResponseType::Error { error } if error.code == TRANSPORT_SPECIFIC_ERROR
+        ));
+        task.abort();
+    }
+}
@@ -1,3 +1,5 @@
+pub(crate) mod http_client;
+mod long_poll;
 pub mod restful_interface;
 
 pub use restful_interface::RestfulInterface;
@@ -1,33 +1,51 @@
+use super::http_client::make_client;
+use super::http_client::HttpClient;
+use super::long_poll::LongPoll;
+use crate::connection_state::ConnectionStateTracker;
+use crate::event_channel::EventChannelConfig;
+use crate::event_channel::EventReceiver;
 use crate::handle_msg::HandleMessage;
 use crate::handle_msg::HandleMessageWithJsep;
 use crate::janus_interface::ConnectionParams;
 use crate::janus_interface::JanusInterface;
+use crate::janus_interface::LongPollConfig;
 use crate::japrotocol::JaResponse;
 use crate::japrotocol::JaSuccessProtocol;
 use crate::japrotocol::ResponseType;
 use crate::japrotocol::ServerInfoRsp;
 use crate::tgenerator::GenerateTransaction;
 use crate::tgenerator::TransactionGenerator;
+use crate::token_provider::TokenProviderImpl;
+use crate::websocket::pending::PendingRequests;
+use crate::websocket::router::Router;
 use crate::Error;
 use jarust_rt::JaTask;
 use serde_json::json;
 use serde_json::Value;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc;
 use tokio::sync::Mutex;
+use tokio::time::Instant;
 
 #[derive(Debug)]
 struct Shared {
     apisecret: Option<String>,
+    token_provider: Option<TokenProviderImpl>,
+    event_channel: EventChannelConfig,
     transaction_generator: TransactionGenerator,
-    client: reqwest::Client,
+    client: HttpClient,
     url: String,
+    long_poll: LongPollConfig,
+    /// Requests waiting on their asynchronous response, delivered by the long polls
+    responses: PendingRequests<JaResponse>,
 }
 
 #[derive(Debug)]
 struct Exclusive {
-    tasks: Vec<JaTask>,
+    router: Router,
+    /// One long poll per session, its events are routed to the handles by their `sender`
+    long_polls: HashMap<u64, JaTask>,
 }
 
 #[derive(Debug)]
@@ -42,7 +60,7 @@ pub struct RestfulInterface {
 }
 
 impl RestfulInterface {
-    fn decorate_request(&self, mut request: Value) -> (Value, String) {
+    async fn decorate_request(&self, mut request: Value) -> Result<(Value, String), Error> {
         let transaction = self
             .inner
             .shared
@@ -51,8 +69,60 @@ impl RestfulInterface {
         if let Some(apisecret) = self.inner.shared.apisecret.clone() {
             request["apisecret"] = apisecret.into();
         };
+        if let Some(token_provider) = &self.inner.shared.token_provider {
+            request["token"] = token_provider.token().await?.into();
+        };
         request["transaction"] = transaction.clone().into();
-        (request, transaction)
+        Ok((request, transaction))
+    }
+
+    /// Starts polling the events of the session, unless it's already polled
+    async fn start_long_poll(&self, session_id: u64) {
+        let mut guard = self.inner.exclusive.lock().await;
+        if guard.long_polls.contains_key(&session_id) {
+            return;
+        }
+        let long_poll = LongPoll {
+            client: self.inner.shared.client.clone(),
+            url: format!("{}/{session_id}", self.inner.shared.url),
+            session_id,
+            apisecret: self.inner.shared.apisecret.clone(),
+            token_provider: self.inner.shared.token_provider.clone(),
+            router: guard.router.clone(),
+            responses: self.inner.shared.responses.clone(),
+            config: self.inner.shared.long_poll,
+        };
+        let task = jarust_rt::spawn("Long polling", long_poll.run());
+        guard.long_polls.insert(session_id, task);
+    }
+
+    /// Posts a request to a handle and returns janus reply, failing on janus errors
+    async fn post_to_handle(
+        &self,
+        session_id: u64,
+        handle_id: u64,
+        request: &Value,
+        timeout: Duration,
+    ) -> Result<JaResponse, Error> {
+        let url = &self.inner.shared.url;
+        let response = self
+            .inner
+            .shared
+            .client
+            .post(format!("{url}/{session_id}/{handle_id}"))
+            .json(request)
+            .timeout(timeout)
+            .send()
+            .await?
+            .json::<JaResponse>()
+            .await?;
+        match response.janus {
+            ResponseType::Error { error } => Err(Error::JanusError {
+                code: error.code,
+                reason: error.reason,
+            }),
+            _ => Ok(response),
+        }
     }
 }
 
@@ -64,15 +134,22 @@ impl JanusInterface for RestfulInterface {
         transaction_generator: impl GenerateTransaction,
     ) -> Result<Self, Error> {
         tracing::debug!("Creating new Restful Interface");
-        let client = reqwest::Client::new();
+        let client = make_client(&conn_params)?;
         let transaction_generator = TransactionGenerator::new(transaction_generator);
         let shared = Shared {
             apisecret: conn_params.apisecret,
+            token_provider: conn_params.token_provider,
+            event_channel: conn_params.event_channel,
             transaction_generator,
             client,
             url: format!("{}/{}", conn_params.url, conn_params.server_root),
+            long_poll: conn_params.long_poll,
+            responses: PendingRequests::new(),
+        };
+        let exclusive = Exclusive {
+            router: Router::new(&conn_params.server_root),
+            long_polls: HashMap::new(),
         };
-        let exclusive = Exclusive { tasks: Vec::new() };
         let inner = InnerResultfulInterface {
             shared,
             exclusive: Mutex::new(exclusive),
@@ -86,7 +163,7 @@ impl JanusInterface for RestfulInterface {
     async fn create(&self, timeout: Duration) -> Result<u64, Error> {
         let url = &self.inner.shared.url;
         let request = json!({"janus": "create"});
-        let (request, _) = self.decorate_request(request);
+        let (request, _) = self.decorate_request(request).await?;
 
         let response = self
             .inner
@@ -115,6 +192,8 @@ impl JanusInterface for RestfulInterface {
                 return Err(Error::UnexpectedResponse);
             }
         };
+        // The long poll keeps the session alive, hence the interface has no keep alive
+        self.start_long_poll(session_id).await;
         Ok(session_id)
     }
 
@@ -146,14 +225,15 @@ impl JanusInterface for RestfulInterface {
         &self,
         session_id: u64,
         plugin_id: String,
+        channel: Option<EventChannelConfig>,
         timeout: Duration,
-    ) -> Result<(u64, mpsc::UnboundedReceiver<JaResponse>), Error> {
+    ) -> Result<(u64, EventReceiver<JaResponse>), Error> {
         let url = &self.inner.shared.url;
         let request = json!({
             "janus": "attach",
             "plugin": plugin_id
         });
-        let (request, _) = self.decorate_request(request);
+        let (request, _) = self.decorate_request(request).await?;
 
         let response = self
             .inner
@@ -181,30 +261,18 @@ impl JanusInterface for RestfulInterface {
                 return Err(Error::UnexpectedResponse);
             }
         };
-        let (tx, rx) = mpsc::unbounded_channel();
-
-        let handle = jarust_rt::spawn("Long polling", {
-            let client = self.inner.shared.client.clone();
-            let url = url.clone();
-
-            async move {
-                loop {
-                    if let Ok(response) = client
-                        .get(format!("{url}/{session_id}?maxev=5"))
-                        .send()
-                        .await
-                    {
-                        if let Ok(res) = response.json::<Vec<JaResponse>>().await {
-                            for r in res {
-                                let _ = tx.send(r);
-                            }
-                        }
-                    };
-                }
-            }
-        });
-
-        self.inner.exclusive.lock().await.tasks.push(handle);
+        let rx = self
+            .inner
+            .exclusive
+            .lock()
+            .await
+            .router
+            .add_subroute(
+                &format!("{session_id}/{handle_id}"),
+                channel.unwrap_or(self.inner.shared.event_channel),
+            )
+            .await;
+        self.start_long_poll(session_id).await;
 
         Ok((handle_id, rx))
     }
@@ -223,7 +291,7 @@ impl JanusInterface for RestfulInterface {
         let request = json!({
             "janus": "destroy"
         });
-        let (request, _) = self.decorate_request(request);
+        let (request, _) = self.decorate_request(request).await?;
 
         self.inner
             .shared
@@ -233,6 +301,11 @@ impl JanusInterface for RestfulInterface {
             .timeout(timeout)
             .send()
             .await?;
+
+        self.inner.shared.responses.fail_session(session_id);
+        let mut guard = self.inner.exclusive.lock().await;
+        guard.long_polls.remove(&session_id);
+        guard.router.remove_subroutes(&session_id.to_string()).await;
         Ok(())
     }
 
@@ -246,7 +319,7 @@ impl JanusInterface for RestfulInterface {
             "janus": "message",
             "body": message.body
         });
-        let (request, transaction) = self.decorate_request(request);
+        let (request, transaction) = self.decorate_request(request).await?;
         self.inner
             .shared
             .client
@@ -263,7 +336,6 @@ impl JanusInterface for RestfulInterface {
         message: HandleMessage,
         timeout: Duration,
     ) -> Result<String, Error> {
-        let url = &self.inner.shared.url;
         let session_id = message.session_id;
         let handle_id = message.handle_id;
 
@@ -271,16 +343,8 @@ impl JanusInterface for RestfulInterface {
             "janus": "message",
             "body": message.body
         });
-        let (request, transaction) = self.decorate_request(request);
-        self.inner
-            .shared
-            .client
-            .post(format!("{url}/{session_id}/{handle_id}"))
-            .json(&request)
-            .timeout(timeout)
-            .send()
-            .await?
-            .json::<JaResponse>()
+        let (request, transaction) = self.decorate_request(request).await?;
+        self.post_to_handle(session_id, handle_id, &request, timeout)
             .await?;
         Ok(transaction)
     }
@@ -290,7 +354,6 @@ impl JanusInterface for RestfulInterface {
         message: HandleMessage,
         timeout: Duration,
     ) -> Result<JaResponse, Error> {
-        let url = &self.inner.shared.url;
         let session_id = message.session_id;
         let handle_id = message.handle_id;
 
@@ -298,19 +361,26 @@ impl JanusInterface for RestfulInterface {
             "janus": "message",
             "body": message.body
         });
-        let (request, _) = self.decorate_request(request);
-        let response = self
+        let (request, transaction) = self.decorate_request(request).await?;
+        // Registered before posting, the long poll may deliver the response before the POST returns
+        let pending = self
             .inner
             .shared
-            .client
-            .post(format!("{url}/{session_id}/{handle_id}"))
-            .json(&request)
-            .timeout(timeout)
-            .send()
-            .await?
-            .json::<JaResponse>()
+            .responses
+            .register(&transaction, Some(session_id));
+        let deadline = Instant::now() + timeout;
+        let response = self
+            .post_to_handle(session_id, handle_id, &request, timeout)
             .await?;
-        Ok(response)
+        match response.janus {
+            // The plugin answers asynchronously, on the long poll
+            ResponseType::Ack => {
+                pending
+                    .response(deadline.saturating_duration_since(Instant::now()))
+                    .await
+            }
+            _ => Ok(response),
+        }
     }
 
     #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
@@ -327,7 +397,7 @@ impl JanusInterface for RestfulInterface {
             "body": message.body,
             "jsep": message.jsep
         });
-        let (request, transaction) = self.decorate_request(request);
+        let (request, transaction) = self.decorate_request(request).await?;
         self.inner
             .shared
             .client
@@ -344,7 +414,6 @@ impl JanusInterface for RestfulInterface {
         message: HandleMessageWithJsep,
         timeout: Duration,
     ) -> Result<String, Error> {
-        let url = &self.inner.shared.url;
         let session_id = message.session_id;
         let handle_id = message.handle_id;
 
@@ -353,16 +422,8 @@ impl JanusInterface for RestfulInterface {
             "body": message.body,
             "jsep": message.jsep
         });
-        let (request, transaction) = self.decorate_request(request);
-        self.inner
-            .shared
-            .client
-            .post(format!("{url}/{session_id}/{handle_id}"))
-            .json(&request)
-            .timeout(timeout)
-            .send()
-            .await?
-            .json::<JaResponse>()
+        let (request, transaction) = self.decorate_request(request).await?;
+        self.post_to_handle(session_id, handle_id, &request, timeout)
             .await?;
         Ok(transaction)
     }
@@ -372,7 +433,7 @@ impl JanusInterface for RestfulInterface {
         let session_id = request.session_id;
         let handle_id = request.handle_id;
 
-        let (request, _) = self.decorate_request(request.body);
+        let (request, _) = self.decorate_request(request.body).await?;
         _ = self
             .inner
             .shared
@@ -391,25 +452,19 @@ impl JanusInterface for RestfulInterface {
         request: HandleMessage,
         timeout: Duration,
     ) -> Result<String, Error> {
-        let url = &self.inner.shared.url;
         let session_id = request.session_id;
         let handle_id = request.handle_id;
 
-        let (request, transaction) = self.decorate_request(request.body);
-        _ = self
-            .inner
-            .shared
-            .client
-            .post(format!("{url}/{session_id}/{handle_id}"))
-            .json(&request)
-            .timeout(timeout)
-            .send()
-            .await?
-            .json::<JaResponse>()
+        let (request, transaction) = self.decorate_request(request.body).await?;
+        self.post_to_handle(session_id, handle_id, &request, timeout)
             .await?;
         Ok(transaction)
     }
 
+    fn connection_state(&self) -> Option<ConnectionStateTracker> {
+        Some(self.inner.shared.client.state())
+    }
+
     fn name(&self) -> Box<str> {
         "Restful Interface".to_string().into_boxed_str()
     }
@@ -417,8 +472,407 @@ impl JanusInterface for RestfulInterface {
 
 impl Drop for Exclusive {
     fn drop(&mut self) {
-        for task in self.tasks.drain(..) {
+        for (_, task) in self.long_polls.drain() {
             task.cancel();
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::RestfulInterface;
+    use crate::backoff::Backoff;
+    use crate::connection_state::ConnectionState;
+    use crate::handle_msg::HandleMessage;
+    use crate::janus_interface::ConnectionParams;
+    use crate::janus_interface::HeadersConfig;
+    use crate::janus_interface::JanusInterface;
+    use crate::janus_interface::JanusInterfaceImpl;
+    use crate::janus_interface::LongPollConfig;
+    use crate::japrotocol::JaHandleEvent;
+    use crate::japrotocol::ResponseType;
+    use crate::tgenerator::RandomTransactionGenerator;
+    use serde_json::json;
+    use serde_json::Value;
+    use std::future::Future;
+    use std::time::Duration;
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+    use tokio::net::TcpStream;
+    use tokio::sync::watch;
+
+    const SESSION_ID: u64 = 1;
+
+    /// Reads a single HTTP request, returns its request line and json body
+    async fn read_request(stream: &mut TcpStream) -> (String, Value) {
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+        loop {
+            let size = stream.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..size]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head
+                    .lines()
+                    .find_map(|line| {
+                        line.to_lowercase()
+                            .strip_prefix("content-length: ")
+                            .map(str::to_string)
+                    })
+                    .map(|length| length.parse::<usize>().unwrap())
+                    .unwrap_or_default();
+                if body.len() >= length {
+                    let request_line = head.lines().next().unwrap().to_string();
+                    let body = serde_json::from_str(body).unwrap_or_default();
+                    return (request_line, body);
+                }
+            }
+        }
+    }
+
+    /// Serves every request on its own connection with the json returned by the handler
+    pub(crate) async fn serve<F, Fut>(listener: TcpListener, handler: F)
+    where
+        F: Fn(String, Value) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = Value> + Send,
+    {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let (request_line, body) = read_request(&mut stream).await;
+                let response = handler(request_line, body).await.to_string();
+                let _ = stream
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{response}",
+                            response.len()
+                        )
+                        .as_bytes(),
+                    )
+                    .await;
+            });
+        }
+    }
+
+    fn plugin_event(sender: u64) -> Value {
+        json!({
+            "janus": "event",
+            "session_id": SESSION_ID,
+            "sender": sender,
+            "plugindata": {"plugin": "janus.plugin.echotest", "data": {"echotest": "event", "result": "ok"}}
+        })
+    }
+
+    #[tokio::test]
+    async fn it_should_route_session_events_to_their_handles() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (attached_tx, _) = watch::channel(0u64);
+        let (polls_tx, polls_rx) = watch::channel(0u64);
+        // The events are released once both handles subscribed to their routes
+        let (release_tx, release_rx) = watch::channel(false);
+
+        tokio::spawn(serve(listener, move |request_line, body| {
+            let attached_tx = attached_tx.clone();
+            let polls_tx = polls_tx.clone();
+            let mut release_rx = release_rx.clone();
+            async move {
+                if request_line.starts_with("GET") {
+                    polls_tx.send_modify(|polls| *polls += 1);
+                    if *polls_tx.borrow() > 1 {
+                        std::future::pending::<()>().await;
+                    }
+                    release_rx.wait_for(|release| *release).await.unwrap();
+                    return json!([plugin_event(2), plugin_event(3)]);
+                }
+                assert_eq!(body["janus"], "attach");
+                attached_tx.send_modify(|attached| *attached += 1);
+                let handle_id = *attached_tx.borrow() + 1;
+                json!({"janus": "success", "transaction": body["transaction"], "session_id": SESSION_ID, "data": {"id": handle_id}})
+            }
+        }));
+
+        let conn_params = ConnectionParams {
+            url,
+            ..Default::default()
+        };
+        let interface = RestfulInterface::make_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap();
+        let timeout = Duration::from_secs(5);
+        let (first_id, mut first) = interface
+            .attach(
+                SESSION_ID,
+                "janus.plugin.echotest".to_string(),
+                None,
+                timeout,
+            )
+            .await
+            .unwrap();
+        let (second_id, mut second) = interface
+            .attach(
+                SESSION_ID,
+                "janus.plugin.echotest".to_string(),
+                None,
+                timeout,
+            )
+            .await
+            .unwrap();
+        release_tx.send_replace(true);
+
+        let first_event = tokio::time::timeout(timeout, first.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let second_event = tokio::time::timeout(timeout, second.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!((first_id, second_id), (2, 3));
+        assert_eq!(first_event.sender, Some(2));
+        assert_eq!(second_event.sender, Some(3));
+        assert!(matches!(
+            first_event.janus,
+            ResponseType::Event(JaHandleEvent::PluginEvent { .. })
+        ));
+        assert!(first.try_recv().is_err());
+        assert!(second.try_recv().is_err());
+        // The first poll and the one following it, a single poll for the whole session
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(*polls_rx.borrow(), 2);
+    }
+
+    #[tokio::test]
+    async fn it_should_report_failed_polls_and_stop_once_the_session_is_gone() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (polls_tx, _) = watch::channel(0u64);
+        let (release_tx, release_rx) = watch::channel(false);
+
+        tokio::spawn(serve(listener, move |request_line, body| {
+            let polls_tx = polls_tx.clone();
+            let mut release_rx = release_rx.clone();
+            async move {
+                if !request_line.starts_with("GET") {
+                    return json!({"janus": "success", "transaction": body["transaction"], "session_id": SESSION_ID, "data": {"id": 2}});
+                }
+                assert!(request_line.contains("maxev=3"), "{request_line}");
+                release_rx.wait_for(|release| *release).await.unwrap();
+                polls_tx.send_modify(|polls| *polls += 1);
+                let polls = *polls_tx.borrow();
+                match polls {
+                    1 => {
+                        json!({"janus": "error", "error": {"code": 490, "reason": "Unknown error"}})
+                    }
+                    2 => json!([plugin_event(2)]),
+                    3 => json!({"janus": "keepalive"}),
+                    _ => {
+                        json!({"janus": "error", "error": {"code": 458, "reason": "No such session 1"}})
+                    }
+                }
+            }
+        }));
+
+        let conn_params = ConnectionParams {
+            url,
+            long_poll: LongPollConfig {
+                maxev: 3,
+                backoff: Backoff {
+                    initial_delay: Duration::from_millis(10),
+                    max_delay: Duration::from_millis(100),
+                },
+            },
+            ..Default::default()
+        };
+        let interface = RestfulInterface::make_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap();
+        let timeout = Duration::from_secs(5);
+        let (_, mut events) = interface
+            .attach(
+                SESSION_ID,
+                "janus.plugin.echotest".to_string(),
+                None,
+                timeout,
+            )
+            .await
+            .unwrap();
+        release_tx.send_replace(true);
+
+        let mut received = Vec::new();
+        while let Some(event) = tokio::time::timeout(timeout, events.recv()).await.unwrap() {
+            received.push(event.janus);
+        }
+
+        assert_eq!(received.len(), 3);
+        assert!(matches!(
+            &received[0],
+            ResponseType::Error { error } if error.code == 490
+        ));
+        assert!(matches!(
+            received[1],
+            ResponseType::Event(JaHandleEvent::PluginEvent { .. })
+        ));
+        assert!(matches!(
+            &received[2],
+            ResponseType::Error { error } if error.code == 458
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_should_wait_for_asynchronous_responses_on_the_long_poll() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        // The transaction of the message, once janus acked it
+        let (acked_tx, acked_rx) = watch::channel(None::<Value>);
+        let (polls_tx, _) = watch::channel(0u64);
+
+        tokio::spawn(serve(listener, move |request_line, body| {
+            let acked_tx = acked_tx.clone();
+            let mut acked_rx = acked_rx.clone();
+            let polls_tx = polls_tx.clone();
+            async move {
+                if request_line.starts_with("GET") {
+                    polls_tx.send_modify(|polls| *polls += 1);
+                    if *polls_tx.borrow() > 1 {
+                        std::future::pending::<()>().await;
+                    }
+                    let transaction = acked_rx.wait_for(Option::is_some).await.unwrap().clone();
+                    let mut event = plugin_event(2);
+                    event["transaction"] = transaction.unwrap();
+                    return json!([event]);
+                }
+                match body["janus"].as_str() {
+                    Some("attach") => {
+                        json!({"janus": "success", "transaction": body["transaction"], "session_id": SESSION_ID, "data": {"id": 2}})
+                    }
+                    _ => {
+                        acked_tx.send_replace(Some(body["transaction"].clone()));
+                        json!({"janus": "ack", "transaction": body["transaction"], "session_id": SESSION_ID})
+                    }
+                }
+            }
+        }));
+
+        let conn_params = ConnectionParams {
+            url,
+            ..Default::default()
+        };
+        let interface = RestfulInterface::make_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap();
+        let timeout = Duration::from_secs(5);
+        let (handle_id, mut events) = interface
+            .attach(
+                SESSION_ID,
+                "janus.plugin.echotest".to_string(),
+                None,
+                timeout,
+            )
+            .await
+            .unwrap();
+        let interface = JanusInterfaceImpl::new(interface);
+
+        let response = interface
+            .send_msg_waiton_rsp::<Value>(
+                HandleMessage {
+                    session_id: SESSION_ID,
+                    handle_id,
+                    body: json!({"audio": true}),
+                },
+                timeout,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response, json!({"echotest": "event", "result": "ok"}));
+        // The response is still an event of the handle
+        let event = tokio::time::timeout(timeout, events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.sender, Some(handle_id));
+        assert!(event.transaction.is_some());
+    }
+
+    #[tokio::test]
+    async fn it_should_send_the_configured_headers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut head = Vec::new();
+            let mut buffer = [0; 4096];
+            while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+                let size = stream.read(&mut buffer).await.unwrap();
+                head.extend_from_slice(&buffer[..size]);
+            }
+            String::from_utf8_lossy(&head).to_lowercase()
+        });
+
+        let conn_params = ConnectionParams {
+            url,
+            headers: HeadersConfig {
+                extra: vec![
+                    ("Authorization".to_string(), "Bearer abc".to_string()),
+                    ("Cookie".to_string(), "sid=1".to_string()),
+                ],
+                user_agent: Some("jarust-test".to_string()),
+                subprotocol: None,
+            },
+            ..Default::default()
+        };
+        let interface = RestfulInterface::make_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap();
+        let _ = interface.create(Duration::from_secs(1)).await;
+
+        let head = server.await.unwrap();
+        assert!(head.contains("authorization: bearer abc"));
+        assert!(head.contains("cookie: sid=1"));
+        assert!(head.contains("user-agent: jarust-test"));
+    }
+
+    #[tokio::test]
+    async fn it_should_follow_the_server_through_the_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+
+        let conn_params = ConnectionParams {
+            url: format!("http://{address}"),
+            ..Default::default()
+        };
+        let interface = RestfulInterface::make_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap();
+        let state = interface.connection_state().unwrap();
+        let timeout = Duration::from_secs(5);
+        let attach = || {
+            interface.attach(
+                SESSION_ID,
+                "janus.plugin.echotest".to_string(),
+                None,
+                timeout,
+            )
+        };
+
+        assert!(attach().await.is_err());
+        assert_eq!(state.state(), ConnectionState::Disconnected);
+
+        let listener = TcpListener::bind(address).await.unwrap();
+        tokio::spawn(serve(listener, |request_line, body| async move {
+            if request_line.starts_with("GET") {
+                std::future::pending::<()>().await;
+            }
+            json!({"janus": "success", "transaction": body["transaction"], "session_id": SESSION_ID, "data": {"id": 2}})
+        }));
+
+        assert!(attach().await.is_ok());
+        assert_eq!(state.state(), ConnectionState::Connected);
+    }
+}
@@ -0,0 +1,231 @@
+//! Stateless signed tokens of janus, enabled by setting `token_auth_secret` in janus config,
+//! full docs: <https://janus.conf.meetecho.com/docs/auth.html>
+//!
+//! A token has the format `expiry,janus,plugin1,...,pluginN:signature`, where the signature is the base64 of the
+//! HMAC-SHA1 of everything before the colon, keyed by the shared secret.
+
+use crate::token_provider::TokenProvider;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::Hmac;
+use hmac::Mac;
+use jarust_rt::SystemTime;
+use sha1::Sha1;
+use std::fmt;
+use std::time::Duration;
+
+const REALM: &str = "janus";
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum SignedTokenError {
+    #[error("Malformed token")]
+    Malformed,
+    #[error("Unexpected realm {{ realm: {realm} }}")]
+    InvalidRealm { realm: String },
+    #[error("Invalid signature")]
+    InvalidSignature,
+    #[error("Token expired")]
+    Expired,
+}
+
+/// The claims of a signed token, the plugins it may attach to and until when.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SignedToken {
+    /// Expiry as seconds since the unix epoch
+    pub expiry: u64,
+    /// Package names of the allowed plugins, see [`crate::plugin_id`]
+    pub plugins: Vec<String>,
+}
+
+impl SignedToken {
+    pub fn new<P>(expiry: SystemTime, plugins: impl IntoIterator<Item = P>) -> Self
+    where
+        P: Into<String>,
+    {
+        Self {
+            expiry: unix_secs(expiry),
+            plugins: plugins.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Creates a token expiring after the given duration from now
+    pub fn expiring_in<P>(ttl: Duration, plugins: impl IntoIterator<Item = P>) -> Self
+    where
+        P: Into<String>,
+    {
+        Self::new(SystemTime::now() + ttl, plugins)
+    }
+
+    /// Whether the token allows attaching to the given plugin
+    pub fn allows(&self, plugin: &str) -> bool {
+        self.plugins.iter().any(|allowed| allowed == plugin)
+    }
+
+    /// Signs the token with the `token_auth_secret` of janus
+    pub fn sign(&self, secret: &str) -> String {
+        let mut data = format!("{},{REALM}", self.expiry);
+        for plugin in &self.plugins {
+            data.push(',');
+            data.push_str(plugin);
+        }
+        let signature = STANDARD.encode(mac(secret, &data).finalize().into_bytes());
+        format!("{data}:{signature}")
+    }
+
+    /// Verifies the signature and the expiry of a token, and returns its claims
+    pub fn verify(token: &str, secret: &str) -> Result<Self, SignedTokenError> {
+        let (data, signature) = token.split_once(':').ok_or(SignedTokenError::Malformed)?;
+        let signature = STANDARD
+            .decode(signature)
+            .map_err(|_| SignedTokenError::Malformed)?;
+        mac(secret, data)
+            .verify_slice(&signature)
+            .map_err(|_| SignedTokenError::InvalidSignature)?;
+
+        let mut parts = data.split(',');
+        let expiry = parts
+            .next()
+            .and_then(|expiry| expiry.parse::<u64>().ok())
+            .ok_or(SignedTokenError::Malformed)?;
+        match parts.next() {
+            Some(REALM) => {}
+            Some(realm) => {
+                return Err(SignedTokenError::InvalidRealm {
+                    realm: realm.to_string(),
+                })
+            }
+            None => return Err(SignedTokenError::Malformed),
+        }
+        if expiry < unix_secs(SystemTime::now()) {
+            return Err(SignedTokenError::Expired);
+        }
+        Ok(Self {
+            expiry,
+            plugins: parts.map(str::to_string).collect(),
+        })
+    }
+}
+
+fn mac(secret: &str, data: &str) -> Hmac<Sha1> {
+    let mut mac =
+        Hmac::<Sha1>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(data.as_bytes());
+    mac
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default()
+}
+
+/// Mints a fresh signed token on every request, for clients sharing the `token_auth_secret` of janus.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SignedTokenProvider {
+    secret: String,
+    plugins: Vec<String>,
+    ttl: Duration,
+}
+
+impl SignedTokenProvider {
+    pub fn new<P>(secret: String, plugins: impl IntoIterator<Item = P>, ttl: Duration) -> Self
+    where
+        P: Into<String>,
+    {
+        Self {
+            secret,
+            plugins: plugins.into_iter().map(Into::into).collect(),
+            ttl,
+        }
+    }
+}
+
+impl fmt::Debug for SignedTokenProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignedTokenProvider")
+            .field("secret", &"<redacted>")
+            .field("plugins", &self.plugins)
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl TokenProvider for SignedTokenProvider {
+    async fn token(&self) -> Result<String, crate::Error> {
+        let token = SignedToken::expiring_in(self.ttl, self.plugins.iter().cloned());
+        Ok(token.sign(&self.secret))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SignedToken;
+    use super::SignedTokenError;
+    use super::SignedTokenProvider;
+    use crate::plugin_id;
+    use std::time::Duration;
+    use std::time::UNIX_EPOCH;
+
+    const SECRET: &str = "janus";
+
+    #[test]
+    fn it_should_sign_like_janus() {
+        let token = SignedToken::new(
+            UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            [plugin_id::ECHO_TEST, plugin_id::VIDEO_ROOM],
+        );
+        assert_eq!(
+            token.sign(SECRET),
+            "1700000000,janus,janus.plugin.echotest,janus.plugin.videoroom:4Cd9La53ri341o8a6Lpcdd5WXeg="
+        );
+    }
+
+    #[test]
+    fn it_should_verify_a_signed_token() {
+        let token = SignedToken::expiring_in(Duration::from_secs(60), [plugin_id::ECHO_TEST]);
+        let verified = SignedToken::verify(&token.sign(SECRET), SECRET).unwrap();
+        assert_eq!(verified, token);
+        assert!(verified.allows(plugin_id::ECHO_TEST));
+        assert!(!verified.allows(plugin_id::STREAMING));
+    }
+
+    #[test]
+    fn it_should_reject_invalid_tokens() {
+        let token = SignedToken::expiring_in(Duration::from_secs(60), [plugin_id::ECHO_TEST]);
+        let signed = token.sign(SECRET);
+        assert_eq!(
+            SignedToken::verify(&signed, "other"),
+            Err(SignedTokenError::InvalidSignature)
+        );
+        assert_eq!(
+            SignedToken::verify(
+                &signed.replace(plugin_id::ECHO_TEST, plugin_id::STREAMING),
+                SECRET
+            ),
+            Err(SignedTokenError::InvalidSignature)
+        );
+        assert_eq!(
+            SignedToken::verify("1700000000,janus", SECRET),
+            Err(SignedTokenError::Malformed)
+        );
+
+        let expired = SignedToken::new(UNIX_EPOCH + Duration::from_secs(1), [plugin_id::ECHO_TEST]);
+        assert_eq!(
+            SignedToken::verify(&expired.sign(SECRET), SECRET),
+            Err(SignedTokenError::Expired)
+        );
+    }
+
+    #[test]
+    fn it_should_not_print_the_secret() {
+        let provider = SignedTokenProvider::new(
+            "top-secret".to_string(),
+            [plugin_id::ECHO_TEST],
+            Duration::from_secs(60),
+        );
+        let printed = format!("{provider:?}");
+        assert!(!printed.contains("top-secret"), "{printed}");
+        assert!(printed.contains(plugin_id::ECHO_TEST), "{printed}");
+    }
+}
@@ -1,5 +1,6 @@
 use std::fmt::Debug;
 use std::ops::Deref;
+use std::sync::Arc;
 
 /// GenerateTransaction can be provided to an interface for generating messages transactions.
 ///
@@ -8,6 +9,13 @@ pub trait GenerateTransaction: Send + Sync + Debug + 'static {
     fn generate_transaction(&self) -> String;
 }
 
+/// Shares a generator between interfaces, e.g. the transports of the fallback interface
+impl<T: GenerateTransaction + ?Sized> GenerateTransaction for Arc<T> {
+    fn generate_transaction(&self) -> String {
+        (**self).generate_transaction()
+    }
+}
+
 #[derive(Debug)]
 pub struct TransactionGenerator(Box<dyn GenerateTransaction>);
 
@@ -25,7 +33,7 @@ impl Deref for TransactionGenerator {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct RandomTransactionGenerator;
 
 impl GenerateTransaction for RandomTransactionGenerator {
@@ -41,7 +49,7 @@ impl GenerateTransaction for RandomTransactionGenerator {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct UuidTransactionGenerator;
 
 impl GenerateTransaction for UuidTransactionGenerator {
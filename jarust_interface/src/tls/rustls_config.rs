@@ -0,0 +1,200 @@
+use super::spki_sha256;
+use crate::janus_interface::TlsConfig;
+use crate::Error;
+use rustls::client::danger::HandshakeSignatureValid;
+use rustls::client::danger::ServerCertVerified;
+use rustls::client::danger::ServerCertVerifier;
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::CertificateDer;
+use rustls::pki_types::PrivateKeyDer;
+use rustls::pki_types::ServerName;
+use rustls::pki_types::UnixTime;
+use rustls::DigitallySignedStruct;
+use rustls::RootCertStore;
+use rustls::SignatureScheme;
+use std::sync::Arc;
+
+pub(crate) fn tls_error(error: impl std::fmt::Display) -> Error {
+    Error::Tls {
+        reason: error.to_string(),
+    }
+}
+
+/// Checks the pin on top of, or instead of when accepting invalid certificates, the webpki verification
+#[derive(Debug)]
+struct PinningVerifier {
+    webpki: Arc<WebPkiServerVerifier>,
+    spki_pin: Option<[u8; 32]>,
+    danger_accept_invalid_certs: bool,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if !self.danger_accept_invalid_certs {
+            self.webpki.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                now,
+            )?;
+        }
+        if let Some(pin) = &self.spki_pin {
+            if spki_sha256(end_entity).as_ref() != Some(pin) {
+                tracing::error!("Server key doesn't match the pin");
+                return Err(rustls::Error::General(
+                    "The server's public key doesn't match the pin".to_string(),
+                ));
+            }
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.webpki.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.webpki.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.webpki.supported_verify_schemes()
+    }
+}
+
+/// The rustls configuration honoring the extra roots, the client identity and the pin of the [`TlsConfig`], the pin
+/// is checked while verifying the server's certificate so a mismatch fails the handshake
+pub(crate) fn make_tls_client_config(tls: &TlsConfig) -> Result<rustls::ClientConfig, Error> {
+    let mut root_store = RootCertStore::empty();
+    let platform_certs = rustls_native_certs::load_native_certs().certs;
+    root_store.add_parsable_certificates(platform_certs);
+    for pem in &tls.root_certificates {
+        for certificate in CertificateDer::pem_slice_iter(pem) {
+            root_store
+                .add(certificate.map_err(tls_error)?)
+                .map_err(tls_error)?;
+        }
+    }
+
+    let webpki = WebPkiServerVerifier::builder(Arc::new(root_store))
+        .build()
+        .map_err(tls_error)?;
+    let builder = if tls.spki_pin.is_none() && !tls.danger_accept_invalid_certs {
+        rustls::ClientConfig::builder().with_webpki_verifier(webpki)
+    } else {
+        if tls.danger_accept_invalid_certs {
+            tracing::warn!("Accepting invalid server certificates");
+        }
+        let verifier = PinningVerifier {
+            webpki,
+            spki_pin: tls.spki_pin,
+            danger_accept_invalid_certs: tls.danger_accept_invalid_certs,
+        };
+        rustls::ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(verifier))
+    };
+
+    let client_config = match &tls.client_identity {
+        Some(identity) => {
+            let certificate_chain = CertificateDer::pem_slice_iter(&identity.certificate_chain)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(tls_error)?;
+            let private_key =
+                PrivateKeyDer::from_pem_slice(&identity.private_key).map_err(tls_error)?;
+            builder
+                .with_client_auth_cert(certificate_chain, private_key)
+                .map_err(tls_error)?
+        }
+        None => builder.with_no_client_auth(),
+    };
+    Ok(client_config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::make_tls_client_config;
+    use super::PinningVerifier;
+    use crate::janus_interface::ClientIdentity;
+    use crate::janus_interface::TlsConfig;
+    use crate::tls::tests::pin;
+    use crate::tls::tests::CERTIFICATE;
+    use crate::tls::tests::PRIVATE_KEY;
+    use rustls::client::danger::ServerCertVerifier;
+    use rustls::client::WebPkiServerVerifier;
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::CertificateDer;
+    use rustls::pki_types::ServerName;
+    use rustls::pki_types::UnixTime;
+    use rustls::RootCertStore;
+    use std::sync::Arc;
+
+    fn verifier(spki_pin: [u8; 32], danger_accept_invalid_certs: bool) -> PinningVerifier {
+        let mut root_store = RootCertStore::empty();
+        root_store
+            .add(CertificateDer::from_pem_slice(CERTIFICATE.as_bytes()).unwrap())
+            .unwrap();
+        PinningVerifier {
+            webpki: WebPkiServerVerifier::builder(Arc::new(root_store))
+                .build()
+                .unwrap(),
+            spki_pin: Some(spki_pin),
+            danger_accept_invalid_certs,
+        }
+    }
+
+    #[test]
+    fn it_should_check_the_pin_even_when_accepting_invalid_certs() {
+        let certificate = CertificateDer::from_pem_slice(CERTIFICATE.as_bytes()).unwrap();
+        let verify = |verifier: PinningVerifier, server_name: &'static str| {
+            let server_name = ServerName::try_from(server_name).unwrap();
+            verifier
+                .verify_server_cert(&certificate, &[], &server_name, &[], UnixTime::now())
+                .is_ok()
+        };
+
+        // Self-signed, so only accepted when accepting invalid certificates
+        assert!(!verify(verifier(pin(), false), "localhost"));
+        assert!(verify(verifier(pin(), true), "janus.example.com"));
+        assert!(!verify(verifier([0; 32], true), "localhost"));
+    }
+
+    #[test]
+    fn it_should_load_the_extra_roots_and_the_client_identity() {
+        let tls = TlsConfig {
+            root_certificates: vec![CERTIFICATE.as_bytes().to_vec()],
+            client_identity: Some(ClientIdentity {
+                certificate_chain: CERTIFICATE.as_bytes().to_vec(),
+                private_key: PRIVATE_KEY.as_bytes().to_vec(),
+            }),
+            ..Default::default()
+        };
+        let client_config = make_tls_client_config(&tls).unwrap();
+        assert!(client_config.client_auth_cert_resolver.has_certs());
+
+        let garbage = TlsConfig {
+            root_certificates: vec![b"-----BEGIN CERTIFICATE-----\ngarbage".to_vec()],
+            ..Default::default()
+        };
+        assert!(make_tls_client_config(&garbage).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use crate::Error;
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// TokenProvider can be provided to an interface to authenticate the requests when janus runs with `token_auth`.
+///
+/// It's asked for a token before sending each request, so it's free to cache, refresh or rotate its tokens
+/// without reconnecting.
+#[async_trait::async_trait]
+pub trait TokenProvider: Send + Sync + Debug + 'static {
+    async fn token(&self) -> Result<String, Error>;
+}
+
+/// Shared handle to a [`TokenProvider`].
+///
+/// Two handles are equal when they point to the same provider.
+#[derive(Debug, Clone)]
+pub struct TokenProviderImpl(Arc<dyn TokenProvider>);
+
+impl TokenProviderImpl {
+    pub fn new(provider: impl TokenProvider) -> Self {
+        Self(Arc::new(provider))
+    }
+
+    fn addr(&self) -> *const () {
+        Arc::as_ptr(&self.0) as *const ()
+    }
+}
+
+impl Deref for TokenProviderImpl {
+    type Target = Arc<dyn TokenProvider>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl PartialEq for TokenProviderImpl {
+    fn eq(&self, other: &Self) -> bool {
+        self.addr() == other.addr()
+    }
+}
+
+impl Eq for TokenProviderImpl {}
+
+impl PartialOrd for TokenProviderImpl {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TokenProviderImpl {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.addr().cmp(&other.addr())
+    }
+}
+
+impl Hash for TokenProviderImpl {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.addr().hash(state);
+    }
+}
+
+/// Always provides the same token
+pub struct StaticTokenProvider(pub String);
+
+impl Debug for StaticTokenProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StaticTokenProvider")
+            .field(&"<redacted>")
+            .finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl TokenProvider for StaticTokenProvider {
+    async fn token(&self) -> Result<String, Error> {
+        Ok(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StaticTokenProvider;
+    use super::TokenProviderImpl;
+
+    #[tokio::test]
+    async fn it_should_compare_providers_by_identity() {
+        let provider = TokenProviderImpl::new(StaticTokenProvider("token".to_string()));
+        let other = TokenProviderImpl::new(StaticTokenProvider("token".to_string()));
+
+        assert_eq!(provider, provider.clone());
+        assert_ne!(provider, other);
+        assert_eq!(provider.token().await.unwrap(), "token");
+    }
+
+    #[test]
+    fn it_should_not_print_the_token() {
+        let provider = StaticTokenProvider("top-secret".to_string());
+        let printed = format!("{provider:?}");
+        assert!(!printed.contains("top-secret"), "{printed}");
+    }
+}
@@ -0,0 +1,15 @@
+/// Snapshot of the transactions tracked by an interface to route the asynchronous plugin events.
+///
+/// Transactions are tracked until their response arrives or their ttl expires, the capacity of the
+/// connection is a soft limit, exceeding it doesn't evict anything.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct TransactionMetrics {
+    /// Transactions currently in flight
+    pub in_flight: usize,
+    /// Highest number of transactions in flight at once
+    pub peak_in_flight: usize,
+    /// Transactions that expired before their response arrived
+    pub expired: u64,
+    /// Transactions started while the capacity was already reached
+    pub over_capacity: u64,
+}
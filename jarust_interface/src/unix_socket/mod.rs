@@ -0,0 +1,7 @@
+mod unix_socket_client;
+
+pub mod unix_socket_interface;
+
+pub use unix_socket_client::UnixSocketClient;
+pub use unix_socket_client::UnixSocketType;
+pub use unix_socket_interface::UnixSocketInterface;
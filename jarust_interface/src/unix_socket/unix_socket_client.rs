@@ -0,0 +1,200 @@
+use crate::connection_state::ConnectionStateTracker;
+use crate::demuxed_interface::DemuxedTransport;
+use crate::demuxed_interface::TransportReceivers;
+use crate::janus_interface::ConnectionParams;
+use crate::Error;
+use bytes::Bytes;
+use jarust_rt::JaTask;
+use socket2::Domain;
+use socket2::SockAddr;
+use socket2::Socket;
+use socket2::Type;
+use std::io::Read;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::unix::AsyncFd;
+use tokio::sync::mpsc;
+
+/// Janus replies with a whole JSON message per packet, larger packets are dropped rather than read truncated.
+const MAX_PACKET_SIZE: usize = 1024 * 1024;
+
+/// The socket types supported by the janus unix sockets transport (`janus.transport.pfunix`)
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum UnixSocketType {
+    /// `SOCK_SEQPACKET`, the default socket type of the transport
+    SeqPacket,
+    /// `SOCK_DGRAM`, the client binds to a temporary path so janus can reply back
+    Datagram,
+}
+
+impl UnixSocketType {
+    /// Parses the socket type and path out of the given url.
+    ///
+    /// - `unix:///path/to/janus.sock` or `/path/to/janus.sock` for [`UnixSocketType::SeqPacket`]
+    /// - `unix+dgram:///path/to/janus.sock` for [`UnixSocketType::Datagram`]
+    pub fn from_url(url: &str) -> (Self, PathBuf) {
+        if let Some(path) = url.strip_prefix("unix+dgram://") {
+            (Self::Datagram, PathBuf::from(path))
+        } else if let Some(path) = url.strip_prefix("unix://") {
+            (Self::SeqPacket, PathBuf::from(path))
+        } else {
+            (Self::SeqPacket, PathBuf::from(url))
+        }
+    }
+}
+
+/// The unix socket under a [`UnixSocketInterface`](super::UnixSocketInterface)
+#[derive(Debug)]
+pub struct UnixSocketClient {
+    socket: Arc<AsyncFd<Socket>>,
+    local_path: Option<PathBuf>,
+    task: Option<JaTask>,
+}
+
+#[async_trait::async_trait]
+impl DemuxedTransport for UnixSocketClient {
+    const NAME: &'static str = "Unix Socket Interface";
+
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    async fn connect(
+        conn_params: &ConnectionParams,
+        _: &ConnectionStateTracker,
+    ) -> Result<(Self, TransportReceivers), Error> {
+        let (socket_type, path) = UnixSocketType::from_url(&conn_params.url);
+        tracing::debug!("Connecting to {path:?} ({socket_type:?})");
+
+        let socket = match socket_type {
+            UnixSocketType::SeqPacket => Socket::new(Domain::UNIX, Type::SEQPACKET, None)?,
+            UnixSocketType::Datagram => Socket::new(Domain::UNIX, Type::DGRAM, None)?,
+        };
+        let local_path = match socket_type {
+            UnixSocketType::SeqPacket => None,
+            UnixSocketType::Datagram => {
+                let local_path = std::env::temp_dir()
+                    .join(format!("jarust-{}.sock", uuid::Uuid::new_v4().simple()));
+                socket.bind(&SockAddr::unix(&local_path)?)?;
+                Some(local_path)
+            }
+        };
+        socket.connect(&SockAddr::unix(&path)?)?;
+        socket.set_nonblocking(true)?;
+        let socket = Arc::new(AsyncFd::new(socket)?);
+
+        let (inbound, receiver) = mpsc::unbounded_channel();
+        let task = jarust_rt::spawn(
+            "Unix socket incoming messages",
+            read_packets(socket.clone(), socket_type, MAX_PACKET_SIZE, inbound),
+        );
+
+        let client = Self {
+            socket,
+            local_path,
+            task: Some(task),
+        };
+        let receivers = TransportReceivers {
+            inbound: receiver,
+            disconnections: None,
+        };
+        Ok((client, receivers))
+    }
+
+    async fn send(&self, data: &[u8]) -> Result<(), Error> {
+        loop {
+            let mut guard = self.socket.writable().await?;
+            match guard.try_io(|socket| socket.get_ref().write(data)) {
+                Ok(result) => {
+                    result?;
+                    return Ok(());
+                }
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+/// Forwards the incoming packets until the socket is closed.
+///
+/// Packets are read with a byte to spare, a read filling the whole buffer means the packet was larger than
+/// `max_packet_size` and got truncated, so it's dropped.
+async fn read_packets(
+    socket: Arc<AsyncFd<Socket>>,
+    socket_type: UnixSocketType,
+    max_packet_size: usize,
+    inbound: mpsc::UnboundedSender<Bytes>,
+) {
+    let mut buffer = vec![0; max_packet_size + 1];
+    loop {
+        let Ok(mut guard) = socket.readable().await else {
+            break;
+        };
+        match guard.try_io(|socket| socket.get_ref().read(&mut buffer)) {
+            Ok(Ok(0)) if socket_type == UnixSocketType::SeqPacket => {
+                tracing::warn!("Connection closed");
+                break;
+            }
+            Ok(Ok(size)) if size > max_packet_size => {
+                tracing::error!("Dropping a packet larger than {max_packet_size} bytes");
+            }
+            Ok(Ok(size)) => {
+                let _ = inbound.send(Bytes::copy_from_slice(&buffer[..size]));
+            }
+            Ok(Err(what)) => {
+                tracing::error!("Failed to receive from the unix socket: {what}");
+                break;
+            }
+            Err(_would_block) => continue,
+        }
+    }
+}
+
+impl Drop for UnixSocketClient {
+    #[tracing::instrument(parent = None, level = tracing::Level::TRACE, skip(self))]
+    fn drop(&mut self) {
+        if let Some(join_handle) = self.task.take() {
+            tracing::debug!("Dropping unix socket transport");
+            join_handle.cancel();
+        }
+        if let Some(local_path) = self.local_path.take() {
+            let _ = std::fs::remove_file(local_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_packets;
+    use super::UnixSocketType;
+    use socket2::Domain;
+    use socket2::Socket;
+    use socket2::Type;
+    use std::io::Write;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::unix::AsyncFd;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn it_should_drop_the_packets_that_dont_fit() {
+        let (local, remote) = Socket::pair(Domain::UNIX, Type::SEQPACKET, None).unwrap();
+        local.set_nonblocking(true).unwrap();
+        let (inbound, mut receiver) = mpsc::unbounded_channel();
+        tokio::spawn(read_packets(
+            Arc::new(AsyncFd::new(local).unwrap()),
+            UnixSocketType::SeqPacket,
+            8,
+            inbound,
+        ));
+
+        (&remote).write_all(b"123456789").unwrap();
+        (&remote).write_all(b"12345678").unwrap();
+
+        let packet = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(packet.as_ref(), b"12345678");
+        drop(remote);
+        assert!(receiver.recv().await.is_none());
+    }
+}
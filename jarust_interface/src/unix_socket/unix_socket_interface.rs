@@ -0,0 +1,153 @@
+use super::unix_socket_client::UnixSocketClient;
+use crate::demuxed_interface::DemuxedInterface;
+
+/// Talks to janus through the janus unix sockets transport (`janus.transport.pfunix`), see
+/// [`UnixSocketType`](super::UnixSocketType) for the urls.
+pub type UnixSocketInterface = DemuxedInterface<UnixSocketClient>;
+
+#[cfg(test)]
+mod tests {
+    use super::UnixSocketInterface;
+    use crate::janus_interface::ConnectionParams;
+    use crate::janus_interface::JanusInterface;
+    use crate::japrotocol::GenericEvent;
+    use crate::japrotocol::JaHandleEvent;
+    use crate::japrotocol::ResponseType;
+    use crate::tgenerator::RandomTransactionGenerator;
+    use serde_json::json;
+    use serde_json::Value;
+    use socket2::Domain;
+    use socket2::SockAddr;
+    use socket2::Socket;
+    use socket2::Type;
+    use std::io::Read;
+    use std::io::Write;
+    use std::os::unix::net::UnixDatagram;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    const SESSION_ID: u64 = 1;
+    const HANDLE_ID: u64 = 2;
+
+    fn socket_path() -> PathBuf {
+        std::env::temp_dir().join(format!("janus-{}.sock", uuid::Uuid::new_v4().simple()))
+    }
+
+    fn parse(packet: &[u8]) -> Value {
+        serde_json::from_slice(packet).unwrap()
+    }
+
+    fn create_rsp(request: &Value) -> Value {
+        json!({"janus": "success", "transaction": request["transaction"], "data": {"id": SESSION_ID}})
+    }
+
+    fn attach_rsp(request: &Value) -> Value {
+        json!({"janus": "success", "transaction": request["transaction"], "session_id": SESSION_ID, "data": {"id": HANDLE_ID}})
+    }
+
+    fn ack(request: &Value) -> Value {
+        json!({"janus": "ack", "transaction": request["transaction"], "session_id": SESSION_ID})
+    }
+
+    fn webrtcup() -> Value {
+        json!({"janus": "webrtcup", "session_id": SESSION_ID, "sender": HANDLE_ID})
+    }
+
+    async fn create_and_attach(url: String) {
+        let conn_params = ConnectionParams {
+            url,
+            ..Default::default()
+        };
+        let interface =
+            UnixSocketInterface::make_interface(conn_params, RandomTransactionGenerator)
+                .await
+                .unwrap();
+        let timeout = Duration::from_secs(5);
+        let session_id = interface.create(timeout).await.unwrap();
+        assert_eq!(session_id, SESSION_ID);
+        let (handle_id, mut events) = interface
+            .attach(
+                session_id,
+                "janus.plugin.echotest".to_string(),
+                None,
+                timeout,
+            )
+            .await
+            .unwrap();
+        assert_eq!(handle_id, HANDLE_ID);
+        interface.keep_alive(session_id, timeout).await.unwrap();
+        let event = tokio::time::timeout(timeout, events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            event.janus,
+            ResponseType::Event(JaHandleEvent::GenericEvent(GenericEvent::WebrtcUp))
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_talk_to_janus_over_seqpacket() {
+        let path = socket_path();
+        let listener = Socket::new(Domain::UNIX, Type::SEQPACKET, None).unwrap();
+        listener.bind(&SockAddr::unix(&path).unwrap()).unwrap();
+        listener.listen(1).unwrap();
+
+        std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut buffer = vec![0; 8192];
+            let size = socket.read(&mut buffer).unwrap();
+            let request = parse(&buffer[..size]);
+            socket
+                .write_all(create_rsp(&request).to_string().as_bytes())
+                .unwrap();
+            let size = socket.read(&mut buffer).unwrap();
+            let request = parse(&buffer[..size]);
+            socket
+                .write_all(attach_rsp(&request).to_string().as_bytes())
+                .unwrap();
+            let size = socket.read(&mut buffer).unwrap();
+            let request = parse(&buffer[..size]);
+            socket
+                .write_all(ack(&request).to_string().as_bytes())
+                .unwrap();
+            socket.write_all(webrtcup().to_string().as_bytes()).unwrap();
+            let _ = socket.read(&mut buffer);
+        });
+
+        create_and_attach(format!("unix://{}", path.display())).await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn it_should_talk_to_janus_over_datagrams() {
+        let path = socket_path();
+        let server = UnixDatagram::bind(&path).unwrap();
+
+        std::thread::spawn(move || {
+            let mut buffer = vec![0; 8192];
+            let (size, client) = server.recv_from(&mut buffer).unwrap();
+            let client = client.as_pathname().unwrap().to_path_buf();
+            let request = parse(&buffer[..size]);
+            server
+                .send_to(create_rsp(&request).to_string().as_bytes(), &client)
+                .unwrap();
+            let (size, _) = server.recv_from(&mut buffer).unwrap();
+            let request = parse(&buffer[..size]);
+            server
+                .send_to(attach_rsp(&request).to_string().as_bytes(), &client)
+                .unwrap();
+            let (size, _) = server.recv_from(&mut buffer).unwrap();
+            let request = parse(&buffer[..size]);
+            server
+                .send_to(ack(&request).to_string().as_bytes(), &client)
+                .unwrap();
+            server
+                .send_to(webrtcup().to_string().as_bytes(), &client)
+                .unwrap();
+        });
+
+        create_and_attach(format!("unix+dgram://{}", path.display())).await;
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -12,4 +12,64 @@ mod adapter;
 #[path = "rustls_adapter.rs"]
 mod adapter;
 
-pub use adapter::connect_async;
+mod proxy;
+
+use crate::janus_interface::ProxyConfig;
+use crate::janus_interface::TlsConfig;
+use crate::websocket::deflate::Deflate;
+use crate::websocket::deflate::Deflater;
+use crate::websocket::deflate::InflatingStream;
+use crate::Error;
+use proxy::Target;
+use tokio::net::TcpStream;
+use tokio_tungstenite::client_async_with_config;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::http::header::SEC_WEBSOCKET_EXTENSIONS;
+use tokio_tungstenite::MaybeTlsStream;
+use tokio_tungstenite::WebSocketStream;
+
+/// The socket under the websocket protocol layer
+pub(crate) type Socket = InflatingStream<MaybeTlsStream<TcpStream>>;
+
+/// How the sockets of a websocket client are opened
+#[derive(Clone, Default, Debug)]
+pub(crate) struct ConnectConfig {
+    pub(crate) tls: TlsConfig,
+    pub(crate) proxy: ProxyConfig,
+    pub(crate) deflate: Option<Deflate>,
+}
+
+/// Opens a socket over the proxy tunnel (if any), secures it for `wss` urls, then performs the websocket handshake.
+///
+/// Returns the compressor of the messages sent along with the socket, if `permessage-deflate` was negotiated.
+pub(crate) async fn connect_async(
+    mut request: Request,
+    config: &ConnectConfig,
+) -> Result<(WebSocketStream<Socket>, Option<Deflater>), Error> {
+    let target = Target::from_uri(request.uri())?;
+    if let Some(deflate) = &config.deflate {
+        request
+            .headers_mut()
+            .insert(SEC_WEBSOCKET_EXTENSIONS, deflate.offer().parse()?);
+    }
+    let socket = proxy::connect(&target, &config.proxy).await?;
+    let stream = adapter::secure(socket, &target, &config.tls).await?;
+    let counters = config
+        .deflate
+        .as_ref()
+        .map(|deflate| deflate.counters.clone());
+    let stream = InflatingStream::new(stream, counters);
+
+    let (stream, response) = client_async_with_config(request, stream, None).await?;
+    let deflater = config.deflate.as_ref().and_then(|deflate| {
+        let extensions = response
+            .headers()
+            .get_all(SEC_WEBSOCKET_EXTENSIONS)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+        deflate.deflater(&extensions)
+    });
+    Ok((stream, deflater))
+}
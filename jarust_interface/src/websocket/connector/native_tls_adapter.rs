@@ -1,14 +1,130 @@
+use super::proxy::Target;
+use crate::janus_interface::TlsConfig;
+use crate::tls::check_pin;
 use crate::Error;
+use native_tls::Certificate;
+use native_tls::Identity;
 use tokio::net::TcpStream;
-use tokio_tungstenite::connect_async_with_config;
-use tokio_tungstenite::tungstenite::handshake::client::Request;
 use tokio_tungstenite::MaybeTlsStream;
-use tokio_tungstenite::WebSocketStream;
 
-pub async fn connect_async(
-    request: Request,
-) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, Error> {
+fn tls_error(error: native_tls::Error) -> Error {
+    Error::Tls {
+        reason: error.to_string(),
+    }
+}
+
+fn make_tls_connector(tls: &TlsConfig) -> Result<tokio_native_tls::TlsConnector, Error> {
+    let mut builder = native_tls::TlsConnector::builder();
+    for pem in &tls.root_certificates {
+        builder.add_root_certificate(Certificate::from_pem(pem).map_err(tls_error)?);
+    }
+    if let Some(identity) = &tls.client_identity {
+        let identity = Identity::from_pkcs8(&identity.certificate_chain, &identity.private_key)
+            .map_err(tls_error)?;
+        builder.identity(identity);
+    }
+    if tls.danger_accept_invalid_certs {
+        tracing::warn!("Accepting invalid server certificates");
+        builder
+            .danger_accept_invalid_certs(true)
+            .danger_accept_invalid_hostnames(true);
+    }
+    let connector = builder.build().map_err(tls_error)?;
+    Ok(connector.into())
+}
+
+/// Performs the TLS handshake of `wss` urls, the pin is checked before the handshake request (and its headers)
+/// is sent
+pub(crate) async fn secure(
+    socket: TcpStream,
+    target: &Target,
+    tls: &TlsConfig,
+) -> Result<MaybeTlsStream<TcpStream>, Error> {
     tracing::trace!("Using native-tls");
-    let (stream, ..) = connect_async_with_config(request, None, false).await?;
-    Ok(stream)
+    if !target.secure {
+        return Ok(MaybeTlsStream::Plain(socket));
+    }
+    let connector = make_tls_connector(tls)?;
+    let stream = connector
+        .connect(&target.host, socket)
+        .await
+        .map_err(tls_error)?;
+    let certificate = stream
+        .get_ref()
+        .peer_certificate()
+        .and_then(|certificate| certificate.map(|it| it.to_der()).transpose())
+        .map_err(tls_error)?;
+    check_pin(tls.spki_pin.as_ref(), certificate.as_deref())?;
+    Ok(MaybeTlsStream::NativeTls(stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::janus_interface::ProxyConfig;
+    use crate::janus_interface::TlsConfig;
+    use crate::tls::tests::pin;
+    use crate::tls::tests::CERTIFICATE;
+    use crate::tls::tests::PRIVATE_KEY;
+    use crate::websocket::connector::connect_async;
+    use crate::websocket::connector::ConnectConfig;
+    use crate::Error;
+    use native_tls::Identity;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    /// Accepts websocket connections over TLS, with the test certificate
+    async fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("wss://localhost:{}", listener.local_addr().unwrap().port());
+        let identity =
+            Identity::from_pkcs8(CERTIFICATE.as_bytes(), PRIVATE_KEY.as_bytes()).unwrap();
+        let acceptor =
+            tokio_native_tls::TlsAcceptor::from(native_tls::TlsAcceptor::new(identity).unwrap());
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    if let Ok(stream) = acceptor.accept(socket).await {
+                        let _ = tokio_tungstenite::accept_async(stream).await;
+                    }
+                });
+            }
+        });
+        url
+    }
+
+    async fn connect(url: &str, tls: TlsConfig) -> Result<(), Error> {
+        let config = ConnectConfig {
+            tls,
+            proxy: ProxyConfig::Direct,
+            deflate: None,
+        };
+        let request = url.into_client_request().unwrap();
+        connect_async(request, &config).await.map(|_| ())
+    }
+
+    #[tokio::test]
+    async fn it_should_trust_the_extra_roots_and_check_the_pin() {
+        let url = serve().await;
+        let tls = TlsConfig {
+            root_certificates: vec![CERTIFICATE.as_bytes().to_vec()],
+            ..Default::default()
+        };
+
+        let pinned = TlsConfig {
+            spki_pin: Some(pin()),
+            ..tls.clone()
+        };
+        assert!(connect(&url, pinned).await.is_ok());
+
+        let mispinned = TlsConfig {
+            spki_pin: Some([0; 32]),
+            ..tls
+        };
+        let result = connect(&url, mispinned).await;
+        assert!(matches!(result, Err(Error::Tls { .. })));
+
+        let result = connect(&url, TlsConfig::default()).await;
+        assert!(matches!(result, Err(Error::Tls { .. })));
+    }
 }
@@ -0,0 +1,243 @@
+use crate::janus_interface::ProxyConfig;
+use crate::Error;
+use hyper_util::client::legacy::connect::proxy::SocksV5;
+use hyper_util::client::legacy::connect::proxy::Tunnel;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::proxy::matcher::Intercept;
+use hyper_util::client::proxy::matcher::Matcher;
+use std::net::IpAddr;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::http::Uri;
+use tower_service::Service;
+
+/// Where a websocket url points to
+pub(crate) struct Target {
+    /// The host, without the brackets of an ipv6 address
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    /// Whether the url is a `wss` one
+    pub(crate) secure: bool,
+}
+
+impl Target {
+    pub(crate) fn from_uri(uri: &Uri) -> Result<Self, Error> {
+        let secure = match uri.scheme_str() {
+            Some("wss") => true,
+            Some("ws") => false,
+            _ => {
+                return Err(Error::InvalidUrl {
+                    reason: format!("Unsupported scheme in {uri}"),
+                })
+            }
+        };
+        let Some(host) = uri.host() else {
+            return Err(Error::InvalidUrl {
+                reason: format!("No host in {uri}"),
+            });
+        };
+        let host = host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let port = uri.port_u16().unwrap_or(if secure { 443 } else { 80 });
+        Ok(Self { host, port, secure })
+    }
+
+    /// The `http` or `https` counterpart of the websocket url, which the proxy rules are written for
+    fn http_uri(&self) -> Result<Uri, Error> {
+        let scheme = if self.secure { "https" } else { "http" };
+        let uri = match self.host.parse::<IpAddr>() {
+            Ok(IpAddr::V6(_)) => format!("{scheme}://[{}]:{}", self.host, self.port),
+            _ => format!("{scheme}://{}:{}", self.host, self.port),
+        };
+        uri.parse().map_err(|_| Error::InvalidUrl {
+            reason: format!("Invalid host {}", self.host),
+        })
+    }
+}
+
+/// Opens a tcp connection to the target, tunneled through the configured proxy unless it's bypassed
+pub(crate) async fn connect(target: &Target, proxy: &ProxyConfig) -> Result<TcpStream, Error> {
+    let uri = target.http_uri()?;
+    let Some(intercept) = intercept(&uri, proxy)? else {
+        return Ok(TcpStream::connect((target.host.as_str(), target.port)).await?);
+    };
+    let proxy = intercept.uri().clone();
+    tracing::debug!("Connecting through the proxy {proxy}");
+    let mut connector = HttpConnector::new();
+    connector.enforce_http(false);
+    let scheme = proxy.scheme_str().unwrap_or_default().to_string();
+    let stream = match scheme.as_str() {
+        "http" => {
+            let mut tunnel = Tunnel::new(proxy, connector);
+            if let Some(auth) = intercept.basic_auth() {
+                tunnel = tunnel.with_auth(auth.clone());
+            }
+            tunnel
+                .call(uri)
+                .await
+                .map_err(|what| proxy_error(what.to_string()))?
+        }
+        "socks5" | "socks5h" => {
+            let mut socks = SocksV5::new(proxy, connector).local_dns(scheme == "socks5");
+            if let Some((username, password)) = intercept.raw_auth() {
+                socks = socks.with_auth(username.to_string(), password.to_string());
+            }
+            socks
+                .call(uri)
+                .await
+                .map_err(|what| proxy_error(what.to_string()))?
+        }
+        _ => return Err(proxy_error(format!("Unsupported proxy scheme {scheme}"))),
+    };
+    Ok(stream.into_inner())
+}
+
+/// The proxy to go through, if any.
+///
+/// The rules are matched by the same matcher reqwest uses for the restful interfaces, so `NO_PROXY` reads the same
+/// for both, IP addresses and CIDR ranges included, and the credentials of the proxy url are percent-decoded.
+fn intercept(uri: &Uri, proxy: &ProxyConfig) -> Result<Option<Intercept>, Error> {
+    let matcher = match proxy {
+        ProxyConfig::Direct => return Ok(None),
+        ProxyConfig::Environment => Matcher::from_env(),
+        ProxyConfig::Custom { url, no_proxy } => {
+            // The matcher ignores the urls it can't use, which would silently connect directly
+            let scheme = url
+                .parse::<Uri>()
+                .map_err(|what| proxy_error(format!("Invalid proxy url {url}: {what}")))?
+                .scheme_str()
+                .map(str::to_string);
+            if !matches!(scheme.as_deref(), Some("http" | "socks5" | "socks5h")) {
+                return Err(proxy_error(format!("Unsupported proxy url {url}")));
+            }
+            Matcher::builder()
+                .all(url.as_str())
+                .no(no_proxy.as_deref().unwrap_or_default())
+                .build()
+        }
+    };
+    Ok(matcher.intercept(uri))
+}
+
+fn proxy_error(reason: String) -> Error {
+    tracing::error!("Proxy error: {reason}");
+    Error::Proxy { reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::connect;
+    use super::intercept;
+    use super::Target;
+    use crate::janus_interface::ProxyConfig;
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    fn target() -> Target {
+        Target {
+            host: "janus.internal".to_string(),
+            port: 8188,
+            secure: false,
+        }
+    }
+
+    #[test]
+    fn it_should_bypass_the_listed_hosts() {
+        let bypasses = |no_proxy: &str, uri: &str| {
+            let proxy = ProxyConfig::Custom {
+                url: "http://proxy:3128".to_string(),
+                no_proxy: Some(no_proxy.to_string()),
+            };
+            intercept(&uri.parse().unwrap(), &proxy).unwrap().is_none()
+        };
+        assert!(bypasses(
+            "localhost, .internal",
+            "http://janus.internal:8188"
+        ));
+        assert!(bypasses("janus.internal", "http://janus.internal:8188"));
+        assert!(bypasses("*", "http://janus.internal:8188"));
+        assert!(bypasses("10.0.0.0/8", "http://10.1.2.3:8188"));
+        assert!(bypasses("::1", "http://[::1]:8188"));
+        assert!(!bypasses("nal", "http://janus.internal:8188"));
+        assert!(!bypasses("10.0.0.0/8", "http://11.1.2.3:8188"));
+        assert!(!bypasses("", "http://janus.internal:8188"));
+    }
+
+    #[test]
+    fn it_should_refuse_the_unsupported_proxies() {
+        let proxy = ProxyConfig::Custom {
+            url: "ftp://proxy:21".to_string(),
+            no_proxy: None,
+        };
+        let uri = "http://janus.internal:8188".parse().unwrap();
+        assert!(intercept(&uri, &proxy).is_err());
+    }
+
+    #[tokio::test]
+    async fn it_should_tunnel_through_an_http_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = ProxyConfig::Custom {
+            url: format!("http://us%40er:p%3Ass@{}", listener.local_addr().unwrap()),
+            no_proxy: None,
+        };
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(socket.read_u8().await.unwrap());
+            }
+            socket
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                .await
+                .unwrap();
+            let mut tunneled = [0; 4];
+            socket.read_exact(&mut tunneled).await.unwrap();
+            socket.write_all(b"hello").await.unwrap();
+            (String::from_utf8(request).unwrap(), tunneled)
+        });
+
+        let mut stream = connect(&target(), &proxy).await.unwrap();
+        stream.write_all(b"ping").await.unwrap();
+        let mut tunneled = [0; 5];
+        stream.read_exact(&mut tunneled).await.unwrap();
+        assert_eq!(&tunneled, b"hello");
+
+        let (request, tunneled) = server.await.unwrap();
+        assert_eq!(&tunneled, b"ping");
+        assert!(request.starts_with("CONNECT janus.internal:8188 HTTP/1.1\r\n"));
+        // The credentials are percent-decoded, "us@er:p:ss"
+        assert!(request.contains("Proxy-Authorization: Basic dXNAZXI6cDpzcw==\r\n"));
+    }
+
+    #[tokio::test]
+    async fn it_should_tunnel_through_a_socks5_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = ProxyConfig::Custom {
+            url: format!("socks5h://{}", listener.local_addr().unwrap()),
+            no_proxy: Some("localhost".to_string()),
+        };
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut greeting = [0; 3];
+            socket.read_exact(&mut greeting).await.unwrap();
+            socket.write_all(&[5, 0]).await.unwrap();
+            let mut request = [0; 5 + 14 + 2];
+            socket.read_exact(&mut request).await.unwrap();
+            socket
+                .write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0x1f, 0x90])
+                .await
+                .unwrap();
+            (greeting, request)
+        });
+
+        connect(&target(), &proxy).await.unwrap();
+
+        let (greeting, request) = server.await.unwrap();
+        assert_eq!(greeting, [5, 1, 0]);
+        assert_eq!(request[..5], [5, 1, 0, 3, 14]);
+        assert_eq!(&request[5..19], b"janus.internal");
+        assert_eq!(request[19..], 8188u16.to_be_bytes());
+    }
+}
@@ -1,28 +1,28 @@
+use super::proxy::Target;
+use crate::janus_interface::TlsConfig;
+use crate::tls::make_tls_client_config;
+use crate::tls::tls_error;
 use crate::Error;
-use rustls::RootCertStore;
+use rustls::pki_types::ServerName;
 use std::sync::Arc;
 use tokio::net::TcpStream;
-use tokio_tungstenite::connect_async_tls_with_config;
-use tokio_tungstenite::tungstenite::handshake::client::Request;
-use tokio_tungstenite::Connector;
+use tokio_rustls::TlsConnector;
 use tokio_tungstenite::MaybeTlsStream;
-use tokio_tungstenite::WebSocketStream;
 
-fn make_tls_client_config() -> Result<Arc<rustls::ClientConfig>, Error> {
-    let mut root_store = RootCertStore::empty();
-    let platform_certs = rustls_native_certs::load_native_certs().certs;
-    root_store.add_parsable_certificates(platform_certs);
-    let client_config = rustls::ClientConfig::builder()
-        .with_root_certificates(root_store)
-        .with_no_client_auth();
-    Ok(Arc::new(client_config))
-}
-
-pub async fn connect_async(
-    request: Request,
-) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, Error> {
+/// Performs the TLS handshake of `wss` urls, the pin is checked while verifying the server's certificate, before
+/// the handshake request is sent
+pub(crate) async fn secure(
+    socket: TcpStream,
+    target: &Target,
+    tls: &TlsConfig,
+) -> Result<MaybeTlsStream<TcpStream>, Error> {
     tracing::trace!("Using rustls");
-    let connector = Connector::Rustls(make_tls_client_config()?);
-    let (stream, ..) = connect_async_tls_with_config(request, None, true, Some(connector)).await?;
-    Ok(stream)
+    socket.set_nodelay(true)?;
+    if !target.secure {
+        return Ok(MaybeTlsStream::Plain(socket));
+    }
+    let connector = TlsConnector::from(Arc::new(make_tls_client_config(tls)?));
+    let domain = ServerName::try_from(target.host.clone()).map_err(tls_error)?;
+    let stream = connector.connect(domain, socket).await?;
+    Ok(MaybeTlsStream::Rustls(stream))
 }
@@ -0,0 +1,694 @@
+//! `permessage-deflate` (RFC 7692), which the websocket protocol layer doesn't implement.
+//!
+//! The compressed messages received are inflated by [`InflatingStream`], right above the socket, so the protocol
+//! layer only ever sees plain frames. Only the compressed frames are buffered, the other ones are handed over as
+//! their bytes come. The messages sent are compressed by the writer task, with a [`Deflater`].
+
+use crate::compression_metrics::CompressionMetrics;
+use crate::janus_interface::DeflateConfig;
+use flate2::Compress;
+use flate2::Compression;
+use flate2::Decompress;
+use flate2::FlushCompress;
+use flate2::FlushDecompress;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::ready;
+use std::task::Context;
+use std::task::Poll;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::ReadBuf;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::Data;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::OpCode;
+use tokio_tungstenite::tungstenite::protocol::frame::Frame;
+use tokio_tungstenite::tungstenite::Message;
+
+const EXTENSION: &str = "permessage-deflate";
+/// Trailer of a deflate block flushed with `Z_SYNC_FLUSH`, stripped from the messages (RFC 7692 section 7.2.1)
+const TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+/// Largest frame and inflated message, as large as the protocol layer accepts
+const MAX_MESSAGE_SIZE: usize = 64 << 20;
+/// Largest handshake scanned for the negotiated extension
+const MAX_HANDSHAKE_SIZE: usize = 16 << 10;
+
+/// The `permessage-deflate` settings of a client, along with its counters which outlive its connections
+#[derive(Clone, Debug)]
+pub(crate) struct Deflate {
+    pub(crate) config: DeflateConfig,
+    pub(crate) counters: CompressionCounters,
+}
+
+impl Deflate {
+    pub(crate) fn new(config: DeflateConfig) -> Self {
+        Self {
+            config,
+            counters: CompressionCounters::default(),
+        }
+    }
+
+    /// The `Sec-WebSocket-Extensions` of the handshake request
+    pub(crate) fn offer(&self) -> String {
+        let mut offer = EXTENSION.to_string();
+        let server_max_window_bits = self.config.server_max_window_bits.clamp(9, 15);
+        if server_max_window_bits < 15 {
+            offer.push_str(&format!(
+                "; server_max_window_bits={server_max_window_bits}"
+            ));
+        }
+        if self.config.server_no_context_takeover {
+            offer.push_str("; server_no_context_takeover");
+        }
+        if self.config.client_no_context_takeover {
+            offer.push_str("; client_no_context_takeover");
+        }
+        offer
+    }
+
+    /// The compressor of a freshly opened socket, unless the server declined the extension.
+    ///
+    /// The window used to compress can't be shrunk, so the messages are sent uncompressed in the unlikely
+    /// case the server asks for a smaller one, which it shouldn't do as `client_max_window_bits` isn't offered.
+    pub(crate) fn deflater(&self, extensions: &str) -> Option<Deflater> {
+        let negotiated = negotiate(extensions)?;
+        if negotiated
+            .client_max_window_bits
+            .is_some_and(|bits| bits < 15)
+        {
+            tracing::debug!("Server asked for a smaller window, sending uncompressed messages");
+            return None;
+        }
+        Some(Deflater {
+            compress: Compress::new(Compression::new(self.config.level.min(9)), false),
+            no_context_takeover: negotiated.client_no_context_takeover
+                || self.config.client_no_context_takeover,
+            counters: self.counters.clone(),
+        })
+    }
+}
+
+/// Bytes in and out of the compression, across the connections of a client
+#[derive(Clone, Default, Debug)]
+pub(crate) struct CompressionCounters {
+    inner: Arc<Counters>,
+}
+
+#[derive(Default, Debug)]
+struct Counters {
+    received_compressed: AtomicU64,
+    received_inflated: AtomicU64,
+    sent_uncompressed: AtomicU64,
+    sent_compressed: AtomicU64,
+}
+
+impl CompressionCounters {
+    pub(crate) fn metrics(&self) -> CompressionMetrics {
+        CompressionMetrics {
+            received_compressed: self.inner.received_compressed.load(Ordering::Relaxed),
+            received_inflated: self.inner.received_inflated.load(Ordering::Relaxed),
+            sent_uncompressed: self.inner.sent_uncompressed.load(Ordering::Relaxed),
+            sent_compressed: self.inner.sent_compressed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The parameters of the extension the server agreed on
+#[derive(Debug, PartialEq)]
+struct Negotiated {
+    client_no_context_takeover: bool,
+    client_max_window_bits: Option<u8>,
+}
+
+/// Looks for `permessage-deflate` among the extensions the server agreed on
+fn negotiate(extensions: &str) -> Option<Negotiated> {
+    extensions.split(',').find_map(|extension| {
+        let mut params = extension.split(';').map(str::trim);
+        if params.next()? != EXTENSION {
+            return None;
+        }
+        let mut negotiated = Negotiated {
+            client_no_context_takeover: false,
+            client_max_window_bits: None,
+        };
+        for param in params {
+            match param.split_once('=') {
+                Some(("client_max_window_bits", bits)) => {
+                    negotiated.client_max_window_bits = bits.trim_matches('"').parse().ok();
+                }
+                None if param == "client_no_context_takeover" => {
+                    negotiated.client_no_context_takeover = true;
+                }
+                _ => {}
+            }
+        }
+        Some(negotiated)
+    })
+}
+
+/// Compresses the messages sent over a socket
+pub(crate) struct Deflater {
+    compress: Compress,
+    no_context_takeover: bool,
+    counters: CompressionCounters,
+}
+
+impl std::fmt::Debug for Deflater {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Deflater")
+            .field("no_context_takeover", &self.no_context_takeover)
+            .finish()
+    }
+}
+
+impl Deflater {
+    /// Compresses a data message into a single frame, other messages are left as they are
+    pub(crate) fn deflate(&mut self, message: Message) -> Message {
+        let (data, opcode) = match message {
+            Message::Binary(data) => (data, Data::Binary),
+            Message::Text(text) => (text.into(), Data::Text),
+            message => return message,
+        };
+        let compressed = match self.compress(&data) {
+            Ok(compressed) => compressed,
+            Err(what) => {
+                tracing::warn!("Failed to compress, sending uncompressed: {what}");
+                self.compress.reset();
+                return Message::Frame(Frame::message(data, OpCode::Data(opcode), true));
+            }
+        };
+        self.counters
+            .inner
+            .sent_uncompressed
+            .fetch_add(data.len() as u64, Ordering::Relaxed);
+        self.counters
+            .inner
+            .sent_compressed
+            .fetch_add(compressed.len() as u64, Ordering::Relaxed);
+        let mut frame = Frame::message(compressed, OpCode::Data(opcode), true);
+        frame.header_mut().rsv1 = true;
+        Message::Frame(frame)
+    }
+
+    fn compress(&mut self, data: &[u8]) -> Result<Vec<u8>, flate2::CompressError> {
+        let mut compressed = Vec::with_capacity(data.len() / 2 + 64);
+        let start = self.compress.total_in();
+        loop {
+            let consumed = (self.compress.total_in() - start) as usize;
+            self.compress
+                .compress_vec(&data[consumed..], &mut compressed, FlushCompress::Sync)?;
+            let consumed = (self.compress.total_in() - start) as usize;
+            // Room left once everything's consumed means the flush is complete
+            if consumed == data.len() && compressed.len() < compressed.capacity() {
+                break;
+            }
+            compressed.reserve(compressed.capacity().max(64));
+        }
+        if compressed.ends_with(&TRAILER) {
+            compressed.truncate(compressed.len() - TRAILER.len());
+        }
+        if compressed.is_empty() {
+            // An empty stored block, RFC 7692 section 7.2.3.6
+            compressed.push(0x00);
+        }
+        if self.no_context_takeover {
+            self.compress.reset();
+        }
+        Ok(compressed)
+    }
+}
+
+enum Mode {
+    /// Scanning the handshake for the negotiated extension
+    Handshake { head: Vec<u8> },
+    /// Inflating the compressed messages
+    Frames,
+    /// Handing the bytes over as they come, the extension wasn't negotiated
+    Passthrough,
+}
+
+/// A compressed message being reassembled out of its frames
+struct Compressed {
+    opcode: u8,
+    /// The mask of its first frame, to mask the plain frame alike
+    mask: Option<[u8; 4]>,
+    payload: Vec<u8>,
+}
+
+/// Inflates the compressed messages read from a socket, and rewrites them as plain frames.
+///
+/// It scans the handshake to learn whether the extension was agreed on, then either inflates the messages with
+/// their `RSV1` bit set or steps aside. The plain frames keep the mask of the compressed ones, if any, so it works
+/// on either end of the socket.
+pub(crate) struct InflatingStream<S> {
+    inner: S,
+    mode: Mode,
+    /// Bytes read from the socket, not processed yet
+    raw: Vec<u8>,
+    /// Bytes ready to be read
+    ready: Vec<u8>,
+    read: usize,
+    /// What's left of the uncompressed frame being handed over
+    passthrough: usize,
+    message: Option<Compressed>,
+    decompress: Decompress,
+    counters: Option<CompressionCounters>,
+}
+
+impl<S> InflatingStream<S> {
+    /// Steps aside unless there are counters, i.e. the extension is offered
+    pub(crate) fn new(inner: S, counters: Option<CompressionCounters>) -> Self {
+        let mode = match counters {
+            Some(_) => Mode::Handshake { head: Vec::new() },
+            None => Mode::Passthrough,
+        };
+        Self {
+            inner,
+            mode,
+            raw: Vec::new(),
+            ready: Vec::new(),
+            read: 0,
+            passthrough: 0,
+            message: None,
+            decompress: Decompress::new(false),
+            counters,
+        }
+    }
+
+    fn scan_handshake(&mut self, bytes: &[u8]) {
+        let Mode::Handshake { head } = &mut self.mode else {
+            self.raw.extend_from_slice(bytes);
+            return;
+        };
+        for (i, &byte) in bytes.iter().enumerate() {
+            head.push(byte);
+            self.ready.push(byte);
+            if head.ends_with(b"\r\n\r\n") {
+                let negotiated = extensions(head).and_then(|it| negotiate(&it)).is_some();
+                self.mode = if negotiated {
+                    Mode::Frames
+                } else {
+                    Mode::Passthrough
+                };
+                self.raw.extend_from_slice(&bytes[i + 1..]);
+                return;
+            }
+            if head.len() > MAX_HANDSHAKE_SIZE {
+                // Not a handshake response the protocol layer will accept either
+                self.mode = Mode::Passthrough;
+                self.raw.extend_from_slice(&bytes[i + 1..]);
+                return;
+            }
+        }
+    }
+
+    /// Rewrites the compressed frames once complete, and hands the other ones over as their bytes come
+    fn process(&mut self) -> io::Result<()> {
+        let mut raw = std::mem::take(&mut self.raw);
+        let mut consumed = 0;
+        loop {
+            if self.passthrough > 0 {
+                let len = self.passthrough.min(raw.len() - consumed);
+                self.ready.extend_from_slice(&raw[consumed..consumed + len]);
+                consumed += len;
+                self.passthrough -= len;
+                if self.passthrough > 0 {
+                    break;
+                }
+            }
+            let Some(header) = FrameHeader::parse(&raw[consumed..])? else {
+                break;
+            };
+            let compressed = match (header.opcode, &self.message) {
+                (0x1 | 0x2, None) => header.rsv1,
+                (0x0, Some(_)) => !header.rsv1,
+                _ => false,
+            };
+            if !compressed {
+                // Control frames and uncompressed messages
+                self.ready
+                    .extend_from_slice(&raw[consumed..consumed + header.payload]);
+                consumed += header.payload;
+                self.passthrough = header.payload_len;
+                continue;
+            }
+            if raw.len() - consumed < header.len() {
+                break;
+            }
+            let mut payload = raw[consumed + header.payload..consumed + header.len()].to_vec();
+            consumed += header.len();
+            if let Some(mask) = header.mask {
+                payload
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(i, byte)| *byte ^= mask[i % 4]);
+            }
+            match self.message.as_mut() {
+                None => {
+                    let message = Compressed {
+                        opcode: header.opcode,
+                        mask: header.mask,
+                        payload,
+                    };
+                    match header.fin {
+                        true => self.inflate(message)?,
+                        false => self.message = Some(message),
+                    }
+                }
+                Some(message) => {
+                    message.payload.extend_from_slice(&payload);
+                    if message.payload.len() > MAX_MESSAGE_SIZE {
+                        return Err(invalid_data("Compressed message too large"));
+                    }
+                    if header.fin {
+                        if let Some(message) = self.message.take() {
+                            self.inflate(message)?;
+                        }
+                    }
+                }
+            }
+        }
+        raw.drain(..consumed);
+        self.raw = raw;
+        Ok(())
+    }
+
+    /// Inflates a compressed message into a single plain frame
+    fn inflate(&mut self, mut message: Compressed) -> io::Result<()> {
+        message.payload.extend_from_slice(&TRAILER);
+        let input = &message.payload;
+        let mut inflated = Vec::with_capacity(input.len() * 4);
+        let start = self.decompress.total_in();
+        loop {
+            let consumed = (self.decompress.total_in() - start) as usize;
+            self.decompress
+                .decompress_vec(&input[consumed..], &mut inflated, FlushDecompress::Sync)
+                .map_err(|what| invalid_data(&what.to_string()))?;
+            let consumed = (self.decompress.total_in() - start) as usize;
+            if consumed == input.len() && inflated.len() < inflated.capacity() {
+                break;
+            }
+            if inflated.len() > MAX_MESSAGE_SIZE {
+                return Err(invalid_data("Inflated message too large"));
+            }
+            inflated.reserve(inflated.capacity().max(1024));
+        }
+
+        if let Some(counters) = &self.counters {
+            counters
+                .inner
+                .received_compressed
+                .fetch_add((input.len() - TRAILER.len()) as u64, Ordering::Relaxed);
+            counters
+                .inner
+                .received_inflated
+                .fetch_add(inflated.len() as u64, Ordering::Relaxed);
+        }
+        let masked = if message.mask.is_some() { 0x80 } else { 0 };
+        self.ready.push(0x80 | message.opcode);
+        match inflated.len() {
+            len @ 0..=125 => self.ready.push(masked | len as u8),
+            len @ 126..=0xffff => {
+                self.ready.push(masked | 126);
+                self.ready.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                self.ready.push(masked | 127);
+                self.ready.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        if let Some(mask) = message.mask {
+            self.ready.extend_from_slice(&mask);
+            inflated
+                .iter_mut()
+                .enumerate()
+                .for_each(|(i, byte)| *byte ^= mask[i % 4]);
+        }
+        self.ready.extend_from_slice(&inflated);
+        Ok(())
+    }
+}
+
+/// The values of the `Sec-WebSocket-Extensions` headers of a handshake request or response
+fn extensions(head: &[u8]) -> Option<String> {
+    let head = std::str::from_utf8(head).ok()?;
+    let values = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-extensions"))
+        .map(|(_, value)| value.trim())
+        .collect::<Vec<_>>();
+    (!values.is_empty()).then(|| values.join(","))
+}
+
+fn invalid_data(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+}
+
+/// What's needed of a frame header (RFC 6455 section 5.2)
+struct FrameHeader {
+    fin: bool,
+    rsv1: bool,
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    /// Offset of the payload
+    payload: usize,
+    payload_len: usize,
+}
+
+impl FrameHeader {
+    /// Parses the header of a frame, unless the header isn't complete yet. Frames larger than a message can be
+    /// are refused before anything of their payload is read.
+    fn parse(bytes: &[u8]) -> io::Result<Option<Self>> {
+        let [first, second, rest @ ..] = bytes else {
+            return Ok(None);
+        };
+        let (payload_len, rest) = match second & 0x7f {
+            126 => {
+                let Some((len, rest)) = rest.split_first_chunk::<2>() else {
+                    return Ok(None);
+                };
+                (u64::from(u16::from_be_bytes(*len)), rest)
+            }
+            127 => {
+                let Some((len, rest)) = rest.split_first_chunk::<8>() else {
+                    return Ok(None);
+                };
+                (u64::from_be_bytes(*len), rest)
+            }
+            len => (u64::from(len), rest),
+        };
+        if payload_len > MAX_MESSAGE_SIZE as u64 {
+            return Err(invalid_data("Frame too large"));
+        }
+        let (mask, rest) = match second & 0x80 {
+            0 => (None, rest),
+            _ => {
+                let Some((mask, rest)) = rest.split_first_chunk::<4>() else {
+                    return Ok(None);
+                };
+                (Some(*mask), rest)
+            }
+        };
+        Ok(Some(Self {
+            fin: first & 0x80 != 0,
+            rsv1: first & 0x40 != 0,
+            opcode: first & 0x0f,
+            mask,
+            payload: bytes.len() - rest.len(),
+            payload_len: payload_len as usize,
+        }))
+    }
+
+    /// Length of the whole frame
+    fn len(&self) -> usize {
+        self.payload + self.payload_len
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for InflatingStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if this.read < this.ready.len() {
+                let len = buf.remaining().min(this.ready.len() - this.read);
+                buf.put_slice(&this.ready[this.read..this.read + len]);
+                this.read += len;
+                if this.read == this.ready.len() {
+                    this.ready.clear();
+                    this.read = 0;
+                }
+                return Poll::Ready(Ok(()));
+            }
+            match this.mode {
+                Mode::Passthrough if this.raw.is_empty() => {
+                    return Pin::new(&mut this.inner).poll_read(cx, buf);
+                }
+                Mode::Passthrough => {
+                    this.ready.append(&mut this.raw);
+                    continue;
+                }
+                Mode::Frames => {
+                    this.process()?;
+                    if !this.ready.is_empty() {
+                        continue;
+                    }
+                }
+                Mode::Handshake { .. } => {}
+            }
+
+            let mut chunk = [0; 8192];
+            let mut chunk = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk))?;
+            let bytes = chunk.filled();
+            if bytes.is_empty() {
+                // Hands over what's left, so the protocol layer reports the truncated frame
+                if this.raw.is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+                this.ready.append(&mut this.raw);
+                continue;
+            }
+            this.scan_handshake(bytes);
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for InflatingStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::negotiate;
+    use super::Deflate;
+    use super::InflatingStream;
+    use super::Negotiated;
+    use crate::janus_interface::DeflateConfig;
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio_tungstenite::tungstenite::protocol::frame::Frame;
+    use tokio_tungstenite::tungstenite::Message;
+
+    #[test]
+    fn it_should_negotiate_the_extension() {
+        let deflate = Deflate::new(DeflateConfig {
+            server_max_window_bits: 10,
+            client_no_context_takeover: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            deflate.offer(),
+            "permessage-deflate; server_max_window_bits=10; client_no_context_takeover"
+        );
+        assert_eq!(
+            negotiate("x-webkit-deflate-frame, permessage-deflate; client_no_context_takeover"),
+            Some(Negotiated {
+                client_no_context_takeover: true,
+                client_max_window_bits: None,
+            })
+        );
+        assert_eq!(negotiate("x-webkit-deflate-frame"), None);
+        assert!(deflate
+            .deflater("permessage-deflate; client_max_window_bits=10")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn it_should_inflate_fragmented_messages() {
+        let deflate = Deflate::new(DeflateConfig::default());
+        let mut deflater = deflate.deflater("permessage-deflate").unwrap();
+        let text = "janus ".repeat(100);
+        let Message::Frame(frame) = deflater.deflate(Message::Text(text.clone().into())) else {
+            panic!("Not compressed");
+        };
+        let compressed = frame.payload().to_vec();
+        assert!(compressed.len() < text.len());
+
+        let (mut server, client) = tokio::io::duplex(64 << 10);
+        let mut stream = InflatingStream::new(client, Some(deflate.counters.clone()));
+        let handshake = "HTTP/1.1 101 Switching Protocols\r\nSec-WebSocket-Extensions: permessage-deflate\r\n\r\n";
+        let (first, second) = compressed.split_at(compressed.len() / 2);
+        let mut bytes = handshake.as_bytes().to_vec();
+        // A compressed text frame, a ping, then the last continuation frame
+        bytes.extend_from_slice(&[0x41, first.len() as u8]);
+        bytes.extend_from_slice(first);
+        bytes.extend_from_slice(&[0x89, 0x00]);
+        bytes.extend_from_slice(&[0x80, second.len() as u8]);
+        bytes.extend_from_slice(second);
+        server.write_all(&bytes).await.unwrap();
+        drop(server);
+
+        let mut read = Vec::new();
+        stream.read_to_end(&mut read).await.unwrap();
+        let (head, frames) = read.split_at(handshake.len());
+        assert_eq!(head, handshake.as_bytes());
+        assert_eq!(frames[..2], [0x89, 0x00]);
+        let mut plain = Vec::new();
+        Frame::message(text.clone(), super::OpCode::Data(super::Data::Text), true)
+            .format(&mut plain)
+            .unwrap();
+        assert_eq!(frames[2..], plain);
+
+        let metrics = deflate.counters.metrics();
+        assert_eq!(metrics.sent_uncompressed, text.len() as u64);
+        assert_eq!(metrics.received_inflated, text.len() as u64);
+        assert_eq!(metrics.received_compressed, compressed.len() as u64);
+    }
+
+    const HANDSHAKE: &str =
+        "HTTP/1.1 101 Switching Protocols\r\nSec-WebSocket-Extensions: permessage-deflate\r\n\r\n";
+
+    #[tokio::test]
+    async fn it_should_hand_the_uncompressed_frames_over_as_they_come() {
+        let deflate = Deflate::new(DeflateConfig::default());
+        let (mut server, client) = tokio::io::duplex(64 << 10);
+        let mut stream = InflatingStream::new(client, Some(deflate.counters.clone()));
+        server.write_all(HANDSHAKE.as_bytes()).await.unwrap();
+        let mut head = vec![0; HANDSHAKE.len()];
+        stream.read_exact(&mut head).await.unwrap();
+
+        // Half of a plain binary frame of 1000 bytes
+        server.write_all(&[0x82, 126, 0x03, 0xe8]).await.unwrap();
+        server.write_all(&[7; 500]).await.unwrap();
+        let mut read = vec![0; 4 + 500];
+        stream.read_exact(&mut read).await.unwrap();
+        assert_eq!(read[..4], [0x82, 126, 0x03, 0xe8]);
+        assert!(read[4..].iter().all(|&byte| byte == 7));
+    }
+
+    #[tokio::test]
+    async fn it_should_refuse_the_frames_larger_than_a_message() {
+        let deflate = Deflate::new(DeflateConfig::default());
+        let (mut server, client) = tokio::io::duplex(64 << 10);
+        let mut stream = InflatingStream::new(client, Some(deflate.counters.clone()));
+        let mut bytes = HANDSHAKE.as_bytes().to_vec();
+        bytes.extend_from_slice(&[0xc1, 127]);
+        bytes.extend_from_slice(&(super::MAX_MESSAGE_SIZE as u64 + 1).to_be_bytes());
+        server.write_all(&bytes).await.unwrap();
+
+        let mut read = Vec::new();
+        let error = stream.read_to_end(&mut read).await.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
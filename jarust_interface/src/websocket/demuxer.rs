@@ -1,82 +1,180 @@
+use super::pending::PendingRequests;
 use super::router::Router;
 use super::tmanager::TransactionManager;
 use crate::japrotocol::JaResponse;
 use crate::japrotocol::ResponseType;
 use crate::Error;
 use bytes::Bytes;
+use serde::Deserialize;
+use std::borrow::Cow;
 use tokio::sync::mpsc;
 
 pub(crate) struct Demuxer {
     pub(crate) inbound_stream: mpsc::UnboundedReceiver<Bytes>,
     pub(crate) router: Router,
-    pub(crate) rsp_sender: mpsc::UnboundedSender<JaResponse>,
-    pub(crate) ack_sender: mpsc::UnboundedSender<JaResponse>,
+    pub(crate) acks: PendingRequests<JaResponse>,
+    pub(crate) responses: PendingRequests<JaResponse>,
     pub(crate) transaction_manager: TransactionManager,
+    pub(crate) disconnections: Option<Disconnections>,
+}
+
+/// Relays the dropped connections once the messages received before them are demuxed,
+/// so the requests in flight are only failed after the replies that made it through.
+pub(crate) struct Disconnections {
+    pub(crate) incoming: mpsc::UnboundedReceiver<String>,
+    pub(crate) outgoing: mpsc::UnboundedSender<String>,
 }
 
 impl Demuxer {
     /// Async task to handle demultiplexing of the inbound stream
     #[tracing::instrument(name = "incoming_message", level = tracing::Level::TRACE, skip_all)]
-    pub(crate) async fn start(self) -> Result<(), Error> {
-        let mut stream = self.inbound_stream;
-        while let Some(next) = stream.recv().await {
-            let Ok(incoming_event) = std::str::from_utf8(&next) else {
-                tracing::error!("Incomplete packet received");
-                continue;
-            };
-
-            tracing::trace!("Received {incoming_event}");
-
-            // Parse the incoming message
-            match serde_json::from_str::<JaResponse>(incoming_event) {
-                Ok(response) => match response.clone().janus {
-                    ResponseType::Error { error } => {
-                        tracing::error!("{error:#?}");
-                        _ = self.rsp_sender.send(response.clone());
-                        _ = self.ack_sender.send(response);
-                    }
-                    ResponseType::Ack => {
-                        _ = self.ack_sender.send(response);
-                    }
-                    ResponseType::Success(_) | ResponseType::ServerInfo(_) => {
-                        _ = self.rsp_sender.send(response);
-                    }
-                    ResponseType::Event(_) => {
-                        if let Err(what) =
-                            Demuxer::demux_event(response, &self.router, &self.transaction_manager)
-                                .await
-                        {
-                            tracing::error!("Error demuxing message: {what}");
-                        }
+    pub(crate) async fn start(mut self) -> Result<(), Error> {
+        loop {
+            let next = match &mut self.disconnections {
+                Some(disconnections) => tokio::select! {
+                    biased;
+                    next = self.inbound_stream.recv() => next,
+                    Some(reason) = disconnections.incoming.recv() => {
+                        // The replies of the requests in flight are lost with the connection
+                        self.acks.fail_all(|| Error::ConnectionLost);
+                        self.responses.fail_all(|| Error::ConnectionLost);
+                        _ = disconnections.outgoing.send(reason);
+                        continue;
                     }
                 },
-                Err(what) => {
-                    tracing::error!("Error parsing response: {what}");
-                }
+                None => self.inbound_stream.recv().await,
+            };
+            let Some(next) = next else {
+                break;
             };
+            self.demux(&next).await;
         }
         Ok(())
     }
 
-    /// Route the message to the proper channel
-    async fn demux_event(
-        message: JaResponse,
-        router: &Router,
-        transaction_manager: &TransactionManager,
-    ) -> Result<(), Error> {
-        // Check if we have a pending transaction and demux to the proper route
-        if let Some(transaction) = message.transaction.clone() {
-            if let Some(path) = transaction_manager.get(&transaction).await {
-                router.pub_subroute(&path, message).await?;
-                return Ok(());
+    /// Routes a frame from its envelope, it's only fully parsed if someone is waiting on it, then moved
+    /// to where it belongs, cloned only when it belongs to both a pending request and a route.
+    async fn demux(&self, next: &[u8]) {
+        tracing::trace!(frame = %String::from_utf8_lossy(next), "Received");
+
+        let envelope = match serde_json::from_slice::<Envelope>(next) {
+            Ok(envelope) => envelope,
+            Err(what) => {
+                tracing::error!("Error parsing response: {what}");
+                return;
+            }
+        };
+        let transaction = envelope.transaction.as_deref();
+
+        match envelope.janus.as_ref() {
+            "ack" => {
+                let Some(transaction) = transaction.filter(|t| self.acks.is_waiting(t)) else {
+                    return;
+                };
+                if let Some(response) = parse(next) {
+                    self.acks.resolve(transaction, response);
+                }
+            }
+            "success" | "server_info" => {
+                let Some(transaction) = transaction else {
+                    return;
+                };
+                self.transaction_manager.remove(transaction);
+                if !self.responses.is_waiting(transaction) {
+                    return;
+                }
+                if let Some(response) = parse(next) {
+                    self.responses.resolve(transaction, response);
+                }
+            }
+            "error" => {
+                let Some(response) = parse(next) else {
+                    return;
+                };
+                if let ResponseType::Error { error } = &response.janus {
+                    tracing::error!("{error:#?}");
+                }
+                let Some(transaction) = transaction else {
+                    return;
+                };
+                self.transaction_manager.remove(transaction);
+                if self.acks.is_waiting(transaction) {
+                    self.acks.resolve(transaction, response.clone());
+                }
+                self.responses.resolve(transaction, response);
+            }
+            _ => self.demux_event(next, &envelope).await,
+        }
+    }
+
+    /// Hands an event to the request waiting on it, if it's an asynchronous plugin response,
+    /// and to its route: the one of its transaction if any, otherwise the one of its session and sender
+    async fn demux_event(&self, next: &[u8], envelope: &Envelope<'_>) {
+        let transaction = envelope.transaction.as_deref();
+        // Asynchronous plugin responses are events carrying the transaction of the request
+        let waiting = transaction.filter(|t| self.responses.is_waiting(t));
+
+        let path = match transaction.and_then(|t| self.transaction_manager.get(t)) {
+            Some(path) => {
+                // The event is the response of the request, nothing else is expected on this transaction
+                if let Some(transaction) = transaction {
+                    self.transaction_manager.remove(transaction);
+                }
+                Some(path)
             }
+            None => Router::path_from_ids(envelope.session_id, envelope.sender),
+        };
+        let route = match &path {
+            Some(path) => self.router.subroute(path).await,
+            None => None,
+        };
+
+        if waiting.is_none() && route.is_none() {
+            tracing::trace!("Nobody is listening, dropping the event");
+            return;
         }
+        let Some(response) = parse(next) else {
+            return;
+        };
+        match (waiting, route) {
+            (Some(transaction), Some(route)) => {
+                self.responses.resolve(transaction, response.clone());
+                if route.send(response).await.is_err() {
+                    tracing::error!("Error demuxing message: {}", Error::SendError);
+                }
+            }
+            (Some(transaction), None) => {
+                self.responses.resolve(transaction, response);
+            }
+            (None, Some(route)) => {
+                if route.send(response).await.is_err() {
+                    tracing::error!("Error demuxing message: {}", Error::SendError);
+                }
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+/// The routing fields of a frame, borrowed from it
+#[derive(Deserialize)]
+struct Envelope<'a> {
+    #[serde(borrow)]
+    janus: Cow<'a, str>,
+    #[serde(borrow, default)]
+    transaction: Option<Cow<'a, str>>,
+    #[serde(default)]
+    session_id: Option<u64>,
+    #[serde(default)]
+    sender: Option<u64>,
+}
 
-        // Try get the route from the response
-        if let Some(path) = Router::path_from_response(message.clone()) {
-            router.pub_subroute(&path, message).await?;
-            return Ok(());
+fn parse(frame: &[u8]) -> Option<JaResponse> {
+    match serde_json::from_slice::<JaResponse>(frame) {
+        Ok(response) => Some(response),
+        Err(what) => {
+            tracing::error!("Error parsing response: {what}");
+            None
         }
-        Ok(())
     }
 }
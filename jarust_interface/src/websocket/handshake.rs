@@ -0,0 +1,21 @@
+use tokio_tungstenite::tungstenite::handshake::server::Callback;
+use tokio_tungstenite::tungstenite::handshake::server::ErrorResponse;
+use tokio_tungstenite::tungstenite::handshake::server::Request;
+use tokio_tungstenite::tungstenite::handshake::server::Response;
+
+/// Accepts the handshake of the test servers once the closure has filled in the response
+pub(crate) struct Accept<F>(pub(crate) F);
+
+impl<F> Callback for Accept<F>
+where
+    F: FnOnce(&Request, &mut Response),
+{
+    fn on_request(
+        self,
+        request: &Request,
+        mut response: Response,
+    ) -> Result<Response, ErrorResponse> {
+        (self.0)(request, &mut response);
+        Ok(response)
+    }
+}
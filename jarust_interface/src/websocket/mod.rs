@@ -1,11 +1,20 @@
+#[cfg(not(target_family = "wasm"))]
 mod connector;
-mod demuxer;
-mod napmap;
-mod ringbuf_map;
-mod router;
-mod tmanager;
-mod websocket_client;
+#[cfg(not(target_family = "wasm"))]
+pub(crate) mod deflate;
+pub(crate) mod demuxer;
+#[cfg(all(test, not(target_family = "wasm")))]
+pub(crate) mod handshake;
+pub(crate) mod pending;
+pub(crate) mod router;
+pub(crate) mod tmanager;
+#[cfg(not(target_family = "wasm"))]
+pub(crate) mod websocket_client;
+#[cfg(target_family = "wasm")]
+#[path = "wasm_websocket_client.rs"]
+pub(crate) mod websocket_client;
 
 pub mod websocket_interface;
 
 pub use websocket_interface::WebSocketInterface;
+pub use websocket_interface::WebSocketTransport;
@@ -0,0 +1,223 @@
+use crate::japrotocol::JaResponse;
+use crate::japrotocol::ResponseType;
+use crate::Error;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::Weak;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+type Waiters<V> = Mutex<HashMap<String, Waiter<V>>>;
+
+#[derive(Debug)]
+struct Waiter<V> {
+    session_id: Option<u64>,
+    sender: oneshot::Sender<Result<V, Error>>,
+}
+
+/// Requests waiting on their reply, by transaction.
+///
+/// A request is registered before it's sent so its reply can't arrive before anyone waits on it, and it's
+/// unregistered once its [`PendingRequest`] is dropped, whether it got its reply, timed out or was abandoned.
+/// Replies nobody waits on are dropped.
+#[derive(Debug)]
+pub(crate) struct PendingRequests<V> {
+    waiters: Arc<Waiters<V>>,
+}
+
+impl<V> PendingRequests<V> {
+    pub(crate) fn new() -> Self {
+        Self {
+            waiters: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Registers a request, `session_id` is the session it's sent on if any
+    #[tracing::instrument(level = tracing::Level::TRACE, skip(self))]
+    pub(crate) fn register(&self, transaction: &str, session_id: Option<u64>) -> PendingRequest<V> {
+        let (sender, receiver) = oneshot::channel();
+        self.lock()
+            .insert(transaction.to_string(), Waiter { session_id, sender });
+        PendingRequest {
+            transaction: transaction.to_string(),
+            receiver,
+            waiters: Arc::downgrade(&self.waiters),
+        }
+    }
+
+    /// Returns whether a request is waiting on the transaction, so replies nobody waits on aren't even parsed
+    pub(crate) fn is_waiting(&self, transaction: &str) -> bool {
+        self.lock().contains_key(transaction)
+    }
+
+    /// Hands the reply to the request waiting on it, returns whether there was one
+    #[tracing::instrument(level = tracing::Level::TRACE, skip(self, reply))]
+    pub(crate) fn resolve(&self, transaction: &str, reply: V) -> bool {
+        match self.lock().remove(transaction) {
+            Some(waiter) => waiter.sender.send(Ok(reply)).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Fails the requests sent on a session that's gone
+    #[tracing::instrument(level = tracing::Level::TRACE, skip(self))]
+    pub(crate) fn fail_session(&self, session_id: u64) {
+        let mut waiters = self.lock();
+        let transactions = waiters
+            .iter()
+            .filter(|(_, waiter)| waiter.session_id == Some(session_id))
+            .map(|(transaction, _)| transaction.clone())
+            .collect::<Vec<_>>();
+        for transaction in transactions {
+            if let Some(waiter) = waiters.remove(&transaction) {
+                let _ = waiter
+                    .sender
+                    .send(Err(Error::SessionDestroyed { session_id }));
+            }
+        }
+    }
+
+    /// Fails every request, e.g. when the connection dropped and their replies are lost with it
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    pub(crate) fn fail_all(&self, error: impl Fn() -> Error) {
+        for (_, waiter) in self.lock().drain() {
+            let _ = waiter.sender.send(Err(error()));
+        }
+    }
+
+    #[allow(unused)]
+    pub(crate) fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Waiter<V>>> {
+        self.waiters.lock().expect("pending requests poisoned")
+    }
+}
+
+impl<V> Clone for PendingRequests<V> {
+    fn clone(&self) -> Self {
+        Self {
+            waiters: self.waiters.clone(),
+        }
+    }
+}
+
+/// A registered request, waiting on its reply
+#[derive(Debug)]
+pub(crate) struct PendingRequest<V> {
+    transaction: String,
+    receiver: oneshot::Receiver<Result<V, Error>>,
+    waiters: Weak<Waiters<V>>,
+}
+
+impl<V> PendingRequest<V> {
+    pub(crate) fn transaction(&self) -> &str {
+        &self.transaction
+    }
+
+    /// Waits for the reply, failing with [`Error::RequestTimeout`] once the timeout elapses
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all, fields(transaction = self.transaction))]
+    pub(crate) async fn wait(mut self, timeout: Duration) -> Result<V, Error> {
+        tracing::trace!("Waiting for reply");
+        match jarust_rt::timeout(timeout, &mut self.receiver).await {
+            Ok(Ok(reply)) => reply,
+            // The registry is gone with its interface
+            Ok(Err(_)) => Err(Error::ConnectionLost),
+            Err(_) => {
+                tracing::error!("Request timeout");
+                Err(Error::RequestTimeout)
+            }
+        }
+    }
+}
+
+impl PendingRequest<JaResponse> {
+    /// Waits for the response, failing with [`Error::JanusError`] if janus replied with an error
+    pub(crate) async fn response(self, timeout: Duration) -> Result<JaResponse, Error> {
+        let response = self.wait(timeout).await?;
+        match response.janus {
+            ResponseType::Error { error } => Err(Error::JanusError {
+                code: error.code,
+                reason: error.reason,
+            }),
+            _ => Ok(response),
+        }
+    }
+}
+
+impl<V> Drop for PendingRequest<V> {
+    fn drop(&mut self) {
+        if let Some(waiters) = self.waiters.upgrade() {
+            waiters
+                .lock()
+                .expect("pending requests poisoned")
+                .remove(&self.transaction);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PendingRequests;
+    use crate::Error;
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    #[tokio::test]
+    async fn it_should_resolve_the_registered_request() {
+        let pending = PendingRequests::new();
+        let request = pending.register("abc", None);
+
+        assert!(pending.resolve("abc", 7));
+        assert!(!pending.resolve("def", 8));
+        assert_eq!(request.wait(TIMEOUT).await.unwrap(), 7);
+        assert_eq!(pending.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn it_should_unregister_on_timeout_and_drop() {
+        let pending = PendingRequests::<u32>::new();
+        let request = pending.register("abc", None);
+        let result = request.wait(Duration::from_millis(10)).await;
+        assert!(matches!(result, Err(Error::RequestTimeout)));
+
+        drop(pending.register("def", None));
+        assert_eq!(pending.len(), 0);
+        assert!(!pending.resolve("abc", 7));
+    }
+
+    #[tokio::test]
+    async fn it_should_fail_with_the_known_cause() {
+        let pending = PendingRequests::<u32>::new();
+        let first = pending.register("abc", Some(1));
+        let second = pending.register("def", Some(2));
+        let third = pending.register("ghi", None);
+
+        pending.fail_session(1);
+        assert!(matches!(
+            first.wait(TIMEOUT).await,
+            Err(Error::SessionDestroyed { session_id: 1 })
+        ));
+
+        pending.fail_all(|| Error::ConnectionLost);
+        assert!(matches!(
+            second.wait(TIMEOUT).await,
+            Err(Error::ConnectionLost)
+        ));
+        assert!(matches!(
+            third.wait(TIMEOUT).await,
+            Err(Error::ConnectionLost)
+        ));
+
+        let fourth = pending.register("jkl", None);
+        drop(pending);
+        assert!(matches!(
+            fourth.wait(TIMEOUT).await,
+            Err(Error::ConnectionLost)
+        ));
+    }
+}
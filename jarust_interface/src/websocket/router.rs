@@ -1,9 +1,12 @@
+use crate::event_channel::event_channel;
+use crate::event_channel::EventChannelConfig;
+use crate::event_channel::EventReceiver;
+use crate::event_channel::EventSender;
 use crate::japrotocol::JaResponse;
 use crate::Error;
 use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::mpsc;
 use tokio::sync::RwLock;
 
 #[derive(Debug)]
@@ -13,7 +16,7 @@ struct Shared {
 
 #[derive(Debug)]
 struct Exclusive {
-    routes: HashMap<String, mpsc::UnboundedSender<JaResponse>>,
+    routes: HashMap<String, EventSender<JaResponse>>,
 }
 
 #[derive(Debug)]
@@ -46,8 +49,12 @@ impl Router {
     }
 
     #[tracing::instrument(level = tracing::Level::TRACE, skip(self))]
-    async fn make_route(&mut self, path: &str) -> mpsc::UnboundedReceiver<JaResponse> {
-        let (tx, rx) = mpsc::unbounded_channel();
+    async fn make_route(
+        &mut self,
+        path: &str,
+        config: EventChannelConfig,
+    ) -> EventReceiver<JaResponse> {
+        let (tx, rx) = event_channel(config);
         {
             self.inner
                 .exclusive
@@ -60,24 +67,52 @@ impl Router {
         rx
     }
 
-    pub(crate) async fn add_subroute(&mut self, end: &str) -> mpsc::UnboundedReceiver<JaResponse> {
+    pub(crate) async fn add_subroute(
+        &mut self,
+        end: &str,
+        config: EventChannelConfig,
+    ) -> EventReceiver<JaResponse> {
         let path = &format!("{}/{}", self.inner.shared.root_path, end);
-        self.make_route(path).await
+        self.make_route(path, config).await
     }
 
+    /// Removes the subroute and all of its nested subroutes, closing their channels
+    #[tracing::instrument(level = tracing::Level::TRACE, skip(self))]
+    pub(crate) async fn remove_subroutes(&self, start: &str) {
+        let path = format!("{}/{}", self.inner.shared.root_path, start);
+        let nested = format!("{path}/");
+        self.inner
+            .exclusive
+            .write()
+            .await
+            .routes
+            .retain(|route, _| route != &path && !route.starts_with(&nested));
+        tracing::trace!("Routes removed");
+    }
+
+    /// Returns the channel of the given subroute, if it exists
+    pub(crate) async fn subroute(&self, subroute: &str) -> Option<EventSender<JaResponse>> {
+        let path = format!("{}/{}", self.inner.shared.root_path, subroute);
+        self.inner.exclusive.read().await.routes.get(&path).cloned()
+    }
+
+    /// Publishes the message to every nested subroute of the given subroute
     #[tracing::instrument(level = tracing::Level::TRACE, skip(self, message))]
-    async fn publish(&self, path: &str, message: JaResponse) -> Result<(), Error> {
-        let channel = {
+    pub(crate) async fn pub_nested_subroutes(&self, start: &str, message: JaResponse) {
+        let nested = format!("{}/{}/", self.inner.shared.root_path, start);
+        let channels = {
             let guard = self.inner.exclusive.read().await;
-            guard.routes.get(path).cloned()
+            guard
+                .routes
+                .iter()
+                .filter(|(route, _)| route.starts_with(&nested))
+                .map(|(_, channel)| channel.clone())
+                .collect::<Vec<_>>()
         };
-        if let Some(channel) = channel {
-            if channel.send(message.clone()).is_err() {
-                return Err(Error::SendError);
-            }
+        for channel in channels {
+            let _ = channel.send(message.clone()).await;
         }
         tracing::trace!("Published");
-        Ok(())
     }
 
     pub(crate) async fn pub_subroute(
@@ -85,8 +120,13 @@ impl Router {
         subroute: &str,
         message: JaResponse,
     ) -> Result<(), Error> {
-        let path = &format!("{}/{}", self.inner.shared.root_path, subroute);
-        self.publish(path, message).await
+        if let Some(channel) = self.subroute(subroute).await {
+            if channel.send(message).await.is_err() {
+                return Err(Error::SendError);
+            }
+        }
+        tracing::trace!("Published");
+        Ok(())
     }
 }
 
@@ -104,9 +144,13 @@ impl Router {
         }
     }
 
-    pub fn path_from_response(response: JaResponse) -> Option<String> {
-        let session_id = response.session_id?;
-        let Some(sender) = response.sender else {
+    pub fn path_from_response(response: &JaResponse) -> Option<String> {
+        Self::path_from_ids(response.session_id, response.sender)
+    }
+
+    pub fn path_from_ids(session_id: Option<u64>, sender: Option<u64>) -> Option<String> {
+        let session_id = session_id?;
+        let Some(sender) = sender else {
             return Some(format!("{session_id}"));
         };
         Some(format!("{session_id}/{sender}"))
@@ -116,14 +160,19 @@ impl Router {
 #[cfg(test)]
 mod tests {
     use super::Router;
+    use crate::event_channel::EventChannelConfig;
     use crate::japrotocol::JaResponse;
     use crate::japrotocol::ResponseType;
 
     #[tokio::test]
     async fn test_basic_usage() {
         let mut router = Router::new("janus");
-        let mut channel_one = router.add_subroute("one").await;
-        let mut channel_two = router.add_subroute("two").await;
+        let mut channel_one = router
+            .add_subroute("one", EventChannelConfig::default())
+            .await;
+        let mut channel_two = router
+            .add_subroute("two", EventChannelConfig::default())
+            .await;
 
         router
             .pub_subroute(
@@ -175,4 +224,25 @@ mod tests {
         assert_eq!(size_one, 1);
         assert_eq!(size_two, 2);
     }
+
+    #[tokio::test]
+    async fn it_should_remove_nested_subroutes() {
+        let mut router = Router::new("janus");
+        let mut session_one = router
+            .add_subroute("1", EventChannelConfig::default())
+            .await;
+        let mut handle_one = router
+            .add_subroute("1/2", EventChannelConfig::default())
+            .await;
+        let mut session_ten = router
+            .add_subroute("10", EventChannelConfig::default())
+            .await;
+
+        router.remove_subroutes("1").await;
+
+        assert_eq!(session_one.recv().await, None);
+        assert_eq!(handle_one.recv().await, None);
+        assert!(session_ten.try_recv().is_err());
+        assert!(!session_ten.is_closed());
+    }
 }
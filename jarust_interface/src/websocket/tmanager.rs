@@ -1,30 +1,177 @@
-use super::ringbuf_map::RingBufMap;
+use crate::transaction_metrics::TransactionMetrics;
+use jarust_rt::Instant;
+use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::Mutex;
+use std::time::Duration;
 
+/// Routes of the requests in flight, by transaction.
+///
+/// A transaction is tracked until its response arrives or its ttl expires. The capacity is a soft limit,
+/// exceeding it is reported and counted but nothing gets evicted.
 #[derive(Clone, Debug)]
 pub(crate) struct TransactionManager {
-    inner: Arc<RwLock<RingBufMap<String, String>>>,
+    inner: Arc<Mutex<Transactions>>,
+}
+
+#[derive(Debug)]
+struct Transactions {
+    capacity: usize,
+    ttl: Duration,
+    routes: HashMap<String, Route>,
+    /// Transactions by insertion, hence by expiry as they all share the same ttl
+    expiries: VecDeque<(Instant, String)>,
+    metrics: TransactionMetrics,
+}
+
+#[derive(Debug)]
+struct Route {
+    path: String,
+    expires_at: Instant,
 }
 
 impl TransactionManager {
     #[tracing::instrument(level = tracing::Level::TRACE)]
-    pub(crate) fn new(capacity: usize) -> Self {
+    pub(crate) fn new(capacity: usize, ttl: Duration) -> Self {
         tracing::trace!("Creating new transaction manager");
-        let transactions = RingBufMap::new(capacity);
-        let inner = Arc::new(RwLock::new(transactions));
+        let transactions = Transactions {
+            capacity,
+            ttl,
+            routes: HashMap::with_capacity(capacity),
+            expiries: VecDeque::with_capacity(capacity),
+            metrics: TransactionMetrics::default(),
+        };
+        let inner = Arc::new(Mutex::new(transactions));
         Self { inner }
     }
 
     #[tracing::instrument(level = tracing::Level::TRACE, skip(self))]
-    pub(crate) async fn get(&self, id: &str) -> Option<String> {
+    pub(crate) fn get(&self, id: &str) -> Option<String> {
         tracing::trace!("Getting transaction");
-        self.inner.read().await.get(&id.into()).cloned()
+        let transactions = self.lock();
+        transactions
+            .routes
+            .get(id)
+            .filter(|route| route.expires_at > Instant::now())
+            .map(|route| route.path.clone())
     }
 
     #[tracing::instrument(parent = None, skip(self))]
-    pub(crate) async fn insert(&self, id: &str, transaction: &str) {
+    pub(crate) fn insert(&self, id: &str, path: &str) {
         tracing::trace!("Inserting transaction");
-        self.inner.write().await.put(id.into(), transaction.into());
+        let mut transactions = self.lock();
+        let now = Instant::now();
+        transactions.expire(now);
+
+        if transactions.routes.len() >= transactions.capacity {
+            transactions.metrics.over_capacity += 1;
+            tracing::warn!(
+                in_flight = transactions.routes.len(),
+                capacity = transactions.capacity,
+                "Transactions in flight exceed the capacity"
+            );
+        }
+
+        let expires_at = now + transactions.ttl;
+        transactions.routes.insert(
+            id.to_string(),
+            Route {
+                path: path.to_string(),
+                expires_at,
+            },
+        );
+        transactions
+            .expiries
+            .push_back((expires_at, id.to_string()));
+        let in_flight = transactions.routes.len();
+        transactions.metrics.in_flight = in_flight;
+        transactions.metrics.peak_in_flight = transactions.metrics.peak_in_flight.max(in_flight);
+    }
+
+    /// Stops tracking a transaction once its response arrived
+    #[tracing::instrument(level = tracing::Level::TRACE, skip(self))]
+    pub(crate) fn remove(&self, id: &str) {
+        let mut transactions = self.lock();
+        if transactions.routes.remove(id).is_some() {
+            transactions.metrics.in_flight = transactions.routes.len();
+        }
+    }
+
+    pub(crate) fn metrics(&self) -> TransactionMetrics {
+        let mut transactions = self.lock();
+        transactions.expire(Instant::now());
+        transactions.metrics
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Transactions> {
+        self.inner.lock().expect("transactions poisoned")
+    }
+}
+
+impl Transactions {
+    fn expire(&mut self, now: Instant) {
+        while let Some((expires_at, _)) = self.expiries.front() {
+            if *expires_at > now {
+                break;
+            }
+            let Some((expires_at, id)) = self.expiries.pop_front() else {
+                break;
+            };
+            // The transaction might have been answered, or reused, in the meantime
+            if self
+                .routes
+                .get(&id)
+                .is_some_and(|route| route.expires_at == expires_at)
+            {
+                self.routes.remove(&id);
+                self.metrics.expired += 1;
+                tracing::debug!(transaction = id, "Transaction expired");
+            }
+        }
+        self.metrics.in_flight = self.routes.len();
+    }
+}
+
+#[cfg(all(test, not(target_family = "wasm")))]
+mod tests {
+    use super::TransactionManager;
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn it_should_keep_transactions_until_they_expire() {
+        let tmanager = TransactionManager::new(2, Duration::from_secs(10));
+        tmanager.insert("abc", "janus/1");
+        tokio::time::advance(Duration::from_secs(5)).await;
+        tmanager.insert("def", "janus/2");
+        tokio::time::advance(Duration::from_secs(5)).await;
+
+        assert_eq!(tmanager.get("abc"), None);
+        assert_eq!(tmanager.get("def"), Some("janus/2".to_string()));
+
+        tmanager.insert("ghi", "janus/3");
+        let metrics = tmanager.metrics();
+        assert_eq!(metrics.in_flight, 2);
+        assert_eq!(metrics.expired, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_should_not_evict_past_the_capacity() {
+        let tmanager = TransactionManager::new(2, Duration::from_secs(10));
+        tmanager.insert("abc", "janus/1");
+        tmanager.insert("def", "janus/2");
+        tmanager.insert("ghi", "janus/3");
+
+        assert_eq!(tmanager.get("abc"), Some("janus/1".to_string()));
+        assert_eq!(tmanager.get("ghi"), Some("janus/3".to_string()));
+
+        tmanager.remove("abc");
+        assert_eq!(tmanager.get("abc"), None);
+
+        let metrics = tmanager.metrics();
+        assert_eq!(metrics.in_flight, 2);
+        assert_eq!(metrics.peak_in_flight, 3);
+        assert_eq!(metrics.over_capacity, 1);
+        assert_eq!(metrics.expired, 0);
     }
 }
@@ -0,0 +1,348 @@
+//! The websocket client of the browser, on top of its `WebSocket` api.
+//!
+//! The browser owns the handshake, the compression and the pings: the handshake headers, TLS settings, proxy,
+//! `permessage-deflate` offer and heartbeat are out of reach, they're ignored.
+
+use crate::janus_interface::DeflateConfig;
+use crate::janus_interface::HeartbeatConfig;
+use crate::janus_interface::ProxyConfig;
+use crate::janus_interface::TlsConfig;
+use crate::janus_interface::WebSocketWriterConfig;
+use crate::Error;
+use bytes::Bytes;
+use jarust_rt::JaTask;
+use reqwest::header::HeaderMap;
+use send_wrapper::SendWrapper;
+use std::cell::RefCell;
+use std::rc::Rc;
+use tokio::sync::mpsc;
+use tokio::sync::oneshot;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use wasm_bindgen::JsValue;
+use web_sys::BinaryType;
+use web_sys::CloseEvent;
+use web_sys::Event;
+use web_sys::MessageEvent;
+use web_sys::WebSocket;
+
+/// Receiving ends of a websocket client, they outlive the underlying socket so they keep working across reconnections.
+pub(crate) struct WebSocketReceivers {
+    /// Incoming text messages
+    pub(crate) inbound: mpsc::UnboundedReceiver<Bytes>,
+    /// The reason of each dropped connection
+    pub(crate) disconnections: mpsc::UnboundedReceiver<String>,
+}
+
+/// What the writer task is fed with
+enum Outbound {
+    Frame(Vec<u8>),
+    /// A freshly opened socket, replacing the previous one
+    Attach(SendWrapper<WebSocket>),
+}
+
+/// Enqueues the frames of a websocket client, the writer task writes them in order.
+///
+/// Cloning the sender is cheap, the clones feed the same writer, which keeps its queue across reconnections.
+#[derive(Clone, Debug)]
+pub(crate) struct WebSocketSender {
+    outbound: mpsc::Sender<Outbound>,
+}
+
+impl WebSocketSender {
+    /// Enqueues a frame without waiting for it to be written, unless the queue is full
+    pub(crate) async fn send(&self, data: &[u8]) -> Result<(), Error> {
+        self.enqueue(Outbound::Frame(data.to_vec())).await
+    }
+
+    async fn enqueue(&self, item: Outbound) -> Result<(), Error> {
+        self.outbound.send(item).await.map_err(|_| {
+            tracing::error!("Transport not opened!");
+            Error::TransportNotOpened
+        })
+    }
+}
+
+/// A browser socket along with its event handlers, which are unset once it's dropped
+#[derive(Debug)]
+struct Socket {
+    websocket: WebSocket,
+    _on_open: Closure<dyn FnMut(Event)>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_error: Closure<dyn FnMut(Event)>,
+    _on_close: Closure<dyn FnMut(CloseEvent)>,
+}
+
+impl Socket {
+    /// Starts the handshake, its outcome is sent to `opened`. Once opened, the incoming text messages are
+    /// forwarded to `inbound` and the reason the connection dropped to `disconnections`.
+    fn open(
+        url: &str,
+        protocol: &str,
+        inbound: mpsc::UnboundedSender<Bytes>,
+        disconnections: mpsc::UnboundedSender<String>,
+        opened: oneshot::Sender<Result<(), String>>,
+    ) -> Result<Self, Error> {
+        let websocket = WebSocket::new_with_str(url, protocol).map_err(browser_error)?;
+        websocket.set_binary_type(BinaryType::Arraybuffer);
+
+        // Taken by whichever comes first, the open or the close event
+        let opened = Rc::new(RefCell::new(Some(opened)));
+
+        let on_open = Closure::<dyn FnMut(Event)>::new({
+            let opened = opened.clone();
+            move |_| {
+                if let Some(opened) = opened.borrow_mut().take() {
+                    let _ = opened.send(Ok(()));
+                }
+            }
+        });
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            if let Some(text) = event.data().as_string() {
+                let _ = inbound.send(text.into());
+            }
+        });
+        let on_error = Closure::<dyn FnMut(Event)>::new(|_| {
+            // The browser doesn't tell what went wrong, the close event that follows has a code at least
+            tracing::warn!("WebSocket error");
+        });
+        let on_close = Closure::<dyn FnMut(CloseEvent)>::new(move |event: CloseEvent| {
+            let reason = format!(
+                "Connection closed {{ code: {}, reason: {} }}",
+                event.code(),
+                event.reason()
+            );
+            match opened.borrow_mut().take() {
+                Some(opened) => {
+                    let _ = opened.send(Err(reason));
+                }
+                None => {
+                    tracing::warn!("{reason}");
+                    let _ = disconnections.send(reason);
+                }
+            }
+        });
+
+        websocket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        websocket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        websocket.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+        websocket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        Ok(Self {
+            websocket,
+            _on_open: on_open,
+            _on_message: on_message,
+            _on_error: on_error,
+            _on_close: on_close,
+        })
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        // The handlers are about to be freed, the browser must not call them anymore
+        self.websocket.set_onopen(None);
+        self.websocket.set_onmessage(None);
+        self.websocket.set_onerror(None);
+        self.websocket.set_onclose(None);
+        let _ = self.websocket.close();
+    }
+}
+
+#[derive(Debug)]
+pub struct WebSocketClient {
+    url: Option<String>,
+    protocol: String,
+    writer_config: WebSocketWriterConfig,
+    sender: Option<WebSocketSender>,
+    socket: Option<SendWrapper<Socket>>,
+    writer: Option<JaTask>,
+    inbound: Option<mpsc::UnboundedSender<Bytes>>,
+    disconnections: Option<mpsc::UnboundedSender<String>>,
+}
+
+impl Default for WebSocketClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebSocketClient {
+    pub fn new() -> Self {
+        Self {
+            url: None,
+            protocol: "janus-protocol".to_string(),
+            writer_config: WebSocketWriterConfig::default(),
+            sender: None,
+            socket: None,
+            writer: None,
+            inbound: None,
+            disconnections: None,
+        }
+    }
+
+    /// Size of the queue of the frames to write, the browser buffers and writes them on its own
+    pub(crate) fn with_writer_config(mut self, writer_config: WebSocketWriterConfig) -> Self {
+        self.writer_config = writer_config;
+        self
+    }
+
+    /// The browser doesn't let the handshake headers be set, they're ignored
+    pub(crate) fn with_headers(self, headers: HeaderMap) -> Self {
+        if !headers.is_empty() {
+            tracing::warn!(
+                "The browser doesn't allow setting the handshake headers, ignoring them"
+            );
+        }
+        self
+    }
+
+    /// The browser verifies the certificates on its own, the TLS settings are ignored
+    pub(crate) fn with_tls(self, tls: TlsConfig) -> Self {
+        if tls != TlsConfig::default() {
+            tracing::warn!("The browser doesn't allow configuring TLS, ignoring the TLS settings");
+        }
+        self
+    }
+
+    /// The browser goes through its own proxy settings
+    pub(crate) fn with_proxy(self, _: ProxyConfig) -> Self {
+        self
+    }
+
+    /// The browser offers `permessage-deflate` on its own
+    pub(crate) fn with_deflate(self, _: Option<DeflateConfig>) -> Self {
+        self
+    }
+
+    /// The browser doesn't let pings be sent, the heartbeat is ignored
+    pub(crate) fn with_heartbeat(self, heartbeat: Option<HeartbeatConfig>) -> Self {
+        if heartbeat.is_some() {
+            tracing::warn!("The browser doesn't allow sending pings, ignoring the heartbeat");
+        }
+        self
+    }
+
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    pub(crate) async fn connect(&mut self, url: &str) -> Result<WebSocketReceivers, Error> {
+        self.connect_with_protocol(url, "janus-protocol").await
+    }
+
+    /// Connects using the given subprotocol, e.g. `janus-admin-protocol` for the admin api
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    pub(crate) async fn connect_with_protocol(
+        &mut self,
+        url: &str,
+        protocol: &str,
+    ) -> Result<WebSocketReceivers, Error> {
+        let (inbound_tx, inbound) = mpsc::unbounded_channel();
+        let (disconnections_tx, disconnections) = mpsc::unbounded_channel();
+        let (outbound, outbound_rx) = mpsc::channel(self.writer_config.queue.max(1));
+        let writer = jarust_rt::spawn("WebSocket outgoing messages", write_frames(outbound_rx));
+        if let Some(writer) = self.writer.replace(writer) {
+            writer.cancel();
+        }
+        self.sender = Some(WebSocketSender { outbound });
+        self.url = Some(url.to_string());
+        self.protocol = protocol.to_string();
+        self.inbound = Some(inbound_tx);
+        self.disconnections = Some(disconnections_tx);
+        self.open().await?;
+        Ok(WebSocketReceivers {
+            inbound,
+            disconnections,
+        })
+    }
+
+    /// Opens a new socket to the previously connected url, messages keep flowing to the same receivers.
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    pub(crate) async fn reconnect(&mut self) -> Result<(), Error> {
+        self.open().await
+    }
+
+    async fn open(&mut self) -> Result<(), Error> {
+        let (Some(url), Some(inbound), Some(disconnections), Some(outbound)) =
+            (&self.url, &self.inbound, &self.disconnections, &self.sender)
+        else {
+            tracing::error!("Transport not opened!");
+            return Err(Error::TransportNotOpened);
+        };
+
+        tracing::debug!("Connecting to {url}");
+        let (opened_tx, opened) = oneshot::channel();
+        let socket = SendWrapper::new(Socket::open(
+            url,
+            &self.protocol,
+            inbound.clone(),
+            disconnections.clone(),
+            opened_tx,
+        )?);
+        let websocket = SendWrapper::new(socket.websocket.clone());
+        let outbound = outbound.clone();
+        // Closes the previous socket, if any, without reporting it as dropped
+        self.socket = Some(socket);
+
+        match opened.await {
+            Ok(Ok(())) => outbound.enqueue(Outbound::Attach(websocket)).await,
+            Ok(Err(reason)) => Err(Error::BrowserWebSocket { reason }),
+            Err(_) => Err(Error::TransportNotOpened),
+        }
+    }
+
+    /// Returns a sender enqueuing to this client's writer, it keeps working across reconnections
+    pub(crate) fn sender(&self) -> Option<WebSocketSender> {
+        self.sender.clone()
+    }
+
+    /// Enqueues a frame, it's written by the writer task in the order it was sent
+    pub async fn send(&self, data: &[u8], _: &str) -> Result<(), Error> {
+        let Some(sender) = &self.sender else {
+            tracing::error!("Transport not opened!");
+            return Err(Error::TransportNotOpened);
+        };
+        sender.send(data).await
+    }
+}
+
+/// Writes the enqueued frames to the current socket.
+///
+/// Frames are never carried over to another socket, those sent while there's no socket to write to are dropped.
+async fn write_frames(mut outbound: mpsc::Receiver<Outbound>) {
+    let mut socket: Option<SendWrapper<WebSocket>> = None;
+    while let Some(item) = outbound.recv().await {
+        match item {
+            Outbound::Frame(frame) => {
+                let Some(writer) = &socket else {
+                    tracing::warn!("Not connected, dropping a frame");
+                    continue;
+                };
+                if let Err(what) = writer.send_with_u8_array(&frame) {
+                    // The close event reports the dropped connection, a new socket is attached once reconnected
+                    tracing::warn!("Failed to write to the socket: {}", browser_error(what));
+                    socket = None;
+                }
+            }
+            Outbound::Attach(attached) => socket = Some(attached),
+        }
+    }
+}
+
+fn browser_error(what: JsValue) -> Error {
+    let reason = what
+        .dyn_ref::<js_sys::Error>()
+        .map(|error| String::from(error.message()))
+        .or_else(|| what.as_string())
+        .unwrap_or_else(|| format!("{what:?}"));
+    Error::BrowserWebSocket { reason }
+}
+
+impl Drop for WebSocketClient {
+    #[tracing::instrument(parent = None, level = tracing::Level::TRACE, skip(self))]
+    fn drop(&mut self) {
+        if self.socket.take().is_some() {
+            tracing::debug!("Dropping wss transport");
+        }
+        if let Some(writer) = self.writer.take() {
+            writer.cancel();
+        }
+    }
+}
@@ -1,21 +1,91 @@
+use crate::janus_interface::DeflateConfig;
+use crate::janus_interface::HeartbeatConfig;
+use crate::janus_interface::ProxyConfig;
+use crate::janus_interface::TlsConfig;
+use crate::janus_interface::WebSocketWriterConfig;
 use crate::websocket::connector;
+use crate::websocket::connector::ConnectConfig;
+use crate::websocket::connector::Socket;
+use crate::websocket::deflate::CompressionCounters;
+use crate::websocket::deflate::Deflate;
+use crate::websocket::deflate::Deflater;
 use crate::Error;
 use bytes::Bytes;
 use futures_util::stream::SplitSink;
+use futures_util::stream::SplitStream;
 use futures_util::stream::StreamExt;
 use futures_util::SinkExt;
 use jarust_rt::JaTask;
-use tokio::net::TcpStream;
+use reqwest::header::HeaderMap;
 use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio::time::Interval;
+use tokio::time::MissedTickBehavior;
 use tokio_tungstenite::tungstenite::client::IntoClientRequest;
 use tokio_tungstenite::tungstenite::Message;
-use tokio_tungstenite::MaybeTlsStream;
 use tokio_tungstenite::WebSocketStream;
 
+type WebSocketSink = SplitSink<WebSocketStream<Socket>, Message>;
+type WebSocketSource = SplitStream<WebSocketStream<Socket>>;
+
+/// Receiving ends of a websocket client, they outlive the underlying socket so they keep working across reconnections.
+pub(crate) struct WebSocketReceivers {
+    /// Incoming text messages
+    pub(crate) inbound: mpsc::UnboundedReceiver<Bytes>,
+    /// The reason of each dropped connection
+    pub(crate) disconnections: mpsc::UnboundedReceiver<String>,
+}
+
+/// What the writer task is fed with
+enum Outbound {
+    Frame(Message),
+    /// The sink of a freshly opened socket, replacing the previous one, along with its compressor if any
+    Attach(WebSocketSink, Option<Deflater>),
+    /// Closes the current socket, e.g. when its peer is deemed dead
+    Close,
+}
+
+/// Enqueues the frames of a websocket client, the writer task writes them in order.
+///
+/// Cloning the sender is cheap, the clones feed the same writer, which keeps its queue across reconnections.
+#[derive(Clone, Debug)]
+pub(crate) struct WebSocketSender {
+    outbound: mpsc::Sender<Outbound>,
+}
+
+impl WebSocketSender {
+    /// Enqueues a frame without waiting for it to be written, unless the queue is full
+    pub(crate) async fn send(&self, data: &[u8]) -> Result<(), Error> {
+        let item = Message::Binary(data.to_vec().into());
+        self.enqueue(Outbound::Frame(item)).await
+    }
+
+    async fn enqueue(&self, item: Outbound) -> Result<(), Error> {
+        self.outbound.send(item).await.map_err(|_| {
+            tracing::error!("Transport not opened!");
+            Error::TransportNotOpened
+        })
+    }
+
+    /// Enqueues unless the queue is full, so the reading end never waits on the writer
+    fn try_enqueue(&self, item: Outbound) -> bool {
+        self.outbound.try_send(item).is_ok()
+    }
+}
+
 #[derive(Debug)]
 pub struct WebSocketClient {
-    sender: Option<SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>>,
+    url: Option<String>,
+    protocol: String,
+    headers: HeaderMap,
+    connect_config: ConnectConfig,
+    writer_config: WebSocketWriterConfig,
+    heartbeat: Option<HeartbeatConfig>,
+    sender: Option<WebSocketSender>,
     task: Option<JaTask>,
+    writer: Option<JaTask>,
+    inbound: Option<mpsc::UnboundedSender<Bytes>>,
+    disconnections: Option<mpsc::UnboundedSender<String>>,
 }
 
 impl Default for WebSocketClient {
@@ -27,44 +97,289 @@ impl Default for WebSocketClient {
 impl WebSocketClient {
     pub fn new() -> Self {
         Self {
+            url: None,
+            protocol: "janus-protocol".to_string(),
+            headers: HeaderMap::new(),
+            connect_config: ConnectConfig::default(),
+            writer_config: WebSocketWriterConfig::default(),
+            heartbeat: None,
             sender: None,
             task: None,
+            writer: None,
+            inbound: None,
+            disconnections: None,
+        }
+    }
+
+    /// Batching of the frames written to the socket
+    pub(crate) fn with_writer_config(mut self, writer_config: WebSocketWriterConfig) -> Self {
+        self.writer_config = writer_config;
+        self
+    }
+
+    /// Extra headers sent on the handshake
+    pub(crate) fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Root certificates, client certificate and pin used for `wss` urls
+    pub(crate) fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.connect_config.tls = tls;
+        self
+    }
+
+    /// The proxy the socket is tunneled through
+    pub(crate) fn with_proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.connect_config.proxy = proxy;
+        self
+    }
+
+    /// Offers `permessage-deflate` when connecting, `None` keeps the messages uncompressed
+    pub(crate) fn with_deflate(mut self, deflate: Option<DeflateConfig>) -> Self {
+        self.connect_config.deflate = deflate.map(Deflate::new);
+        self
+    }
+
+    /// Pings the server and closes the connection when its pong is late, `None` disables the heartbeat
+    pub(crate) fn with_heartbeat(mut self, heartbeat: Option<HeartbeatConfig>) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    pub(crate) async fn connect(&mut self, url: &str) -> Result<WebSocketReceivers, Error> {
+        self.connect_with_protocol(url, "janus-protocol").await
+    }
+
+    /// Connects using the given subprotocol, e.g. `janus-admin-protocol` for the admin api
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    pub(crate) async fn connect_with_protocol(
+        &mut self,
+        url: &str,
+        protocol: &str,
+    ) -> Result<WebSocketReceivers, Error> {
+        let (inbound_tx, inbound) = mpsc::unbounded_channel();
+        let (disconnections_tx, disconnections) = mpsc::unbounded_channel();
+        let (outbound, outbound_rx) = mpsc::channel(self.writer_config.queue.max(1));
+        let writer = jarust_rt::spawn(
+            "WebSocket outgoing messages",
+            write_frames(outbound_rx, self.writer_config.max_batch.max(1)),
+        );
+        if let Some(writer) = self.writer.replace(writer) {
+            writer.cancel();
         }
+        self.sender = Some(WebSocketSender { outbound });
+        self.url = Some(url.to_string());
+        self.protocol = protocol.to_string();
+        self.inbound = Some(inbound_tx);
+        self.disconnections = Some(disconnections_tx);
+        self.open().await?;
+        Ok(WebSocketReceivers {
+            inbound,
+            disconnections,
+        })
     }
 
+    /// Opens a new socket to the previously connected url, messages keep flowing to the same receivers.
     #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
-    pub async fn connect(&mut self, url: &str) -> Result<mpsc::UnboundedReceiver<Bytes>, Error> {
+    pub(crate) async fn reconnect(&mut self) -> Result<(), Error> {
+        self.open().await
+    }
+
+    async fn open(&mut self) -> Result<(), Error> {
+        let (Some(url), Some(inbound), Some(disconnections), Some(outbound)) =
+            (&self.url, &self.inbound, &self.disconnections, &self.sender)
+        else {
+            tracing::error!("Transport not opened!");
+            return Err(Error::TransportNotOpened);
+        };
+
         tracing::debug!("Connecting to {url}");
-        let mut request = url.into_client_request()?;
+        let mut request = url.as_str().into_client_request()?;
         let headers = request.headers_mut();
-        headers.insert("Sec-Websocket-Protocol", "janus-protocol".parse()?);
-        let stream = connector::connect_async(request).await?;
+        headers.extend(self.headers.clone());
+        headers.insert("Sec-Websocket-Protocol", self.protocol.parse()?);
+        let (stream, deflater) = connector::connect_async(request, &self.connect_config).await?;
 
-        let (sender, mut receiver) = stream.split();
-        let (tx, rx) = mpsc::unbounded_channel();
+        let (sender, receiver) = stream.split();
+        let inbound = inbound.clone();
+        let disconnections = disconnections.clone();
+        let writer = outbound.clone();
+        let heartbeat = self.heartbeat;
 
         let task = jarust_rt::spawn("WebSocket incoming messages", async move {
-            while let Some(Ok(message)) = receiver.next().await {
-                if let Message::Text(text) = message {
-                    let _ = tx.send(text.into());
-                }
-            }
+            let reason = read_frames(receiver, &inbound, &writer, heartbeat).await;
+            tracing::warn!("{reason}");
+            let _ = disconnections.send(reason);
         });
 
-        self.sender = Some(sender);
-        self.task = Some(task);
-        Ok(rx)
+        if let Some(task) = self.task.replace(task) {
+            task.cancel();
+        }
+        outbound.enqueue(Outbound::Attach(sender, deflater)).await
     }
 
-    pub async fn send(&mut self, data: &[u8], _: &str) -> Result<(), Error> {
-        let item = Message::Binary(data.to_vec().into());
-        if let Some(sender) = &mut self.sender {
-            sender.send(item).await?;
-        } else {
+    /// Returns the counters of the compression across the connections of this client, if it's offered
+    pub(crate) fn compression_counters(&self) -> Option<CompressionCounters> {
+        let deflate = self.connect_config.deflate.as_ref()?;
+        Some(deflate.counters.clone())
+    }
+
+    /// Returns a sender enqueuing to this client's writer, it keeps working across reconnections
+    pub(crate) fn sender(&self) -> Option<WebSocketSender> {
+        self.sender.clone()
+    }
+
+    /// Enqueues a frame, it's written by the writer task in the order it was sent
+    pub async fn send(&self, data: &[u8]) -> Result<(), Error> {
+        let Some(sender) = &self.sender else {
             tracing::error!("Transport not opened!");
             return Err(Error::TransportNotOpened);
+        };
+        sender.send(data).await
+    }
+}
+
+/// Forwards the incoming text messages until the connection drops, then returns why it dropped.
+///
+/// With a heartbeat, the server is pinged every interval and a pong that's later than the deadline closes
+/// the connection, as its peer is most likely gone (e.g. a half-open connection after a NAT timeout).
+async fn read_frames(
+    mut receiver: WebSocketSource,
+    inbound: &mpsc::UnboundedSender<Bytes>,
+    writer: &WebSocketSender,
+    heartbeat: Option<HeartbeatConfig>,
+) -> String {
+    let mut pings = heartbeat.map(|heartbeat| {
+        let mut pings =
+            tokio::time::interval_at(Instant::now() + heartbeat.interval, heartbeat.interval);
+        pings.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        pings
+    });
+    let mut pong_deadline = None;
+    loop {
+        tokio::select! {
+            next = receiver.next() => match next {
+                Some(Ok(Message::Text(text))) => {
+                    let _ = inbound.send(text.into());
+                }
+                Some(Ok(Message::Pong(_))) => {
+                    tracing::trace!("Pong received");
+                    pong_deadline = None;
+                }
+                Some(Ok(Message::Close(frame))) => {
+                    break format!("Connection closed by the server: {frame:?}");
+                }
+                Some(Ok(_)) => {}
+                Some(Err(what)) => break what.to_string(),
+                None => break "Connection closed".to_string(),
+            },
+            _ = tick(&mut pings) => {
+                // A full queue means the writer is busy, the next tick pings again
+                if writer.try_enqueue(Outbound::Frame(Message::Ping(Bytes::new()))) {
+                    tracing::trace!("Ping sent");
+                }
+                if let (None, Some(heartbeat)) = (pong_deadline, heartbeat) {
+                    pong_deadline = Some(Instant::now() + heartbeat.pong_timeout);
+                }
+            }
+            _ = deadline(pong_deadline) => {
+                writer.try_enqueue(Outbound::Close);
+                break "Dead peer, no pong received in time".to_string();
+            }
         }
-        Ok(())
+    }
+}
+
+async fn tick(pings: &mut Option<Interval>) {
+    match pings {
+        Some(pings) => {
+            pings.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+async fn deadline(at: Option<Instant>) {
+    match at {
+        Some(at) => tokio::time::sleep_until(at).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Writes the enqueued frames to the current socket.
+///
+/// The frames already queued are written together, up to `max_batch`, then flushed at once. The data frames are
+/// compressed, with the compressor of the current socket, when `permessage-deflate` was negotiated.
+///
+/// Frames are never carried over to another socket: those sent while there's no socket to write to, or still
+/// batched when the socket is replaced or closed, are dropped. Their requests are failed by the disconnection, or
+/// time out, and are sent again by their callers once reconnected.
+async fn write_frames(mut outbound: mpsc::Receiver<Outbound>, max_batch: usize) {
+    let mut sink: Option<WebSocketSink> = None;
+    let mut deflater: Option<Deflater> = None;
+    let mut batch = Vec::with_capacity(max_batch);
+    while let Some(next) = outbound.recv().await {
+        let mut next = Some(next);
+        while let Some(item) = next.take() {
+            match item {
+                Outbound::Frame(_) if sink.is_none() => {
+                    tracing::warn!("Not connected, dropping a frame");
+                }
+                Outbound::Frame(frame) => batch.push(frame),
+                Outbound::Attach(attached, compressor) => {
+                    drop_batch(&mut batch);
+                    sink = Some(attached);
+                    deflater = compressor;
+                }
+                Outbound::Close => {
+                    drop_batch(&mut batch);
+                    if let Some(mut closed) = sink.take() {
+                        let _ = closed.close().await;
+                    }
+                }
+            }
+            if batch.len() < max_batch {
+                next = outbound.try_recv().ok();
+            }
+        }
+
+        let Some(writer) = &mut sink else {
+            continue;
+        };
+        if batch.is_empty() {
+            continue;
+        }
+        tracing::trace!(frames = batch.len(), "Writing frames");
+        let result = async {
+            for frame in batch.drain(..) {
+                let frame = match &mut deflater {
+                    Some(deflater) => deflater.deflate(frame),
+                    None => frame,
+                };
+                writer.feed(frame).await?;
+            }
+            writer.flush().await
+        }
+        .await;
+        if let Err(what) = result {
+            // The reading end reports the dropped connection, a new sink is attached once reconnected
+            tracing::warn!("Failed to write to the socket: {what}");
+            drop_batch(&mut batch);
+            sink = None;
+        }
+    }
+}
+
+/// Drops the frames batched for a socket that's gone
+fn drop_batch(batch: &mut Vec<Message>) {
+    if !batch.is_empty() {
+        tracing::warn!(
+            frames = batch.len(),
+            "Socket gone, dropping the batched frames"
+        );
+        batch.clear();
     }
 }
 
@@ -75,5 +390,8 @@ impl Drop for WebSocketClient {
             tracing::debug!("Dropping wss transport");
             join_handle.cancel();
         }
+        if let Some(writer) = self.writer.take() {
+            writer.cancel();
+        }
     }
 }
@@ -1,460 +1,625 @@
-use super::demuxer::Demuxer;
-use super::napmap::NapMap;
-use super::router::Router;
-use super::tmanager::TransactionManager;
 use super::websocket_client::WebSocketClient;
-use crate::handle_msg::HandleMessage;
-use crate::handle_msg::HandleMessageWithJsep;
+use super::websocket_client::WebSocketReceivers;
+use super::websocket_client::WebSocketSender;
+#[cfg(not(target_family = "wasm"))]
+use crate::compression_metrics::CompressionMetrics;
+use crate::connection_state::ConnectionStateTracker;
+use crate::demuxed_interface::DemuxedInterface;
+use crate::demuxed_interface::DemuxedTransport;
+use crate::demuxed_interface::TransportReceivers;
 use crate::janus_interface::ConnectionParams;
-use crate::janus_interface::JanusInterface;
-use crate::japrotocol::JaResponse;
-use crate::japrotocol::JaSuccessProtocol;
-use crate::japrotocol::ResponseType;
-use crate::japrotocol::ServerInfoRsp;
-use crate::tgenerator::GenerateTransaction;
-use crate::tgenerator::TransactionGenerator;
+#[cfg(not(target_family = "wasm"))]
+use crate::websocket::deflate::CompressionCounters;
 use crate::Error;
-use jarust_rt::JaTask;
-use serde_json::json;
-use serde_json::Value;
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::mpsc;
 use tokio::sync::Mutex;
 
-#[derive(Debug)]
-struct Shared {
-    tasks: Vec<JaTask>,
-    server_root: String,
-    apisecret: Option<String>,
-    transaction_generator: TransactionGenerator,
-    ack_map: Arc<NapMap<String, JaResponse>>,
-    rsp_map: Arc<NapMap<String, JaResponse>>,
-}
-
-#[derive(Debug)]
-struct Exclusive {
-    router: Router,
-    ws: WebSocketClient,
-    transaction_manager: TransactionManager,
-}
+/// Talks to janus over a websocket, reconnecting and claiming back the live sessions when configured to.
+pub type WebSocketInterface = DemuxedInterface<WebSocketTransport>;
 
+/// The websocket under a [`WebSocketInterface`]
 #[derive(Debug)]
-struct InnerWebSocketInterface {
-    shared: Shared,
-    exclusive: Mutex<Exclusive>,
+pub struct WebSocketTransport {
+    /// Feeds the writer task of the socket, it keeps working across reconnections
+    sender: WebSocketSender,
+    client: Mutex<WebSocketClient>,
+    /// Bytes in and out of the compression, if it's offered. The browser doesn't expose them
+    #[cfg(not(target_family = "wasm"))]
+    compression: Option<CompressionCounters>,
 }
 
-#[derive(Debug, Clone)]
-pub struct WebSocketInterface {
-    inner: Arc<InnerWebSocketInterface>,
-}
+#[async_trait::async_trait]
+impl DemuxedTransport for WebSocketTransport {
+    const NAME: &'static str = "WebSocket Interface";
 
-impl WebSocketInterface {
     #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
-    pub async fn send(&self, message: Value) -> Result<String, Error> {
-        let (message, transaction) = self.decorate_request(message);
-
-        let path =
-            Router::path_from_request(&message).unwrap_or(self.inner.shared.server_root.clone());
-
-        let mut guard = self.inner.exclusive.lock().await;
-        guard.transaction_manager.insert(&transaction, &path).await;
-        guard.ws.send(message.to_string().as_bytes(), &path).await?;
-        tracing::trace!("Sending {message:#?}");
-        Ok(transaction)
+    async fn connect(
+        conn_params: &ConnectionParams,
+        _: &ConnectionStateTracker,
+    ) -> Result<(Self, TransportReceivers), Error> {
+        let mut client = WebSocketClient::new()
+            .with_headers(conn_params.headers.header_map()?)
+            .with_tls(conn_params.tls.clone())
+            .with_proxy(conn_params.proxy.clone())
+            .with_deflate(conn_params.deflate)
+            .with_writer_config(conn_params.writer)
+            .with_heartbeat(conn_params.heartbeat);
+        let WebSocketReceivers {
+            inbound,
+            disconnections,
+        } = match &conn_params.headers.subprotocol {
+            Some(subprotocol) => {
+                client
+                    .connect_with_protocol(&conn_params.url, subprotocol)
+                    .await?
+            }
+            None => client.connect(&conn_params.url).await?,
+        };
+        let sender = client.sender().ok_or(Error::TransportNotOpened)?;
+        let transport = Self {
+            sender,
+            #[cfg(not(target_family = "wasm"))]
+            compression: client.compression_counters(),
+            client: Mutex::new(client),
+        };
+        let receivers = TransportReceivers {
+            inbound,
+            disconnections: Some(disconnections),
+        };
+        Ok((transport, receivers))
     }
 
-    #[tracing::instrument(level = tracing::Level::TRACE, skip(self, timeout))]
-    async fn poll_response(
-        &self,
-        transaction: &str,
-        timeout: Duration,
-    ) -> Result<JaResponse, Error> {
-        tracing::trace!("Polling response");
-        match tokio::time::timeout(
-            timeout,
-            self.inner.shared.rsp_map.get(transaction.to_string()),
-        )
-        .await
-        {
-            Ok(Some(response)) => match response.janus {
-                ResponseType::Error { error } => Err(Error::JanusError {
-                    code: error.code,
-                    reason: error.reason,
-                }),
-                _ => Ok(response),
-            },
-            Ok(None) => {
-                tracing::error!("Incomplete packet");
-                Err(Error::IncompletePacket)
-            }
-            Err(_) => {
-                tracing::error!("Request timeout");
-                Err(Error::RequestTimeout)
-            }
-        }
+    async fn send(&self, data: &[u8]) -> Result<(), Error> {
+        self.sender.send(data).await
     }
 
-    #[tracing::instrument(level = tracing::Level::TRACE, skip(self, timeout))]
-    async fn poll_ack(&self, transaction: &str, timeout: Duration) -> Result<JaResponse, Error> {
-        tracing::trace!("Polling ack");
-        match tokio::time::timeout(
-            timeout,
-            self.inner.shared.ack_map.get(transaction.to_string()),
-        )
-        .await
-        {
-            Ok(Some(response)) => match response.janus {
-                ResponseType::Error { error } => Err(Error::JanusError {
-                    code: error.code,
-                    reason: error.reason,
-                }),
-                _ => Ok(response),
-            },
-            Ok(None) => {
-                tracing::error!("Incomplete packet");
-                Err(Error::IncompletePacket)
-            }
-            Err(_) => {
-                tracing::error!("Request timeout");
-                Err(Error::RequestTimeout)
-            }
-        }
+    async fn reconnect(&self) -> Result<(), Error> {
+        self.client.lock().await.reconnect().await
     }
 
-    fn decorate_request(&self, mut request: Value) -> (Value, String) {
-        let transaction = self
-            .inner
-            .shared
-            .transaction_generator
-            .generate_transaction();
-        if let Some(apisecret) = self.inner.shared.apisecret.clone() {
-            request["apisecret"] = apisecret.into();
-        };
-        request["transaction"] = transaction.clone().into();
-        (request, transaction)
+    #[cfg(not(target_family = "wasm"))]
+    fn compression_metrics(&self) -> Option<CompressionMetrics> {
+        let compression = self.compression.as_ref()?;
+        Some(compression.metrics())
     }
 }
 
-#[async_trait::async_trait]
-impl JanusInterface for WebSocketInterface {
-    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
-    async fn make_interface(
-        conn_params: ConnectionParams,
-        transaction_generator: impl GenerateTransaction,
-    ) -> Result<Self, Error> {
-        tracing::debug!("Creating WebSocket Interface");
-        let router = Router::new(&conn_params.server_root);
-        let mut websocket = WebSocketClient::new();
-        let receiver = websocket.connect(&conn_params.url).await?;
-        let transaction_manager = TransactionManager::new(conn_params.capacity);
-        let transaction_generator = TransactionGenerator::new(transaction_generator);
-
-        let ack_map = Arc::new(NapMap::<String, JaResponse>::new(conn_params.capacity));
-        let rsp_map = Arc::new(NapMap::<String, JaResponse>::new(conn_params.capacity));
-
-        let (rsp_sender, mut rsp_receiver) = mpsc::unbounded_channel::<JaResponse>();
-        let (ack_sender, mut ack_receiver) = mpsc::unbounded_channel::<JaResponse>();
-
-        let rsp_task = jarust_rt::spawn("Responses gathering task", {
-            let rsp_map = rsp_map.clone();
-            async move {
-                while let Some(rsp) = rsp_receiver.recv().await {
-                    if let Some(transaction) = rsp.transaction.clone() {
-                        rsp_map.insert(transaction, rsp).await;
-                    }
-                }
-            }
+#[cfg(all(test, not(target_family = "wasm")))]
+mod tests {
+    use super::WebSocketInterface;
+    use crate::backoff::Backoff;
+    use crate::connection_state::ConnectionState;
+    use crate::janus_interface::ConnectionParams;
+    use crate::janus_interface::DeflateConfig;
+    use crate::janus_interface::HeadersConfig;
+    use crate::janus_interface::HeartbeatConfig;
+    use crate::janus_interface::JanusInterface;
+    use crate::janus_interface::ReconnectConfig;
+    use crate::janus_interface::WebSocketWriterConfig;
+    use crate::japrotocol::GenericEvent;
+    use crate::japrotocol::JaHandleEvent;
+    use crate::japrotocol::ResponseType;
+    use crate::tgenerator::RandomTransactionGenerator;
+    use crate::token_provider::TokenProvider;
+    use crate::token_provider::TokenProviderImpl;
+    use crate::websocket::deflate::Deflate;
+    use crate::websocket::deflate::InflatingStream;
+    use crate::websocket::handshake::Accept;
+    use crate::Error;
+    use futures_util::SinkExt;
+    use futures_util::StreamExt;
+    use serde_json::json;
+    use serde_json::Value;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use tokio::net::TcpStream;
+    use tokio::sync::mpsc;
+    use tokio::sync::oneshot;
+    use tokio_tungstenite::tungstenite::handshake::server::Request;
+    use tokio_tungstenite::tungstenite::handshake::server::Response;
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::WebSocketStream;
+
+    const SESSION_ID: u64 = 1;
+    const HANDLE_ID: u64 = 2;
+
+    async fn accept(listener: &TcpListener) -> WebSocketStream<TcpStream> {
+        let (stream, _) = listener.accept().await.unwrap();
+        let callback = Accept(|_: &Request, response: &mut Response| {
+            response
+                .headers_mut()
+                .insert("Sec-Websocket-Protocol", "janus-protocol".parse().unwrap());
         });
+        tokio_tungstenite::accept_hdr_async(stream, callback)
+            .await
+            .unwrap()
+    }
 
-        let ack_task = jarust_rt::spawn("ACKs gathering task", {
-            let ack_map = ack_map.clone();
-            async move {
-                while let Some(rsp) = ack_receiver.recv().await {
-                    if let Some(transaction) = rsp.transaction.clone() {
-                        ack_map.insert(transaction, rsp).await;
-                    }
-                }
-            }
-        });
+    async fn next_request(ws: &mut WebSocketStream<TcpStream>) -> Value {
+        let message = ws.next().await.unwrap().unwrap();
+        serde_json::from_slice(&message.into_data()).unwrap()
+    }
 
-        let demux_task = jarust_rt::spawn("Demultiplexing task", {
-            let router = router.clone();
-            let transaction_manager = transaction_manager.clone();
-            let demuxer = Demuxer {
-                inbound_stream: receiver,
-                router,
-                rsp_sender,
-                ack_sender,
-                transaction_manager,
-            };
-            async move { demuxer.start().await }
-        });
+    async fn reply(ws: &mut WebSocketStream<TcpStream>, response: Value) {
+        ws.send(Message::Text(response.to_string().into()))
+            .await
+            .unwrap();
+    }
 
-        let shared = Shared {
-            tasks: vec![demux_task, rsp_task, ack_task],
-            server_root: conn_params.server_root,
-            apisecret: conn_params.apisecret,
-            transaction_generator,
-            ack_map,
-            rsp_map,
-        };
-        let exclusive = Exclusive {
-            router,
-            ws: websocket,
-            transaction_manager,
-        };
-        let inner = InnerWebSocketInterface {
-            shared,
-            exclusive: Mutex::new(exclusive),
-        };
-        let this = Self {
-            inner: Arc::new(inner),
-        };
-        Ok(this)
+    fn server_info(transaction: &Value) -> Value {
+        json!({
+            "janus": "server_info",
+            "transaction": transaction,
+            "name": "Janus WebRTC Server",
+            "version": 1,
+            "version_string": "1.0.0",
+            "author": "Meetecho s.r.l.",
+            "commit-hash": "",
+            "compile-time": "",
+            "log-to-stdout": true,
+            "log-to-file": false,
+            "data_channels": true,
+            "accepting-new-sessions": true,
+            "session-timeout": 60,
+            "reclaim-session-timeout": 30,
+            "candidates-timeout": 45,
+            "server-name": "Fake",
+            "local-ip": "127.0.0.1",
+            "ipv6": false,
+            "ice-lite": false,
+            "ice-tcp": false,
+            "ice-nomination": "regular",
+            "ice-keepalive-conncheck": false,
+            "full-trickle": false,
+            "mdns-enabled": false,
+            "min-nack-queue": 200,
+            "twcc-period": 200,
+            "dtls-mtu": 1200,
+            "static-event-loops": 0,
+            "api_secret": false,
+            "auth_token": false,
+            "event_handlers": false,
+            "opaqueid_in_api": false,
+            "dependencies": {},
+            "transports": {},
+            "plugins": {}
+        })
     }
 
-    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
-    async fn create(&self, timeout: Duration) -> Result<u64, Error> {
-        let request = json!({
-            "janus": "create"
+    #[tokio::test]
+    async fn it_should_reconnect_and_reclaim_sessions() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (claims_tx, mut claims_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            let mut ws = accept(&listener).await;
+            let request = next_request(&mut ws).await;
+            reply(&mut ws, json!({"janus": "success", "transaction": request["transaction"], "data": {"id": SESSION_ID}})).await;
+            let request = next_request(&mut ws).await;
+            reply(&mut ws, json!({"janus": "success", "transaction": request["transaction"], "session_id": SESSION_ID, "data": {"id": HANDLE_ID}})).await;
+            drop(ws);
+
+            let mut ws = accept(&listener).await;
+            let request = next_request(&mut ws).await;
+            assert_eq!(request["janus"], "info");
+            reply(&mut ws, server_info(&request["transaction"])).await;
+            let request = next_request(&mut ws).await;
+            assert_eq!(request["janus"], "claim");
+            claims_tx.send(request["session_id"].as_u64()).unwrap();
+            reply(&mut ws, json!({"janus": "success", "transaction": request["transaction"], "session_id": SESSION_ID})).await;
+            reply(
+                &mut ws,
+                json!({"janus": "webrtcup", "session_id": SESSION_ID, "sender": HANDLE_ID}),
+            )
+            .await;
+            std::future::pending::<()>().await;
         });
 
-        let transaction = self.send(request).await?;
-        let response = self.poll_response(&transaction, timeout).await?;
-        let session_id = match response.janus {
-            ResponseType::Success(JaSuccessProtocol::Data { data }) => data.id,
-            ResponseType::Error { error } => {
-                let what = Error::JanusError {
-                    code: error.code,
-                    reason: error.reason,
-                };
-                tracing::error!("{what}");
-                return Err(what);
-            }
-            _ => {
-                tracing::error!("Unexpected response");
-                return Err(Error::UnexpectedResponse);
-            }
+        let conn_params = ConnectionParams {
+            url,
+            reconnect: Some(ReconnectConfig {
+                backoff: Backoff {
+                    initial_delay: Duration::from_millis(10),
+                    max_delay: Duration::from_millis(100),
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
         };
-        Ok(session_id)
+        let interface = WebSocketInterface::make_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap();
+        let state = interface.connection_state().unwrap();
+        let mut transitions = state.transitions();
+        let timeout = Duration::from_secs(5);
+        let session_id = interface.create(timeout).await.unwrap();
+        let (_, mut events) = interface
+            .attach(
+                session_id,
+                "janus.plugin.echotest".to_string(),
+                None,
+                timeout,
+            )
+            .await
+            .unwrap();
+
+        let claimed = tokio::time::timeout(timeout, claims_rx.recv())
+            .await
+            .unwrap();
+        assert_eq!(claimed, Some(Some(SESSION_ID)));
+
+        let event = tokio::time::timeout(timeout, events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            event.janus,
+            ResponseType::Event(JaHandleEvent::GenericEvent(GenericEvent::WebrtcUp))
+        );
+
+        assert_eq!(state.state(), ConnectionState::Connected);
+        let visited = [
+            transitions.recv().await.unwrap().to,
+            transitions.recv().await.unwrap().to,
+            transitions.recv().await.unwrap().to,
+        ];
+        assert_eq!(
+            visited,
+            [
+                ConnectionState::Disconnected,
+                ConnectionState::Connecting,
+                ConnectionState::Connected
+            ]
+        );
     }
 
-    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
-    async fn server_info(&self, timeout: Duration) -> Result<ServerInfoRsp, Error> {
-        let request = json!({
-            "janus": "info"
+    #[tokio::test]
+    async fn it_should_not_carry_frames_over_to_the_next_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (accept_tx, accept_rx) = oneshot::channel();
+
+        let server = tokio::spawn(async move {
+            let mut ws = accept(&listener).await;
+            let request = next_request(&mut ws).await;
+            reply(&mut ws, json!({"janus": "success", "transaction": request["transaction"], "data": {"id": SESSION_ID}})).await;
+            drop(ws);
+
+            accept_rx.await.unwrap();
+            let mut ws = accept(&listener).await;
+            let mut received = Vec::new();
+            loop {
+                let request = next_request(&mut ws).await;
+                match request["janus"].as_str().unwrap() {
+                    "info" => reply(&mut ws, server_info(&request["transaction"])).await,
+                    "claim" => reply(&mut ws, json!({"janus": "success", "transaction": request["transaction"], "session_id": SESSION_ID})).await,
+                    "keepalive" => return received,
+                    janus => received.push(janus.to_string()),
+                }
+            }
         });
-        let transaction = self.send(request).await?;
-        let response = self.poll_response(&transaction, timeout).await?;
-        match response.janus {
-            ResponseType::ServerInfo(info) => Ok(*info),
-            ResponseType::Error { error } => Err(Error::JanusError {
-                code: error.code,
-                reason: error.reason,
+
+        let conn_params = ConnectionParams {
+            url,
+            reconnect: Some(ReconnectConfig {
+                backoff: Backoff {
+                    initial_delay: Duration::from_millis(10),
+                    max_delay: Duration::from_millis(100),
+                },
+                ..Default::default()
             }),
-            _ => Err(Error::IncompletePacket),
+            ..Default::default()
+        };
+        let interface = WebSocketInterface::make_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap();
+        let mut transitions = interface.connection_state().unwrap().transitions();
+        let timeout = Duration::from_secs(5);
+        interface.create(timeout).await.unwrap();
+
+        // The server holds back the next socket, the messages are sent while reconnecting
+        while transitions.recv().await.unwrap().to != ConnectionState::Connecting {}
+        for _ in 0..3 {
+            interface
+                .send(json!({"janus": "message", "session_id": SESSION_ID, "handle_id": HANDLE_ID, "body": {}}))
+                .await
+                .unwrap();
         }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        accept_tx.send(()).unwrap();
+        while transitions.recv().await.unwrap().to != ConnectionState::Connected {}
+        interface
+            .send(json!({"janus": "keepalive", "session_id": SESSION_ID}))
+            .await
+            .unwrap();
+
+        let received = tokio::time::timeout(timeout, server)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(received.is_empty(), "{received:?}");
     }
 
-    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
-    async fn attach(
-        &self,
-        session_id: u64,
-        plugin_id: String,
-        timeout: Duration,
-    ) -> Result<(u64, mpsc::UnboundedReceiver<JaResponse>), Error> {
-        let request = json!({
-            "janus": "attach",
-            "session_id": session_id,
-            "plugin": plugin_id
+    #[tokio::test]
+    async fn it_should_fail_requests_in_flight_when_the_connection_drops() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let mut ws = accept(&listener).await;
+            next_request(&mut ws).await;
+            drop(ws);
         });
-        let transaction = self.send(request).await?;
-        let response = self.poll_response(&transaction, timeout).await?;
-        let handle_id = match response.janus {
-            ResponseType::Success(JaSuccessProtocol::Data { data }) => data.id,
-            ResponseType::Error { error } => {
-                let what = Error::JanusError {
-                    code: error.code,
-                    reason: error.reason,
-                };
-                tracing::error!("{what}");
-                return Err(what);
-            }
-            _ => {
-                tracing::error!("Unexpected response");
-                return Err(Error::UnexpectedResponse);
-            }
+
+        let conn_params = ConnectionParams {
+            url,
+            ..Default::default()
         };
-        let receiver = self
-            .inner
-            .exclusive
-            .lock()
+        let interface = WebSocketInterface::make_interface(conn_params, RandomTransactionGenerator)
             .await
-            .router
-            .add_subroute(&format!("{session_id}/{handle_id}"))
-            .await;
-        Ok((handle_id, receiver))
+            .unwrap();
+        let result = interface.create(Duration::from_secs(5)).await;
+        assert!(matches!(result, Err(Error::ConnectionLost)));
     }
 
-    fn has_keep_alive(&self) -> bool {
-        true
-    }
+    #[tokio::test]
+    async fn it_should_write_the_enqueued_requests_in_order() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
 
-    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
-    async fn keep_alive(&self, session_id: u64, timeout: Duration) -> Result<(), Error> {
-        let request = json!({
-            "janus": "keepalive",
-            "session_id": session_id
+        let server = tokio::spawn(async move {
+            let mut ws = accept(&listener).await;
+            let mut indexes = Vec::new();
+            for _ in 0..50 {
+                indexes.push(next_request(&mut ws).await["index"].as_u64().unwrap());
+            }
+            indexes
         });
-        let transaction = self.send(request).await?;
-        self.poll_ack(&transaction, timeout).await?;
-        Ok(())
-    }
 
-    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
-    async fn destroy(&self, session_id: u64, timeout: Duration) -> Result<(), Error> {
-        let request = json!({
-            "janus": "destroy",
-            "session_id": session_id
-        });
-        let transaction = self.send(request).await?;
-        self.poll_response(&transaction, timeout).await?;
-        Ok(())
-    }
+        let conn_params = ConnectionParams {
+            url,
+            writer: WebSocketWriterConfig {
+                queue: 4,
+                max_batch: 8,
+            },
+            ..Default::default()
+        };
+        let interface = WebSocketInterface::make_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap();
+        for index in 0..50 {
+            interface
+                .send(json!({"janus": "keepalive", "session_id": SESSION_ID, "index": index}))
+                .await
+                .unwrap();
+        }
 
-    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
-    async fn fire_and_forget_msg(&self, message: HandleMessage) -> Result<String, Error> {
-        let request = json!({
-            "janus": "message",
-            "session_id": message.session_id,
-            "handle_id": message.handle_id,
-            "body": message.body
-        });
-        let transaction = self.send(request).await?;
-        Ok(transaction)
+        let indexes = tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(indexes, (0..50).collect::<Vec<_>>());
     }
 
-    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
-    async fn send_msg_waiton_ack(
-        &self,
-        message: HandleMessage,
-        timeout: Duration,
-    ) -> Result<String, Error> {
-        let request = json!({
-            "janus": "message",
-            "session_id": message.session_id,
-            "handle_id": message.handle_id,
-            "body": message.body
+    #[tokio::test]
+    async fn it_should_not_track_the_keep_alives() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let mut ws = accept(&listener).await;
+            let request = next_request(&mut ws).await;
+            reply(&mut ws, json!({"janus": "success", "transaction": request["transaction"], "data": {"id": SESSION_ID}})).await;
+            while let Some(Ok(message)) = ws.next().await {
+                let request: Value = serde_json::from_slice(&message.into_data()).unwrap();
+                reply(&mut ws, json!({"janus": "ack", "transaction": request["transaction"], "session_id": SESSION_ID})).await;
+            }
         });
-        let transaction = self.send(request).await?;
-        self.poll_ack(&transaction, timeout).await?;
-        Ok(transaction)
+
+        let conn_params = ConnectionParams {
+            url,
+            capacity: 4,
+            ..Default::default()
+        };
+        let interface = WebSocketInterface::make_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap();
+        let timeout = Duration::from_secs(5);
+        let session_id = interface.create(timeout).await.unwrap();
+        for _ in 0..10 {
+            interface.keep_alive(session_id, timeout).await.unwrap();
+        }
+
+        let metrics = interface.transaction_metrics().unwrap();
+        assert_eq!(metrics.in_flight, 0);
+        assert_eq!(metrics.peak_in_flight, 1);
+        assert_eq!(metrics.over_capacity, 0);
     }
 
-    async fn internal_send_msg_waiton_rsp(
-        &self,
-        message: HandleMessage,
-        timeout: Duration,
-    ) -> Result<JaResponse, Error> {
-        let request = json!({
-            "janus": "message",
-            "session_id": message.session_id,
-            "handle_id": message.handle_id,
-            "body": message.body
+    #[tokio::test]
+    async fn it_should_close_the_connection_when_the_pong_is_late() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (alive_tx, mut alive_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            // The peer answers the pings as long as it reads, then goes silent without closing the socket
+            let mut ws = accept(&listener).await;
+            tokio::select! {
+                _ = async { while ws.next().await.is_some() {} } => {}
+                _ = alive_rx.recv() => {}
+            }
+            std::future::pending::<()>().await;
         });
-        let transaction = self.send(request).await?;
-        self.poll_response(&transaction, timeout).await
+
+        let conn_params = ConnectionParams {
+            url,
+            heartbeat: Some(HeartbeatConfig {
+                interval: Duration::from_millis(20),
+                pong_timeout: Duration::from_millis(50),
+            }),
+            reconnect: Some(ReconnectConfig {
+                backoff: Backoff {
+                    initial_delay: Duration::from_millis(10),
+                    max_delay: Duration::from_millis(10),
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let interface = WebSocketInterface::make_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap();
+        let state = interface.connection_state().unwrap();
+        let mut transitions = state.transitions();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(state.state(), ConnectionState::Connected);
+        assert!(transitions.try_recv().is_err());
+        alive_tx.send(()).unwrap();
+
+        let transition = tokio::time::timeout(Duration::from_secs(5), transitions.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(transition.to, ConnectionState::Disconnected);
+        assert!(transition.reason.contains("no pong"));
     }
 
-    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
-    async fn fire_and_forget_msg_with_jsep(
-        &self,
-        message: HandleMessageWithJsep,
-    ) -> Result<String, Error> {
-        let request = json!({
-            "janus": "message",
-            "session_id": message.session_id,
-            "handle_id": message.handle_id,
-            "body": message.body,
-            "jsep": message.jsep
+    #[tokio::test]
+    async fn it_should_connect_with_the_configured_headers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (headers_tx, headers_rx) = std::sync::mpsc::channel();
+            let callback = Accept(move |request: &Request, response: &mut Response| {
+                headers_tx.send(request.headers().clone()).unwrap();
+                let protocol = request.headers()["Sec-Websocket-Protocol"].clone();
+                response
+                    .headers_mut()
+                    .insert("Sec-Websocket-Protocol", protocol);
+            });
+            let _ws = tokio_tungstenite::accept_hdr_async(stream, callback)
+                .await
+                .unwrap();
+            headers_rx.recv().unwrap()
         });
-        let transaction = self.send(request).await?;
-        Ok(transaction)
+
+        let conn_params = ConnectionParams {
+            url,
+            headers: HeadersConfig {
+                extra: vec![("Authorization".to_string(), "Bearer abc".to_string())],
+                user_agent: Some("jarust-test".to_string()),
+                subprotocol: Some("custom-protocol".to_string()),
+            },
+            ..Default::default()
+        };
+        let _interface =
+            WebSocketInterface::make_interface(conn_params, RandomTransactionGenerator)
+                .await
+                .unwrap();
+
+        let headers = server.await.unwrap();
+        assert_eq!(headers["Authorization"], "Bearer abc");
+        assert_eq!(headers["User-Agent"], "jarust-test");
+        assert_eq!(headers["Sec-Websocket-Protocol"], "custom-protocol");
     }
 
-    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
-    async fn send_msg_waiton_ack_with_jsep(
-        &self,
-        message: HandleMessageWithJsep,
-        timeout: Duration,
-    ) -> Result<String, Error> {
-        let request = json!({
-            "janus": "message",
-            "session_id": message.session_id,
-            "handle_id": message.handle_id,
-            "body": message.body,
-            "jsep": message.jsep,
+    #[tokio::test]
+    async fn it_should_compress_the_messages_both_ways() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let deflate = Deflate::new(DeflateConfig::default());
+            let stream = InflatingStream::new(stream, Some(deflate.counters.clone()));
+            let callback = Accept(|request: &Request, response: &mut Response| {
+                let headers = response.headers_mut();
+                headers.insert("Sec-Websocket-Protocol", "janus-protocol".parse().unwrap());
+                let extensions = request.headers()["Sec-WebSocket-Extensions"].clone();
+                headers.insert("Sec-WebSocket-Extensions", extensions);
+            });
+            let mut ws = tokio_tungstenite::accept_hdr_async(stream, callback)
+                .await
+                .unwrap();
+            let mut deflater = deflate.deflater("permessage-deflate").unwrap();
+
+            let message = ws.next().await.unwrap().unwrap();
+            let request: Value = serde_json::from_slice(&message.into_data()).unwrap();
+            let response = json!({"janus": "success", "transaction": request["transaction"], "data": {"id": SESSION_ID}});
+            ws.send(deflater.deflate(Message::Text(response.to_string().into())))
+                .await
+                .unwrap();
+            deflate.counters.metrics()
         });
-        let transaction = self.send(request).await?;
-        self.poll_ack(&transaction, timeout).await?;
-        Ok(transaction)
-    }
 
-    async fn send_handle_request(&self, request: HandleMessage) -> Result<(), Error> {
-        let mut req = request.body;
-        merge_json(
-            &mut req,
-            &json!({
-                "session_id": request.session_id,
-                "handle_id": request.handle_id,
-            }),
-        );
-        _ = self.send(req).await?;
-        Ok(())
+        let conn_params = ConnectionParams {
+            url,
+            deflate: Some(DeflateConfig::default()),
+            ..Default::default()
+        };
+        let interface = WebSocketInterface::make_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap();
+        let session_id = interface.create(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(session_id, SESSION_ID);
+
+        let server_metrics = server.await.unwrap();
+        let metrics = interface.compression_metrics().unwrap();
+        assert!(metrics.sent_compressed > 0);
+        assert_eq!(metrics.sent_compressed, server_metrics.received_compressed);
+        assert_eq!(metrics.sent_uncompressed, server_metrics.received_inflated);
+        assert_eq!(metrics.received_compressed, server_metrics.sent_compressed);
+        assert_eq!(metrics.received_inflated, server_metrics.sent_uncompressed);
+        assert!(metrics.received_ratio().is_some());
     }
 
-    async fn send_handle_request_waiton_ack(
-        &self,
-        request: HandleMessage,
-        timeout: Duration,
-    ) -> Result<String, Error> {
-        let mut req = request.body;
-        merge_json(
-            &mut req,
-            &json!({
-                "session_id": request.session_id,
-                "handle_id": request.handle_id,
-            }),
-        );
-        let transaction = self.send(req).await?;
-        self.poll_ack(&transaction, timeout).await?;
-        Ok(transaction)
-    }
+    #[derive(Debug, Default)]
+    struct RotatingTokenProvider(AtomicUsize);
 
-    fn name(&self) -> Box<str> {
-        "WebSocket Interface".to_string().into_boxed_str()
+    #[async_trait::async_trait]
+    impl TokenProvider for RotatingTokenProvider {
+        async fn token(&self) -> Result<String, Error> {
+            let generation = self.0.fetch_add(1, Ordering::Relaxed);
+            Ok(format!("token-{generation}"))
+        }
     }
-}
 
-impl Drop for InnerWebSocketInterface {
-    fn drop(&mut self) {
-        self.shared.tasks.iter().for_each(|task| {
-            task.cancel();
+    #[tokio::test]
+    async fn it_should_attach_a_fresh_token_to_each_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let mut ws = accept(&listener).await;
+            let mut tokens = Vec::new();
+            let request = next_request(&mut ws).await;
+            tokens.push(request["token"].clone());
+            reply(&mut ws, json!({"janus": "success", "transaction": request["transaction"], "data": {"id": SESSION_ID}})).await;
+            let request = next_request(&mut ws).await;
+            tokens.push(request["token"].clone());
+            reply(&mut ws, json!({"janus": "ack", "transaction": request["transaction"], "session_id": SESSION_ID})).await;
+            tokens
         });
-    }
-}
 
-fn merge_json(a: &mut Value, b: &Value) {
-    match (a, b) {
-        (&mut Value::Object(ref mut a), Value::Object(b)) => {
-            for (k, v) in b {
-                merge_json(a.entry(k.clone()).or_insert(Value::Null), v);
-            }
-        }
-        (a, b) => {
-            *a = b.clone();
-        }
+        let conn_params = ConnectionParams {
+            url,
+            token_provider: Some(TokenProviderImpl::new(RotatingTokenProvider::default())),
+            ..Default::default()
+        };
+        let interface = WebSocketInterface::make_interface(conn_params, RandomTransactionGenerator)
+            .await
+            .unwrap();
+        let timeout = Duration::from_secs(5);
+        let session_id = interface.create(timeout).await.unwrap();
+        interface.keep_alive(session_id, timeout).await.unwrap();
+
+        let tokens = server.await.unwrap();
+        assert_eq!(tokens, vec![json!("token-0"), json!("token-1")]);
     }
 }
@@ -20,6 +20,14 @@ mod from;
 #[macro_use]
 mod make_dto;
 
+#[cfg(any(
+    feature = "echo-test",
+    feature = "audio-bridge",
+    feature = "video-room",
+    feature = "streaming"
+))]
+mod listener;
+
 #[cfg(feature = "echo-test")]
 pub mod echo_test;
 
@@ -0,0 +1,33 @@
+use jarust_core::prelude::*;
+use jarust_interface::event_channel::EventReceiver;
+use tokio::sync::mpsc;
+
+/// Forwards the events of a handle that parse as plugin events.
+///
+/// The plugin channel only holds a single event, so a slow consumer holds back the event channel of the
+/// handle, where its capacity and overflow policy apply.
+pub(crate) fn listen<H, E>(
+    name: &str,
+    handle: JaHandle,
+    mut receiver: EventReceiver<JaResponse>,
+) -> (H, mpsc::Receiver<E>)
+where
+    H: From<JaHandle> + PluginTask,
+    E: TryFrom<JaResponse> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(1);
+    let task = jarust_rt::spawn(name, async move {
+        while let Some(rsp) = receiver.recv().await {
+            let Ok(event) = E::try_from(rsp) else {
+                continue;
+            };
+            // Nobody listens anymore, dropping the receiver closes the event channel of the handle
+            if tx.send(event).await.is_err() {
+                break;
+            }
+        }
+    });
+    let mut handle: H = handle.into();
+    handle.assign_task(task);
+    (handle, rx)
+}
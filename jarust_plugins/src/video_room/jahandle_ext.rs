@@ -1,6 +1,9 @@
 use super::events::PluginEvent;
 use super::handle::VideoRoomHandle;
+use crate::listener::listen;
 use jarust_core::prelude::*;
+use jarust_interface::event_channel::EventChannelConfig;
+use jarust_interface::plugin_id;
 use std::ops::Deref;
 use std::time::Duration;
 use tokio::sync::mpsc;
@@ -13,21 +16,23 @@ pub trait VideoRoom: Attach {
     async fn attach_video_room(
         &self,
         timeout: Duration,
-    ) -> Result<(Self::Handle, mpsc::UnboundedReceiver<Self::Event>), jarust_interface::Error> {
-        let (handle, mut receiver) = self
-            .attach("janus.plugin.videoroom".to_string(), timeout)
+    ) -> Result<(Self::Handle, mpsc::Receiver<Self::Event>), jarust_interface::Error> {
+        let (handle, receiver) = self
+            .attach(plugin_id::VIDEO_ROOM.to_string(), timeout)
             .await?;
-        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-        let task = jarust_rt::spawn("videoroom listener", async move {
-            while let Some(rsp) = receiver.recv().await {
-                if let Ok(event) = rsp.try_into() {
-                    let _ = tx.send(event);
-                };
-            }
-        });
-        let mut handle: Self::Handle = handle.into();
-        handle.assign_task(task);
-        Ok((handle, rx))
+        Ok(listen("videoroom listener", handle, receiver))
+    }
+
+    /// Same as [`attach_video_room`](Self::attach_video_room), with an event channel of its own
+    async fn attach_video_room_with_channel(
+        &self,
+        channel: EventChannelConfig,
+        timeout: Duration,
+    ) -> Result<(Self::Handle, mpsc::Receiver<Self::Event>), jarust_interface::Error> {
+        let (handle, receiver) = self
+            .attach_with_channel(plugin_id::VIDEO_ROOM.to_string(), channel, timeout)
+            .await?;
+        Ok(listen("videoroom listener", handle, receiver))
     }
 }
 
@@ -2,16 +2,28 @@
 //!
 //! A runtime abstraction crate for jarust.
 //!
+//! Tasks and timers run on tokio natively, and on the browser's event loop when targeting wasm.
+//!
 
-#[cfg(not(any(feature = "tokio-rt")))]
+#[cfg(all(not(target_family = "wasm"), not(feature = "tokio-rt")))]
 compile_error!("Feature \"tokio-rt\" must be enabled for this crate.");
 
-#[cfg(feature = "tokio-rt")]
+#[cfg(all(target_family = "wasm", not(feature = "wasm-rt")))]
+compile_error!("Feature \"wasm-rt\" must be enabled for this crate when targeting wasm.");
+
+#[cfg(all(feature = "tokio-rt", not(target_family = "wasm")))]
 #[path = "tokio_rt.rs"]
 pub mod jatask;
 
+#[cfg(all(feature = "wasm-rt", target_family = "wasm"))]
+#[path = "wasm_rt.rs"]
+pub mod jatask;
+
 use futures_util::Future;
+pub use jatask::Instant;
 pub use jatask::JaTask;
+pub use jatask::SystemTime;
+use std::time::Duration;
 
 /// Spawns a new task. The name field is for debugging purposes only.
 #[tracing::instrument(level = tracing::Level::TRACE, skip_all, fields(task_name = name))]
@@ -23,3 +35,28 @@ where
     tracing::trace!("Spawning task");
     jatask::spawn(name, future)
 }
+
+/// Waits until the duration has elapsed.
+pub async fn sleep(duration: Duration) {
+    jatask::sleep(duration).await
+}
+
+/// Waits on the future for at most the duration, it's dropped if it didn't complete in time.
+pub async fn timeout<F>(duration: Duration, future: F) -> Result<F::Output, Elapsed>
+where
+    F: Future,
+{
+    jatask::timeout(duration, future).await
+}
+
+/// The future given to [`timeout`] didn't complete in time
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Elapsed;
+
+impl std::fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
@@ -1,6 +1,11 @@
+use crate::Elapsed;
 use futures_util::Future;
+use std::time::Duration;
 use tokio::task::AbortHandle;
 
+pub use std::time::SystemTime;
+pub use tokio::time::Instant;
+
 pub fn spawn<F>(name: &str, future: F) -> JaTask
 where
     F: Future + Send + 'static,
@@ -32,3 +37,16 @@ impl Drop for JaTask {
         self.cancel();
     }
 }
+
+pub async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+pub async fn timeout<F>(duration: Duration, future: F) -> Result<F::Output, Elapsed>
+where
+    F: Future,
+{
+    tokio::time::timeout(duration, future)
+        .await
+        .map_err(|_| Elapsed)
+}
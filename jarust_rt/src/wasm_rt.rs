@@ -0,0 +1,117 @@
+use crate::Elapsed;
+use futures_util::future::AbortHandle;
+use futures_util::future::Abortable;
+use futures_util::future::Either;
+use futures_util::Future;
+use send_wrapper::SendWrapper;
+use std::time::Duration;
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::JsFuture;
+
+pub use web_time::Instant;
+pub use web_time::SystemTime;
+
+#[wasm_bindgen]
+extern "C" {
+    // Available on the global scope of windows, workers and node alike
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(handler: &js_sys::Function, timeout: i32) -> JsValue;
+}
+
+/// Tasks are spawned on the current thread's event loop, the `Send` bounds are kept for the code
+/// spawning them to stay portable
+pub fn spawn<F>(name: &str, future: F) -> JaTask
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let (handle, registration) = AbortHandle::new_pair();
+    wasm_bindgen_futures::spawn_local(async move {
+        let _ = Abortable::new(future, registration).await;
+    });
+    JaTask {
+        inner: handle,
+        task_name: name.to_owned(),
+    }
+}
+
+#[derive(Debug)]
+pub struct JaTask {
+    inner: AbortHandle,
+    pub task_name: String,
+}
+
+impl JaTask {
+    pub fn cancel(&self) {
+        self.inner.abort();
+    }
+}
+
+impl Drop for JaTask {
+    #[tracing::instrument(level = tracing::Level::TRACE, skip_all)]
+    fn drop(&mut self) {
+        tracing::trace!(task_name = self.task_name, "Dropping task");
+        self.cancel();
+    }
+}
+
+pub async fn sleep(duration: Duration) {
+    let timeout = i32::try_from(duration.as_millis()).unwrap_or(i32::MAX);
+    // Promises only live on the thread they were created on, which is the only thread here
+    let timer = SendWrapper::new(JsFuture::from(js_sys::Promise::new(&mut |resolve, _| {
+        set_timeout(&resolve, timeout);
+    })));
+    let _ = timer.await;
+}
+
+pub async fn timeout<F>(duration: Duration, future: F) -> Result<F::Output, Elapsed>
+where
+    F: Future,
+{
+    let future = std::pin::pin!(future);
+    let elapsed = std::pin::pin!(sleep(duration));
+    match futures_util::future::select(future, elapsed).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right(_) => Err(Elapsed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test]
+    async fn it_should_time_out_after_the_duration() {
+        let started = crate::Instant::now();
+        let result = crate::timeout(
+            Duration::from_millis(20),
+            crate::sleep(Duration::from_secs(5)),
+        )
+        .await;
+        assert_eq!(result, Err(crate::Elapsed));
+        assert!(started.elapsed() >= Duration::from_millis(20));
+
+        let result = crate::timeout(Duration::from_secs(5), async { 7 }).await;
+        assert_eq!(result, Ok(7));
+    }
+
+    #[wasm_bindgen_test]
+    async fn it_should_stop_cancelled_tasks() {
+        let ran = Arc::new(AtomicBool::new(false));
+        let task = crate::spawn("Test task", {
+            let ran = ran.clone();
+            async move {
+                crate::sleep(Duration::from_millis(20)).await;
+                ran.store(true, Ordering::Relaxed);
+            }
+        });
+        task.cancel();
+        crate::sleep(Duration::from_millis(50)).await;
+        assert!(!ran.load(Ordering::Relaxed));
+    }
+}